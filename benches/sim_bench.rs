@@ -1,9 +1,11 @@
 use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
 use glam::Vec2;
-use ptcl_rs::core::{ParticleSpawn, ParticleSystem, ParticleTypeTrait, SplineState};
+use ptcl_rs::core::{
+    Flocking, ParticleSpawn, ParticleSystem, ParticleTypeTrait, Rect, SplineState,
+};
 use std::hint::black_box;
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 enum BenchType {
     Burst,
     Smoke,
@@ -104,6 +106,82 @@ fn seed_rich_system(count: u32) -> ParticleSystem<BenchType> {
     ps
 }
 
+fn seed_static_system(count: u32) -> ParticleSystem<BenchType> {
+    let mut ps = ParticleSystem::new();
+    ps.reserve_particles(count);
+
+    for i in 0..count {
+        let base = Vec2::new((i % 512) as f32, ((i / 512) % 512) as f32);
+        ps.spawn(ParticleSpawn::new(
+            BenchType::Burst,
+            STEADY_COUNTER,
+            base,
+            Vec2::splat(6.0),
+        ));
+    }
+
+    ps
+}
+
+// Cycles through 16 channel combinations, most of them outside the old
+// match fast paths.
+fn seed_mixed_combos_system(count: u32) -> ParticleSystem<BenchType> {
+    let mut ps = ParticleSystem::new();
+    ps.reserve_particles(count);
+
+    for i in 0..count {
+        let base = Vec2::new((i % 512) as f32, ((i / 512) % 512) as f32);
+        let combo = i % 16;
+        let mut spawn =
+            ParticleSpawn::new(BenchType::Smoke, STEADY_COUNTER, base, Vec2::splat(6.0));
+        if (combo & 1) != 0 {
+            spawn = spawn
+                .with_velocity(Vec2::new(0.02, -0.03))
+                .with_acceleration(Vec2::new(0.0, 0.0002));
+        }
+        if (combo & 2) != 0 {
+            spawn = spawn.with_size_velocity(0.02);
+        }
+        if (combo & 4) != 0 {
+            spawn = spawn.with_rotation_velocity(0.15);
+        }
+        if (combo & 8) != 0 {
+            spawn = spawn
+                .with_alpha_velocity(-0.0001)
+                .with_alpha_acceleration(0.000001);
+        }
+        ps.spawn(spawn);
+    }
+
+    ps
+}
+
+// Short-lived sparks, either in the stateless analytic lane or stepped.
+fn seed_spark_system(count: u32, analytic: bool) -> ParticleSystem<BenchType> {
+    let mut ps = ParticleSystem::new();
+    ps.reserve_particles(count);
+    ps.reserve_analytic_particles(count);
+
+    for i in 0..count {
+        let angle = (i % 360) as f32 * 0.0174533;
+        let mut spawn = ParticleSpawn::new(
+            BenchType::Burst,
+            STEADY_COUNTER,
+            Vec2::new(256.0, 256.0),
+            Vec2::splat(3.0),
+        )
+        .with_velocity(Vec2::new(angle.cos(), angle.sin()) * 2.0)
+        .with_acceleration(Vec2::new(0.0, 0.05))
+        .with_alpha_velocity(-0.0001);
+        if analytic {
+            spawn = spawn.with_analytic();
+        }
+        ps.spawn(spawn);
+    }
+
+    ps
+}
+
 fn seed_burst_system() -> ParticleSystem<BenchType> {
     let mut ps = ParticleSystem::new();
     ps.reserve_particles(100_000);
@@ -173,6 +251,16 @@ fn bench_step_steady_50k(c: &mut Criterion) {
     });
 }
 
+fn bench_step_steady_500k(c: &mut Criterion) {
+    c.bench_function("step_steady_500k", |b| {
+        let mut ps = seed_steady_system(500_000);
+        b.iter(|| {
+            ps.step();
+            black_box(ps.len());
+        });
+    });
+}
+
 fn bench_step_linear_50k(c: &mut Criterion) {
     c.bench_function("step_linear_50k", |b| {
         let mut ps = seed_linear_system(50_000);
@@ -193,6 +281,49 @@ fn bench_step_rich_50k(c: &mut Criterion) {
     });
 }
 
+fn bench_step_static_50k(c: &mut Criterion) {
+    c.bench_function("step_static_50k", |b| {
+        let mut ps = seed_static_system(50_000);
+        b.iter(|| {
+            ps.step();
+            black_box(ps.len());
+        });
+    });
+}
+
+fn bench_step_mixed_combos_50k(c: &mut Criterion) {
+    c.bench_function("step_mixed_combos_50k", |b| {
+        let mut ps = seed_mixed_combos_system(50_000);
+        b.iter(|| {
+            ps.step();
+            black_box(ps.len());
+        });
+    });
+}
+
+fn bench_step_analytic_50k(c: &mut Criterion) {
+    c.bench_function("step_analytic_50k", |b| {
+        let mut ps = seed_spark_system(50_000, true);
+        b.iter(|| {
+            ps.step();
+            black_box(ps.len());
+        });
+    });
+}
+
+fn bench_advance_600_50k(c: &mut Criterion) {
+    c.bench_function("advance_600_50k", |b| {
+        b.iter_batched(
+            || seed_spark_system(50_000, false),
+            |mut ps| {
+                ps.advance(600);
+                black_box(ps.len());
+            },
+            BatchSize::SmallInput,
+        );
+    });
+}
+
 fn bench_burst_100k_lifecycle(c: &mut Criterion) {
     c.bench_function("burst_100k_lifecycle", |b| {
         b.iter_batched(
@@ -240,16 +371,131 @@ fn bench_spawn_50k_batch(c: &mut Criterion) {
     });
 }
 
+// Queries land in the middle of the seeded block, about 4k particles each.
+const SPATIAL_CELL_SIZE: f32 = 16.0;
+const QUERY_CENTER: Vec2 = Vec2::new(256.0, 48.0);
+const QUERY_SIZE: f32 = 64.0;
+
+fn bench_step_steady_50k_spatial_index(c: &mut Criterion) {
+    c.bench_function("step_steady_50k_spatial_index", |b| {
+        let mut ps = seed_steady_system(50_000);
+        ps.set_spatial_index(SPATIAL_CELL_SIZE);
+        b.iter(|| {
+            ps.step();
+            black_box(ps.len());
+        });
+    });
+}
+
+fn bench_query_rect_50k(c: &mut Criterion, name: &str, indexed: bool) {
+    c.bench_function(name, |b| {
+        let mut ps = seed_steady_system(50_000);
+        if indexed {
+            ps.set_spatial_index(SPATIAL_CELL_SIZE);
+        }
+        let rect = Rect::from_center_size(QUERY_CENTER, Vec2::splat(QUERY_SIZE));
+        b.iter(|| {
+            let mut alpha = 0.0;
+            ps.for_each_particle_in_rect(black_box(rect), |particle| alpha += particle.alpha);
+            black_box(alpha);
+        });
+    });
+}
+
+fn bench_query_rect_50k_scan(c: &mut Criterion) {
+    bench_query_rect_50k(c, "query_rect_50k_scan", false);
+}
+
+fn bench_query_rect_50k_indexed(c: &mut Criterion) {
+    bench_query_rect_50k(c, "query_rect_50k_indexed", true);
+}
+
+fn bench_count_circle_50k(c: &mut Criterion, name: &str, indexed: bool) {
+    c.bench_function(name, |b| {
+        let mut ps = seed_steady_system(50_000);
+        if indexed {
+            ps.set_spatial_index(SPATIAL_CELL_SIZE);
+        }
+        b.iter(|| {
+            black_box(ps.count_in_circle(black_box(QUERY_CENTER), QUERY_SIZE * 0.5));
+        });
+    });
+}
+
+fn bench_count_circle_50k_scan(c: &mut Criterion) {
+    bench_count_circle_50k(c, "count_circle_50k_scan", false);
+}
+
+fn bench_count_circle_50k_indexed(c: &mut Criterion) {
+    bench_count_circle_50k(c, "count_circle_50k_indexed", true);
+}
+
+// Agents 4 units apart with headings spread around the circle, so each one
+// starts with about 28 flockmates in range.
+fn seed_flock_system(count: u32) -> ParticleSystem<BenchType> {
+    let mut ps = ParticleSystem::new();
+    ps.reserve_particles(count);
+
+    for i in 0..count {
+        let base = Vec2::new((i % 100) as f32, (i / 100) as f32) * 4.0;
+        let heading = (i as f32 * 2.399_963).sin_cos();
+        ps.spawn(
+            ParticleSpawn::new(BenchType::Burst, STEADY_COUNTER, base, Vec2::splat(2.0))
+                .with_velocity(Vec2::new(heading.1, heading.0) * 0.5),
+        );
+    }
+
+    ps
+}
+
+fn bench_step_flock_10k(c: &mut Criterion, name: &str, flocking: bool) {
+    c.bench_function(name, |b| {
+        let mut ps = seed_flock_system(10_000);
+        if flocking {
+            ps.set_flocking(
+                BenchType::Burst,
+                Flocking::new(12.0, 0.5, 0.05, 0.005)
+                    .with_max_neighbors(16)
+                    .with_max_speed(1.5),
+            );
+        }
+        b.iter(|| {
+            ps.step();
+            black_box(ps.len());
+        });
+    });
+}
+
+fn bench_step_flock_10k_unsteered(c: &mut Criterion) {
+    bench_step_flock_10k(c, "step_flock_10k_unsteered", false);
+}
+
+fn bench_step_flock_10k_flocking(c: &mut Criterion) {
+    bench_step_flock_10k(c, "step_flock_10k_flocking", true);
+}
+
 criterion_group!(
     benches,
     bench_step_100,
     bench_step_1k,
     bench_step_steady_10k,
     bench_step_steady_50k,
+    bench_step_steady_500k,
     bench_step_linear_50k,
     bench_step_rich_50k,
+    bench_step_static_50k,
+    bench_step_mixed_combos_50k,
+    bench_step_analytic_50k,
+    bench_advance_600_50k,
     bench_burst_100k_lifecycle,
     bench_spawn_50k_single,
-    bench_spawn_50k_batch
+    bench_spawn_50k_batch,
+    bench_step_steady_50k_spatial_index,
+    bench_query_rect_50k_scan,
+    bench_query_rect_50k_indexed,
+    bench_count_circle_50k_scan,
+    bench_count_circle_50k_indexed,
+    bench_step_flock_10k_unsteered,
+    bench_step_flock_10k_flocking
 );
 criterion_main!(benches);
@@ -2,7 +2,7 @@ use glam::Vec2;
 use ptcl_rs::core::{ParticleSpawn, ParticleSystem, ParticleTypeTrait, SplineState};
 use std::hint::black_box;
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 enum ProfileType {
     Burst,
     Smoke,
@@ -0,0 +1,318 @@
+use std::mem;
+
+use super::ballistic_lanes::BallisticLanes;
+use super::death::DeathCause;
+use super::handle::{ParticleLocation, SPLINE_LANE};
+use super::interpolation::PreviousState;
+use super::particle_model::{ParticlePayload, ParticleTypeTrait, SplineParticle};
+use super::particle_system::ParticleSystem;
+use super::particle_view::ParticleBatch;
+use super::time_scale::{finish_spline_particle_scaled, integrate_spline_particle_scaled};
+use super::trail::NO_TRAIL;
+
+// Where in `step` an affector runs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AffectorStage {
+    // Before anything moves.
+    PreIntegrate,
+    // After velocities and accelerations are applied, before spline particles
+    // are pulled onto their curve.
+    PostIntegrate,
+    // At the end of the step, with every particle in its final place.
+    PostSpline,
+}
+
+// A system-level behavior run every step over each lane's particles. Batches
+// never mix lanes, so an affector sees one call per ballistic sub-lane plus
+// one for the spline lane.
+pub trait Affector<T, U = ()>: Send + Sync
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    fn affect(&mut self, particles: &mut ParticleBatch<'_, T, U>);
+}
+
+impl<T, U, F> Affector<T, U> for F
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+    F: FnMut(&mut ParticleBatch<'_, T, U>) + Send + Sync,
+{
+    fn affect(&mut self, particles: &mut ParticleBatch<'_, T, U>) {
+        self(particles)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct AffectorId(pub(crate) u32);
+
+impl AffectorId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+type AffectorSlot<T, U> = Option<(AffectorStage, Box<dyn Affector<T, U>>)>;
+
+// Affectors in the order they were added; removal leaves a hole so ids stay
+// valid.
+pub(crate) struct AffectorList<T, U>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    slots: Vec<AffectorSlot<T, U>>,
+    active: bool,
+}
+
+impl<T, U> AffectorList<T, U>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    pub(crate) fn new() -> Self {
+        Self {
+            slots: Vec::new(),
+            active: false,
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn is_active(&self) -> bool {
+        self.active
+    }
+
+    fn refresh(&mut self) {
+        self.active = self.slots.iter().any(Option::is_some);
+    }
+
+    // Returns whether any affector ran at `stage`.
+    fn run(
+        &mut self,
+        stage: AffectorStage,
+        ballistic: &mut BallisticLanes<T, U>,
+        spline_particles: &mut [SplineParticle<T, U>],
+    ) -> bool {
+        let mut ran = false;
+        for (_, affector) in self
+            .slots
+            .iter_mut()
+            .flatten()
+            .filter(|(affector_stage, _)| *affector_stage == stage)
+        {
+            for lane in &mut ballistic.lanes {
+                if !lane.hot.is_empty() {
+                    affector.affect(&mut ParticleBatch::ballistic(&mut lane.hot, &mut lane.cold));
+                }
+            }
+            if !spline_particles.is_empty() {
+                affector.affect(&mut ParticleBatch::spline(spline_particles));
+            }
+            ran = true;
+        }
+        ran
+    }
+}
+
+impl<T, U> ParticleSystem<T, U>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    // Affectors run in the order they were added within each stage. Like time
+    // controls, any affector moves `step` onto a path that steps every
+    // particle individually and keeps new spawns out of the analytic lane.
+    pub fn add_affector(
+        &mut self,
+        stage: AffectorStage,
+        affector: impl Affector<T, U> + 'static,
+    ) -> AffectorId {
+        let id = AffectorId(self.affectors.slots.len() as u32);
+        self.affectors.slots.push(Some((stage, Box::new(affector))));
+        self.affectors.refresh();
+        self.materialize_analytic();
+        id
+    }
+
+    pub fn remove_affector(&mut self, id: AffectorId) {
+        self.affectors.slots[id.index()] = None;
+        self.affectors.refresh();
+    }
+
+    pub fn clear_affectors(&mut self) {
+        self.affectors.slots.clear();
+        self.affectors.refresh();
+    }
+
+    // Affectors may give velocity to particles in sub-lanes that never
+    // integrate it, so those move to a lane that does after each stage.
+    fn run_affectors(&mut self, stage: AffectorStage) {
+        if self
+            .affectors
+            .run(stage, &mut self.ballistic, &mut self.spline_particles)
+        {
+            self.ballistic.promote_moving(&mut self.handles);
+        }
+    }
+
+    // `step_time_scaled` with the spline step split around `PostIntegrate`.
+    // Without time controls every scale is `1`, which matches `step` exactly.
+    pub(crate) fn step_affected(&mut self) {
+        self.run_affectors(AffectorStage::PreIntegrate);
+
+        self.ballistic.step_scaled(
+            &mut self.trails,
+            &mut self.types,
+            &mut self.handles,
+            &mut self.deaths,
+            &self.time,
+        );
+
+        let mut started = mem::take(&mut self.spline_scratch);
+        started.clear();
+        let mut i = 0;
+        while i < self.spline_particles.len() {
+            let particle = &mut self.spline_particles[i];
+            if particle.core.counter == 0 {
+                self.remove_particle(ParticleLocation::new(SPLINE_LANE, i), DeathCause::Expired);
+                continue;
+            }
+
+            if self.interpolation {
+                self.spline_previous[i] = PreviousState::from(&particle.core);
+            }
+            let core = &particle.core;
+            let scale = self
+                .time
+                .scale(&self.types, core.type_slot, core.draw_layer, core.pos);
+            started.push((integrate_spline_particle_scaled(particle, scale), scale));
+            i += 1;
+        }
+
+        self.run_affectors(AffectorStage::PostIntegrate);
+
+        for (particle, &(previous_pos, scale)) in self.spline_particles.iter_mut().zip(&started) {
+            finish_spline_particle_scaled(
+                particle,
+                previous_pos,
+                &self.spline_paths,
+                &self.anchors,
+                scale,
+            );
+            if particle.core.trail != NO_TRAIL && scale > 0.0 {
+                self.trails.record(particle.core.trail, particle.core.pos);
+            }
+        }
+        self.spline_scratch = started;
+
+        self.run_affectors(AffectorStage::PostSpline);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use glam::Vec2;
+
+    use super::*;
+    use crate::core::{ParticleSpawn, SplineState};
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    struct Leaf;
+    impl ParticleTypeTrait for Leaf {}
+
+    fn leaf(pos: Vec2) -> ParticleSpawn<Leaf> {
+        ParticleSpawn::new(Leaf, 10, pos, Vec2::ONE)
+    }
+
+    type Log = Arc<Mutex<Vec<(&'static str, f32)>>>;
+
+    // Logs the first particle's x under `label` each call.
+    fn logger(log: &Log, label: &'static str) -> impl Affector<Leaf> + 'static {
+        let log = Arc::clone(log);
+        move |particles: &mut ParticleBatch<'_, Leaf>| {
+            let x = particles.get_mut(0).pos().x;
+            log.lock().unwrap().push((label, x));
+        }
+    }
+
+    #[test]
+    fn stages_run_in_step_order() {
+        let mut system = ParticleSystem::new();
+        system.spawn(leaf(Vec2::ZERO).with_velocity(Vec2::X));
+        let log = Log::default();
+        system.add_affector(AffectorStage::PostSpline, logger(&log, "post spline"));
+        system.add_affector(AffectorStage::PreIntegrate, logger(&log, "pre"));
+        system.add_affector(AffectorStage::PostIntegrate, logger(&log, "post"));
+        system.add_affector(AffectorStage::PreIntegrate, logger(&log, "pre 2"));
+        system.step();
+
+        assert_eq!(
+            *log.lock().unwrap(),
+            [
+                ("pre", 0.0),
+                ("pre 2", 0.0),
+                ("post", 1.0),
+                ("post spline", 1.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn batches_never_mix_lanes() {
+        let mut system = ParticleSystem::new();
+        system.spawn_batch([
+            leaf(Vec2::ZERO),
+            leaf(Vec2::ZERO).with_velocity(Vec2::X),
+            leaf(Vec2::ZERO).with_velocity(Vec2::X),
+            leaf(Vec2::ZERO).with_spline(SplineState {
+                t: 0.0,
+                strength: 1.0,
+                point_1: Vec2::ZERO,
+                point_2: Vec2::X,
+                point_3: Vec2::Y,
+            }),
+        ]);
+        let batches = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&batches);
+        system.add_affector(
+            AffectorStage::PreIntegrate,
+            move |particles: &mut ParticleBatch<'_, Leaf>| {
+                seen.lock()
+                    .unwrap()
+                    .push((particles.is_spline(), particles.len()));
+            },
+        );
+        system.step();
+
+        let mut batches = batches.lock().unwrap().clone();
+        batches.sort_unstable();
+        assert_eq!(batches, [(false, 1), (false, 2), (true, 1)]);
+    }
+
+    #[test]
+    fn velocity_from_an_affector_moves_static_particles() {
+        let mut system = ParticleSystem::new();
+        let resting = system.spawn_with_handle(leaf(Vec2::ZERO)).unwrap();
+        // Existing analytic particles are stepped from here on.
+        let analytic = system
+            .spawn_with_handle(leaf(Vec2::ZERO).with_velocity(Vec2::Y).with_analytic())
+            .unwrap();
+        let wind = system.add_affector(
+            AffectorStage::PreIntegrate,
+            |particles: &mut ParticleBatch<'_, Leaf>| {
+                particles.for_each(|p| p.set_velocity(p.velocity() + Vec2::X));
+            },
+        );
+        system.step();
+        assert_eq!(system.get(resting).unwrap().pos, Vec2::X);
+        assert_eq!(system.get(analytic).unwrap().pos, Vec2::new(1.0, 1.0));
+
+        system.remove_affector(wind);
+        system.step();
+        assert_eq!(system.get(resting).unwrap().pos, Vec2::new(2.0, 0.0));
+        assert_eq!(system.get(analytic).unwrap().pos, Vec2::new(2.0, 2.0));
+    }
+}
@@ -0,0 +1,572 @@
+use std::ops::{Add, Mul};
+
+use glam::Vec2;
+
+use super::death::{DeathCause, DeathLog};
+use super::handle::{HandleTable, ParticleLocation, ANALYTIC_LANE};
+use super::interpolation::PreviousState;
+use super::particle_model::{
+    facing_rotation, ParticleCore, ParticlePayload, ParticleRenderData, ParticleTypeTrait,
+    HAS_ACCELERATION, HAS_ALPHA_ACCELERATION, HAS_ALPHA_VELOCITY, HAS_ROTATION_ACCELERATION,
+    HAS_ROTATION_VELOCITY, HAS_SIZE_ACCELERATION, HAS_SIZE_VELOCITY, HAS_VELOCITY,
+};
+use super::particle_system::{retire_particle, ParticleSystem, TICK_ORIGIN};
+use super::particle_types::TypeTable;
+use super::trail::{TrailPool, NO_TRAIL};
+
+impl<T, U> ParticleSystem<T, U>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    // Steps taken since the system was created, wrapping.
+    pub fn tick(&self) -> u32 {
+        self.tick.wrapping_sub(TICK_ORIGIN)
+    }
+
+    // Equivalent to calling `step` `steps` times. Ballistic particles without
+    // trails and whose size/alpha clamping is exact in closed form jump
+    // straight to the result, matching `step` up to float rounding; everything
+    // else, including the whole spline lane, is stepped. Under time controls,
+    // affectors or shockwaves in flight there is no closed form and every step
+    // runs.
+    pub fn advance(&mut self, steps: u32) {
+        if steps == 0 {
+            return;
+        }
+
+        // Hooked types need the same between-step bookkeeping as `step`.
+        if self.stepwise() || T::STEP_HOOK || !self.shockwaves.is_empty() {
+            for _ in 0..steps {
+                self.step_particles();
+            }
+            self.deaths.dying.clear();
+            if self.interpolation {
+                self.ballistic.sync_previous();
+                self.sync_spline_previous();
+            }
+            self.refresh_spatial_index();
+            return;
+        }
+
+        self.tick = self.tick.wrapping_add(steps);
+        self.deaths.dying.clear();
+        self.analytic.retire_expired(
+            self.tick,
+            &mut self.trails,
+            &mut self.types,
+            &mut self.handles,
+            &mut self.deaths,
+        );
+        self.ballistic.advance(
+            steps,
+            &mut self.trails,
+            &mut self.types,
+            &mut self.handles,
+            &mut self.deaths,
+        );
+        for _ in 0..steps {
+            self.step_spline_lane();
+        }
+        // Particles that expired during the jump have no last frame to draw.
+        self.deaths.dying.clear();
+        // Interpolating across the jump would smear every particle over it.
+        if self.interpolation {
+            self.sync_spline_previous();
+        }
+        self.refresh_spatial_index();
+    }
+
+    // Advances to the absolute `tick`. Stepped state cannot run backwards, so
+    // scrubbing back means clearing, respawning and seeking forward again.
+    pub fn seek(&mut self, tick: u32) {
+        let steps = tick.wrapping_sub(self.tick());
+        debug_assert!(
+            (steps as i32) >= 0,
+            "seek can only move forward ({} -> {})",
+            self.tick(),
+            tick
+        );
+        if (steps as i32) > 0 {
+            self.advance(steps);
+        }
+    }
+
+    pub fn reserve_analytic_particles(&mut self, additional: u32) {
+        self.analytic.reserve(additional as usize);
+    }
+
+    // Evaluates every analytic particle in place and moves it to the
+    // ballistic lane, for paths that step particles individually.
+    pub(crate) fn materialize_analytic(&mut self) {
+        for (core, previous) in self.analytic.iter_with_previous(self.tick) {
+            let location = self.ballistic.push_with_previous(core, previous);
+            self.handles.relocate(core.handle, location);
+        }
+        self.analytic.clear();
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct AnalyticTiming {
+    pub(crate) spawn_tick: u32,
+    pub(crate) lifetime: u32,
+}
+
+impl AnalyticTiming {
+    // Steps taken since spawn, or `None` once `step` would have removed it.
+    #[inline(always)]
+    pub(crate) fn age(&self, tick: u32) -> Option<u32> {
+        let age = tick.wrapping_sub(self.spawn_tick);
+        (age <= self.lifetime).then_some(age)
+    }
+}
+
+// Stateless lane: particles are stored exactly as spawned and evaluated in
+// closed form for the current tick. Stepping only drops expired entries, which
+// reads the compact timing array and never touches the spawn records.
+#[derive(Clone, Debug)]
+pub(crate) struct AnalyticLane<T, U>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    pub(crate) timing: Vec<AnalyticTiming>,
+    pub(crate) spawns: Vec<ParticleCore<T, U>>,
+    // Room the lane grows to once it fills, from `reserve_each`.
+    reserved: usize,
+}
+
+impl<T, U> AnalyticLane<T, U>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    pub(crate) fn new() -> Self {
+        Self {
+            timing: Vec::new(),
+            spawns: Vec::new(),
+            reserved: 0,
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.timing.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.timing.is_empty()
+    }
+
+    pub(crate) fn capacity(&self) -> usize {
+        self.timing.capacity().min(self.spawns.capacity())
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.timing.clear();
+        self.spawns.clear();
+    }
+
+    pub(crate) fn reserve(&mut self, additional: usize) {
+        self.timing.reserve(additional);
+        self.spawns.reserve(additional);
+    }
+
+    // Sizes the lane for `count` particles now if it is in use, or when its
+    // first particle arrives otherwise.
+    pub(crate) fn reserve_each(&mut self, count: usize) {
+        self.reserved = self.reserved.max(count);
+        if self.capacity() > 0 {
+            self.reserve(count.saturating_sub(self.len()));
+        }
+    }
+
+    #[cold]
+    fn grow(&mut self) {
+        self.reserve(self.reserved.saturating_sub(self.len()).max(1));
+    }
+
+    // Accepts `core` if it is exact in closed form over its whole lifetime.
+    #[inline(always)]
+    pub(crate) fn try_push(&mut self, core: ParticleCore<T, U>) -> Result<(), ParticleCore<T, U>> {
+        if core.trail != NO_TRAIL || !closed_form_exact(&core, core.counter) {
+            return Err(core);
+        }
+
+        if self.len() == self.capacity() {
+            self.grow();
+        }
+        self.timing.push(AnalyticTiming {
+            spawn_tick: core.spawn_tick,
+            lifetime: core.counter,
+        });
+        self.spawns.push(core);
+        Ok(())
+    }
+
+    #[inline(always)]
+    pub(crate) fn swap_remove(
+        &mut self,
+        index: usize,
+        handles: &mut HandleTable,
+    ) -> ParticleCore<T, U> {
+        self.timing.swap_remove(index);
+        let core = self.spawns.swap_remove(index);
+        if let Some(moved) = self.spawns.get(index) {
+            handles.relocate(moved.handle, ParticleLocation::new(ANALYTIC_LANE, index));
+        }
+        core
+    }
+
+    pub(crate) fn retire_expired(
+        &mut self,
+        tick: u32,
+        trails: &mut TrailPool,
+        types: &mut TypeTable<T>,
+        handles: &mut HandleTable,
+        deaths: &mut DeathLog<T, U>,
+    ) {
+        let mut i = 0;
+        while i < self.timing.len() {
+            if self.timing[i].age(tick).is_none() {
+                let core = self.swap_remove(i, handles);
+                if deaths.is_recording() {
+                    let mut last = core;
+                    advance_closed_form(&mut last, core.counter);
+                    deaths.record(
+                        (&last).into(),
+                        handles.handle(core.handle),
+                        DeathCause::Expired,
+                    );
+                }
+                retire_particle(
+                    core.trail,
+                    core.type_slot,
+                    core.handle,
+                    trails,
+                    types,
+                    handles,
+                );
+                continue;
+            }
+            i += 1;
+        }
+    }
+
+    // `BallisticLanes::retain` over the states at `tick`.
+    pub(crate) fn retain(
+        &mut self,
+        tick: u32,
+        mut keep: impl FnMut(ParticleRenderData<T, U>) -> bool,
+        trails: &mut TrailPool,
+        types: &mut TypeTable<T>,
+        handles: &mut HandleTable,
+        deaths: &mut DeathLog<T, U>,
+    ) -> usize {
+        let mut removed = 0;
+        let mut i = 0;
+        while i < self.timing.len() {
+            let mut current = self.spawns[i];
+            let age = self.timing[i].age(tick).unwrap_or(current.counter);
+            advance_closed_form(&mut current, age);
+            let particle = ParticleRenderData::from(&current);
+            if !keep(particle) {
+                let core = self.swap_remove(i, handles);
+                if deaths.is_recording() {
+                    deaths.record(particle, handles.handle(core.handle), DeathCause::Killed);
+                }
+                retire_particle(
+                    core.trail,
+                    core.type_slot,
+                    core.handle,
+                    trails,
+                    types,
+                    handles,
+                );
+                removed += 1;
+                continue;
+            }
+            i += 1;
+        }
+        removed
+    }
+
+    // Current state paired with the state one step earlier, or with itself for
+    // particles spawned since the last step.
+    pub(crate) fn iter_with_previous(
+        &self,
+        tick: u32,
+    ) -> impl Iterator<Item = (ParticleCore<T, U>, PreviousState)> + '_ {
+        self.timing
+            .iter()
+            .zip(self.spawns.iter())
+            .filter_map(move |(timing, spawn)| {
+                timing.age(tick).map(|age| {
+                    let mut previous = *spawn;
+                    advance_closed_form(&mut previous, age.saturating_sub(1));
+                    let mut current = previous;
+                    if age > 0 {
+                        advance_closed_form(&mut current, 1);
+                    }
+                    (current, PreviousState::from(&previous))
+                })
+            })
+    }
+
+    pub(crate) fn iter_at(&self, tick: u32) -> impl Iterator<Item = ParticleCore<T, U>> + '_ {
+        self.iter_indexed_at(tick).map(|(_, core)| core)
+    }
+
+    // `iter_at` with each particle's index in the lane, which skips expired
+    // entries still awaiting `retire_expired`.
+    pub(crate) fn iter_indexed_at(
+        &self,
+        tick: u32,
+    ) -> impl Iterator<Item = (usize, ParticleCore<T, U>)> + '_ {
+        self.timing
+            .iter()
+            .zip(self.spawns.iter())
+            .enumerate()
+            .filter_map(move |(index, (timing, spawn))| {
+                timing.age(tick).map(|age| {
+                    let mut core = *spawn;
+                    advance_closed_form(&mut core, age);
+                    (index, core)
+                })
+            })
+    }
+}
+
+// `x` and `v` after `n` steps of `v += a; x += v`.
+#[inline(always)]
+fn integrate<V>(x: V, v: V, a: V, n: f32) -> (V, V)
+where
+    V: Copy + Add<Output = V> + Mul<f32, Output = V>,
+{
+    (x + v * n + a * (n * (n + 1.0) * 0.5), v + a * n)
+}
+
+// Whether clamping into `lo..=hi` after every step, as `step` does, lands on
+// the same value as clamping once after `n` steps.
+fn clamp_exact(x: f32, v: f32, a: f32, n: f32, lo: f32, hi: f32) -> bool {
+    // Increments that never change sign pin the value at whichever bound it
+    // reaches, as long as it starts in range.
+    let first = v + a;
+    let last = v + a * n;
+    let monotone = (first >= 0.0 && last >= 0.0) || (first <= 0.0 && last <= 0.0);
+    if monotone && x >= lo && x <= hi {
+        return true;
+    }
+
+    // Otherwise the unclamped path must stay in range over steps `1..=n`.
+    let at = |k: f32| x + v * k + a * (k * (k + 1.0) * 0.5);
+    let mut min = at(1.0).min(at(n));
+    let mut max = at(1.0).max(at(n));
+    if a != 0.0 {
+        let vertex = -(v + a * 0.5) / a;
+        if vertex > 1.0 && vertex < n {
+            min = min.min(at(vertex));
+            max = max.max(at(vertex));
+        }
+    }
+    min >= lo && max <= hi
+}
+
+pub(crate) fn closed_form_exact<T, U>(core: &ParticleCore<T, U>, steps: u32) -> bool
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    // Per-step hooks need every step to run.
+    if T::STEP_HOOK {
+        return false;
+    }
+
+    let n = steps as f32;
+    if (core.flags & HAS_SIZE_VELOCITY) != 0 {
+        let a = channel_acceleration(core.flags, HAS_SIZE_ACCELERATION, core.size_acceleration);
+        let v = core.size_velocity;
+        if !clamp_exact(core.size.x, v, a, n, 0.0, f32::INFINITY)
+            || !clamp_exact(core.size.y, v, a, n, 0.0, f32::INFINITY)
+        {
+            return false;
+        }
+    }
+
+    if (core.flags & HAS_ALPHA_VELOCITY) != 0 {
+        let a = channel_acceleration(core.flags, HAS_ALPHA_ACCELERATION, core.alpha_acceleration);
+        if !clamp_exact(core.alpha, core.alpha_velocity, a, n, 0.0, 1.0) {
+            return false;
+        }
+    }
+
+    true
+}
+
+#[inline(always)]
+fn channel_acceleration(flags: u16, mask: u16, acceleration: f32) -> f32 {
+    if (flags & mask) != 0 {
+        acceleration
+    } else {
+        0.0
+    }
+}
+
+// Applies `steps` steps in closed form. Callers check `closed_form_exact` and
+// that the particle lives at least `steps` more steps.
+pub(crate) fn advance_closed_form<T, U>(core: &mut ParticleCore<T, U>, steps: u32)
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    if steps == 0 {
+        return;
+    }
+
+    let n = steps as f32;
+    let flags = core.flags;
+    core.counter -= steps;
+
+    if (flags & HAS_VELOCITY) != 0 {
+        let a = if (flags & HAS_ACCELERATION) != 0 {
+            core.acceleration
+        } else {
+            Vec2::ZERO
+        };
+        (core.pos, core.velocity) = integrate(core.pos, core.velocity, a, n);
+    }
+
+    if (flags & HAS_SIZE_VELOCITY) != 0 {
+        let a = channel_acceleration(flags, HAS_SIZE_ACCELERATION, core.size_acceleration);
+        let (size, velocity) = integrate(
+            core.size,
+            Vec2::splat(core.size_velocity),
+            Vec2::splat(a),
+            n,
+        );
+        core.size = size.max(Vec2::ZERO);
+        core.size_velocity = velocity.x;
+    }
+
+    if (flags & HAS_ROTATION_VELOCITY) != 0 {
+        let a = channel_acceleration(flags, HAS_ROTATION_ACCELERATION, core.rotation_acceleration);
+        (core.rotation, core.rotation_velocity) =
+            integrate(core.rotation, core.rotation_velocity, a, n);
+    }
+
+    if (flags & HAS_ALPHA_VELOCITY) != 0 {
+        let a = channel_acceleration(flags, HAS_ALPHA_ACCELERATION, core.alpha_acceleration);
+        let (alpha, velocity) = integrate(core.alpha, core.alpha_velocity, a, n);
+        core.alpha = alpha.clamp(0.0, 1.0);
+        core.alpha_velocity = velocity;
+    }
+
+    // Orientation only depends on the final state of a ballistic particle.
+    if core.oriented {
+        let velocity = core.velocity;
+        if let Some(rotation) = facing_rotation(core.orientation, core.pos, velocity, velocity) {
+            core.rotation = rotation;
+        }
+        if core.stretch != 0.0 {
+            core.stretch_scale = 1.0 + velocity.length() * core.stretch;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec2;
+
+    use crate::core::{ParticleHandle, ParticleSpawn, ParticleSystem, ParticleTypeTrait};
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct Spark;
+
+    impl ParticleTypeTrait for Spark {}
+
+    fn spawns() -> Vec<ParticleSpawn<Spark>> {
+        vec![
+            ParticleSpawn::new(Spark, 200, Vec2::new(1.0, 2.0), Vec2::splat(4.0))
+                .with_velocity(Vec2::new(3.0, -1.5))
+                .with_acceleration(Vec2::new(0.0, 0.25)),
+            ParticleSpawn::new(Spark, 200, Vec2::ZERO, Vec2::splat(2.0))
+                .with_size_velocity(0.5)
+                .with_size_acceleration(-0.01)
+                .with_rotation_velocity(0.1)
+                .with_rotation_acceleration(0.002),
+            ParticleSpawn::new(Spark, 200, Vec2::new(-5.0, 0.0), Vec2::ONE)
+                .with_velocity(Vec2::X)
+                .with_alpha_velocity(-0.004),
+            // Shrinks to zero partway, so the clamp forces the stepped path.
+            ParticleSpawn::new(Spark, 200, Vec2::ZERO, Vec2::splat(3.0))
+                .with_size_velocity(1.0)
+                .with_size_acceleration(-0.05),
+            ParticleSpawn::new(Spark, 200, Vec2::new(7.0, 7.0), Vec2::ONE)
+                .with_velocity(Vec2::new(-0.5, 0.5))
+                .with_acceleration(Vec2::new(0.01, 0.0))
+                .with_analytic(),
+            // Dies before the end of the jump.
+            ParticleSpawn::new(Spark, 40, Vec2::ZERO, Vec2::ONE).with_velocity(Vec2::Y),
+        ]
+    }
+
+    fn system() -> (ParticleSystem<Spark>, Vec<ParticleHandle>) {
+        let mut system = ParticleSystem::new();
+        let handles = spawns()
+            .into_iter()
+            .map(|spawn| system.spawn_with_handle(spawn).unwrap())
+            .collect();
+        (system, handles)
+    }
+
+    fn assert_matches(
+        jumped: &ParticleSystem<Spark>,
+        stepped: &ParticleSystem<Spark>,
+        handles: &[ParticleHandle],
+    ) {
+        assert_eq!(jumped.tick(), stepped.tick());
+        assert_eq!(jumped.len(), stepped.len());
+        for &handle in handles {
+            let (a, b) = match (jumped.get(handle), stepped.get(handle)) {
+                (Some(a), Some(b)) => (a, b),
+                (None, None) => continue,
+                (a, b) => panic!("liveness differs: {a:?} vs {b:?}"),
+            };
+            assert_eq!(a.counter, b.counter);
+            assert!(a.pos.abs_diff_eq(b.pos, 1e-2), "{:?} vs {:?}", a.pos, b.pos);
+            assert!(
+                a.size.abs_diff_eq(b.size, 1e-3),
+                "{:?} vs {:?}",
+                a.size,
+                b.size
+            );
+            assert!((a.rotation - b.rotation).abs() < 1e-3);
+            assert!((a.alpha - b.alpha).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn advance_matches_stepping() {
+        let (mut jumped, handles) = system();
+        let (mut stepped, _) = system();
+
+        jumped.advance(120);
+        for _ in 0..120 {
+            stepped.step();
+        }
+        assert_matches(&jumped, &stepped, &handles);
+    }
+
+    #[test]
+    fn seek_matches_stepping() {
+        let (mut jumped, handles) = system();
+        let (mut stepped, _) = system();
+
+        jumped.seek(25);
+        jumped.seek(90);
+        for _ in 0..90 {
+            stepped.step();
+        }
+        assert_matches(&jumped, &stepped, &handles);
+    }
+}
@@ -0,0 +1,164 @@
+use glam::Vec2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct AnchorId(pub(crate) u32);
+
+impl AnchorId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+pub(crate) const NO_ANCHOR: u32 = u32::MAX;
+
+// Live positions that spline control points can follow. Gameplay writes these
+// each frame and spline particles pick them up on their next step.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct AnchorTable {
+    positions: Vec<Vec2>,
+}
+
+impl AnchorTable {
+    pub(crate) fn add(&mut self, pos: Vec2) -> AnchorId {
+        let id = AnchorId(self.positions.len() as u32);
+        self.positions.push(pos);
+        id
+    }
+
+    pub(crate) fn set(&mut self, id: AnchorId, pos: Vec2) {
+        self.positions[id.index()] = pos;
+    }
+
+    pub(crate) fn get(&self, id: AnchorId) -> Vec2 {
+        self.positions[id.index()]
+    }
+
+    #[inline(always)]
+    pub(crate) fn get_raw(&self, raw: u32) -> Vec2 {
+        if raw == NO_ANCHOR {
+            Vec2::ZERO
+        } else {
+            self.positions[raw as usize]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec2;
+
+    use crate::core::{
+        calculate_bezier_point, ParticleSpawn, ParticleSystem, ParticleTypeTrait, SplinePath,
+        SplinePathState, SplineState,
+    };
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct Wisp;
+
+    impl ParticleTypeTrait for Wisp {}
+
+    const EPSILON: f32 = 1e-4;
+
+    fn curve(t: f32, point_1: Vec2, point_2: Vec2, point_3: Vec2) -> ParticleSpawn<Wisp> {
+        ParticleSpawn::new(Wisp, 100, Vec2::ZERO, Vec2::ONE).with_spline(SplineState {
+            t,
+            strength: 1.0,
+            point_1,
+            point_2,
+            point_3,
+        })
+    }
+
+    #[test]
+    fn anchored_points_follow_their_anchor() {
+        let mut system = ParticleSystem::new();
+        let start = system.add_anchor(Vec2::new(0.0, 0.0));
+        let control = system.add_anchor(Vec2::new(20.0, 40.0));
+        let end = system.add_anchor(Vec2::new(40.0, 0.0));
+        let offset = Vec2::new(1.0, -1.0);
+        let particle = system
+            .spawn_with_handle(
+                curve(0.25, offset, offset, offset)
+                    .with_spline_start_anchor(start)
+                    .with_spline_control_anchor(control)
+                    .with_spline_end_anchor(end),
+            )
+            .unwrap();
+
+        for target in [Vec2::new(40.0, 0.0), Vec2::new(-10.0, 60.0)] {
+            system.set_anchor(end, target);
+            system.step();
+            let expected =
+                calculate_bezier_point(0.25, system.anchor(start), system.anchor(control), target)
+                    + offset;
+            let pos = system.get(particle).unwrap().pos;
+            assert!(pos.abs_diff_eq(expected, EPSILON), "{pos} vs {expected}");
+        }
+    }
+
+    #[test]
+    fn unanchored_points_stay_absolute() {
+        let mut system = ParticleSystem::new();
+        let end = system.add_anchor(Vec2::new(50.0, 50.0));
+        let (point_1, point_2) = (Vec2::new(-5.0, 0.0), Vec2::new(10.0, 20.0));
+        let particle = system
+            .spawn_with_handle(curve(0.5, point_1, point_2, Vec2::ZERO).with_spline_end_anchor(end))
+            .unwrap();
+
+        system.step();
+        let expected = calculate_bezier_point(0.5, point_1, point_2, Vec2::new(50.0, 50.0));
+        assert!(system
+            .get(particle)
+            .unwrap()
+            .pos
+            .abs_diff_eq(expected, EPSILON));
+
+        system.set_anchor(end, Vec2::new(-50.0, 0.0));
+        system.step();
+        let expected = calculate_bezier_point(0.5, point_1, point_2, Vec2::new(-50.0, 0.0));
+        assert!(system
+            .get(particle)
+            .unwrap()
+            .pos
+            .abs_diff_eq(expected, EPSILON));
+    }
+
+    #[test]
+    fn path_anchors_shift_and_blend() {
+        let mut system = ParticleSystem::new();
+        let path =
+            system.add_spline_path(SplinePath::polyline(&[Vec2::ZERO, Vec2::new(100.0, 0.0)]));
+        let control = system.add_anchor(Vec2::new(0.0, 10.0));
+        let start = system.add_anchor(Vec2::ZERO);
+        let end = system.add_anchor(Vec2::new(0.0, 40.0));
+        let on_path = |t| {
+            ParticleSpawn::new(Wisp, 100, Vec2::ZERO, Vec2::ONE).with_spline_path(SplinePathState {
+                t,
+                strength: 1.0,
+                path,
+                arc_length: false,
+            })
+        };
+        let shifted = system
+            .spawn_with_handle(on_path(0.5).with_spline_control_anchor(control))
+            .unwrap();
+        let blended = system
+            .spawn_with_handle(
+                on_path(0.25)
+                    .with_spline_start_anchor(start)
+                    .with_spline_end_anchor(end),
+            )
+            .unwrap();
+
+        system.step();
+        let pos = system.get(shifted).unwrap().pos;
+        assert!(pos.abs_diff_eq(Vec2::new(50.0, 10.0), EPSILON), "{pos}");
+        let pos = system.get(blended).unwrap().pos;
+        assert!(pos.abs_diff_eq(Vec2::new(25.0, 10.0), EPSILON), "{pos}");
+
+        system.set_anchor(control, Vec2::new(-50.0, 0.0));
+        system.step();
+        let pos = system.get(shifted).unwrap().pos;
+        assert!(pos.abs_diff_eq(Vec2::ZERO, EPSILON), "{pos}");
+    }
+}
@@ -0,0 +1,808 @@
+use glam::Vec2;
+
+use super::analytic::{advance_closed_form, closed_form_exact};
+use super::death::{DeathCause, DeathLog};
+use super::handle::{HandleTable, ParticleLocation};
+use super::interpolation::PreviousState;
+use super::particle_model::{
+    facing_rotation, BallisticCold, BallisticHot, ParticleCore, ParticlePayload,
+    ParticleRenderData, ParticleTypeTrait, HAS_ACCELERATION, HAS_ALPHA_ACCELERATION,
+    HAS_ALPHA_VELOCITY, HAS_ROTATION_ACCELERATION, HAS_ROTATION_VELOCITY, HAS_SIZE_ACCELERATION,
+    HAS_SIZE_VELOCITY, HAS_VELOCITY,
+};
+use super::particle_system::retire_particle;
+use super::particle_types::TypeTable;
+use super::particle_view::ParticleMut;
+use super::time_scale::{step_ballistic_scaled, TimeControls};
+use super::trail::{TrailPool, NO_TRAIL};
+
+// Lane keys are the channel flags plus one bit for particles that need an
+// orientation pass, so every lane runs a loop with no per-particle dispatch.
+pub(crate) const ORIENTED_LANE: u16 = 1 << 8;
+const LANE_KEY_COUNT: usize = 1 << 9;
+const NO_LANE: u16 = u16::MAX;
+
+// Stands in for `KEY` in the one monomorphization shared by every lane key
+// outside the list in `dispatch_lane_key!`. Real keys never set the high bits.
+pub(crate) const DYNAMIC_LANE_KEY: u16 = u16::MAX;
+
+// Calls the `KEY` monomorphization of `$func` for the common lane keys and
+// the `DYNAMIC_LANE_KEY` one for the rest. Monomorphizing all 512 keys cost
+// compile time and binary size for combinations few scenes ever spawn; the
+// shared loop still tests a key that is constant across the lane, so its
+// branches predict perfectly.
+macro_rules! dispatch_lane_key {
+    ($key:expr, $func:ident::<$($t:ty),+>($($arg:expr),*)) => {{
+        let key: u16 = $key;
+        dispatch_lane_key!(
+            @keys key, $func, ($($t),+), ($($arg),*),
+            0,
+            HAS_VELOCITY,
+            HAS_VELOCITY | HAS_ACCELERATION,
+            HAS_VELOCITY | HAS_ALPHA_VELOCITY,
+            HAS_VELOCITY | HAS_ACCELERATION | HAS_ALPHA_VELOCITY,
+            HAS_VELOCITY | HAS_ACCELERATION | HAS_SIZE_VELOCITY,
+            HAS_VELOCITY | HAS_ACCELERATION | HAS_SIZE_VELOCITY | HAS_ALPHA_VELOCITY,
+            HAS_VELOCITY | HAS_ACCELERATION | HAS_ROTATION_VELOCITY,
+            HAS_VELOCITY | HAS_ACCELERATION | HAS_SIZE_VELOCITY | HAS_SIZE_ACCELERATION
+                | HAS_ROTATION_VELOCITY | HAS_ROTATION_ACCELERATION | HAS_ALPHA_VELOCITY,
+            HAS_ALPHA_VELOCITY,
+            HAS_SIZE_VELOCITY | HAS_ALPHA_VELOCITY,
+            HAS_VELOCITY | ORIENTED_LANE,
+            HAS_VELOCITY | HAS_ACCELERATION | ORIENTED_LANE,
+            HAS_VELOCITY | HAS_ACCELERATION | HAS_ALPHA_VELOCITY | ORIENTED_LANE,
+        )
+    }};
+    (@keys $key:ident, $func:ident, $ts:tt, $args:tt, $hot:expr, $($rest:expr,)*) => {
+        if $key == $hot {
+            dispatch_lane_key!(@call $func, $ts, $args, $hot)
+        } else {
+            dispatch_lane_key!(@keys $key, $func, $ts, $args, $($rest,)*)
+        }
+    };
+    (@keys $key:ident, $func:ident, $ts:tt, $args:tt, ) => {
+        dispatch_lane_key!(@call $func, $ts, $args, DYNAMIC_LANE_KEY)
+    };
+    (@call $func:ident, ($($t:ty),+), ($($arg:expr),*), $hot:expr) => {
+        $func::<$($t),+, { $hot }>($($arg),*)
+    };
+}
+
+// The key a keyed loop runs with: `KEY` itself when it was monomorphized, so
+// every channel test folds away, or the lane's own key otherwise.
+#[inline(always)]
+pub(crate) const fn resolve_lane_key<const KEY: u16>(key: u16) -> u16 {
+    if KEY == DYNAMIC_LANE_KEY {
+        key
+    } else {
+        KEY
+    }
+}
+
+#[cfg(feature = "parallel")]
+pub(crate) use dispatch_lane_key;
+
+// `hot` and `cold` hold the two halves of each particle at the same index, and
+// every removal goes through `swap_remove` so they stay in lockstep. While
+// interpolation is enabled `previous` joins them; otherwise it stays empty.
+#[derive(Clone, Debug)]
+pub(crate) struct FlagLane<T, U>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    pub(crate) key: u16,
+    pub(crate) index: u16,
+    pub(crate) hot: Vec<BallisticHot>,
+    pub(crate) cold: Vec<BallisticCold<T, U>>,
+    pub(crate) previous: Vec<PreviousState>,
+}
+
+impl<T, U> FlagLane<T, U>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    #[inline(always)]
+    pub(crate) fn len(&self) -> usize {
+        self.hot.len()
+    }
+
+    #[inline(always)]
+    pub(crate) fn capacity(&self) -> usize {
+        self.hot.capacity().min(self.cold.capacity())
+    }
+
+    fn reserve(&mut self, additional: usize, interpolate: bool) {
+        self.hot.reserve(additional);
+        self.cold.reserve(additional);
+        if interpolate {
+            self.previous.reserve(additional);
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn swap_remove(
+        &mut self,
+        index: usize,
+        handles: &mut HandleTable,
+    ) -> BallisticCold<T, U> {
+        self.hot.swap_remove(index);
+        if !self.previous.is_empty() {
+            self.previous.swap_remove(index);
+        }
+        let removed = self.cold.swap_remove(index);
+        if let Some(moved) = self.cold.get(index) {
+            handles.relocate(moved.handle, ParticleLocation::new(self.index, index));
+        }
+        removed
+    }
+
+    // Removes a particle, recording its death if asked to. Shared by every lane
+    // key rather than inlined into each step loop.
+    #[inline(never)]
+    fn remove(
+        &mut self,
+        index: usize,
+        cause: DeathCause,
+        trails: &mut TrailPool,
+        types: &mut TypeTable<T>,
+        handles: &mut HandleTable,
+        deaths: &mut DeathLog<T, U>,
+    ) {
+        if deaths.is_recording() {
+            let handle = handles.handle(self.cold[index].handle);
+            let particle = (&self.hot[index], &self.cold[index]).into();
+            deaths.record(particle, handle, cause);
+        }
+        let cold = self.swap_remove(index, handles);
+        retire_particle(
+            cold.trail,
+            cold.type_slot,
+            cold.handle,
+            trails,
+            types,
+            handles,
+        );
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&BallisticHot, &BallisticCold<T, U>)> {
+        self.hot.iter().zip(self.cold.iter())
+    }
+}
+
+// The ballistic lane, bucketed at spawn time into one sub-lane per lane key.
+#[derive(Clone, Debug)]
+pub(crate) struct BallisticLanes<T, U>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    pub(crate) lanes: Vec<FlagLane<T, U>>,
+    lookup: Vec<u16>,
+    // Total room asked for through `reserve`, shared by every sub-lane.
+    reserved: usize,
+    // Room every sub-lane grows to once it fills, from `reserve_each`.
+    reserved_each: usize,
+    interpolate: bool,
+}
+
+impl<T, U> BallisticLanes<T, U>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    pub(crate) fn new() -> Self {
+        Self {
+            lanes: Vec::new(),
+            lookup: vec![NO_LANE; LANE_KEY_COUNT],
+            reserved: 0,
+            reserved_each: 0,
+            interpolate: false,
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn lane_key(core: &ParticleCore<T, U>) -> u16 {
+        let oriented = if core.oriented { ORIENTED_LANE } else { 0 };
+        core.flags | oriented
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.lanes.iter().map(FlagLane::len).sum()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.lanes.iter().all(|lane| lane.hot.is_empty())
+    }
+
+    pub(crate) fn capacity(&self) -> usize {
+        self.lanes.iter().map(FlagLane::capacity).sum()
+    }
+
+    // What the fullest flag combination can reach without reallocating.
+    pub(crate) fn lane_capacity(&self) -> usize {
+        self.lanes.iter().map(FlagLane::capacity).min().unwrap_or(0)
+    }
+
+    pub(crate) fn clear(&mut self) {
+        for lane in &mut self.lanes {
+            lane.hot.clear();
+            lane.cold.clear();
+            lane.previous.clear();
+        }
+    }
+
+    pub(crate) fn set_interpolation(&mut self, enabled: bool) {
+        self.interpolate = enabled;
+        for lane in &mut self.lanes {
+            lane.previous.clear();
+        }
+        if enabled {
+            self.sync_previous();
+        }
+    }
+
+    // Drops the step being interpolated across, e.g. after `advance`.
+    pub(crate) fn sync_previous(&mut self) {
+        for lane in &mut self.lanes {
+            lane.previous.clear();
+            lane.previous
+                .extend(lane.hot.iter().map(PreviousState::from));
+        }
+    }
+
+    // Sub-lane occupancy is unknown ahead of spawning, so the reservation is
+    // only recorded here. Sub-lanes claim what is left of it as they fill.
+    pub(crate) fn reserve(&mut self, additional: usize) {
+        self.reserved = self.reserved.max(self.len() + additional);
+    }
+
+    // Sizes every sub-lane, including ones created later, for `count`
+    // particles, so no single flag combination reallocates below it.
+    pub(crate) fn reserve_each(&mut self, count: usize) {
+        self.reserved_each = self.reserved_each.max(count);
+        for lane in &mut self.lanes {
+            let additional = count.saturating_sub(lane.len());
+            lane.reserve(additional, self.interpolate);
+        }
+    }
+
+    #[inline(always)]
+    fn lane_for(&mut self, key: u16) -> &mut FlagLane<T, U> {
+        let lane = match self.lookup[key as usize] {
+            NO_LANE => {
+                let index = self.lanes.len() as u16;
+                self.lookup[key as usize] = index;
+                self.lanes.push(FlagLane {
+                    key,
+                    index,
+                    hot: Vec::new(),
+                    cold: Vec::new(),
+                    previous: Vec::new(),
+                });
+                self.lanes.len() - 1
+            }
+            lane => lane as usize,
+        };
+        let full = &self.lanes[lane];
+        if full.len() == full.capacity()
+            || (self.interpolate && full.previous.len() == full.previous.capacity())
+        {
+            self.grow(lane);
+        }
+        &mut self.lanes[lane]
+    }
+
+    // A full sub-lane grows to `reserved_each` and takes all of the shared
+    // reservation no sub-lane holds yet, or grows as a plain `Vec` once both
+    // are used up.
+    #[cold]
+    fn grow(&mut self, lane: usize) {
+        let unclaimed = self.reserved.saturating_sub(self.capacity());
+        let lane = &mut self.lanes[lane];
+        let additional = unclaimed
+            .max(self.reserved_each.saturating_sub(lane.len()))
+            .max(1);
+        lane.reserve(additional, self.interpolate);
+    }
+
+    #[inline(always)]
+    pub(crate) fn push(&mut self, core: ParticleCore<T, U>) -> ParticleLocation {
+        let (hot, cold) = core.split();
+        let interpolate = self.interpolate;
+        let lane = self.lane_for(Self::lane_key(&core));
+        if interpolate {
+            lane.previous.push(PreviousState::from(&hot));
+        }
+        lane.hot.push(hot);
+        lane.cold.push(cold);
+        ParticleLocation::new(lane.index, lane.len() - 1)
+    }
+
+    // `push` for a particle moved from another lane mid-flight.
+    pub(crate) fn push_with_previous(
+        &mut self,
+        core: ParticleCore<T, U>,
+        previous: PreviousState,
+    ) -> ParticleLocation {
+        let (hot, cold) = core.split();
+        let interpolate = self.interpolate;
+        let lane = self.lane_for(Self::lane_key(&core));
+        if interpolate {
+            lane.previous.push(previous);
+        }
+        lane.hot.push(hot);
+        lane.cold.push(cold);
+        ParticleLocation::new(lane.index, lane.len() - 1)
+    }
+
+    // Pushes `core` as it would be after `steps` steps. Callers check that it
+    // survives them.
+    pub(crate) fn push_advanced(
+        &mut self,
+        core: ParticleCore<T, U>,
+        steps: u32,
+        trails: &mut TrailPool,
+    ) {
+        let key = Self::lane_key(&core);
+        let (mut hot, mut cold) = core.split();
+        dispatch_lane_key!(
+            key,
+            advance_keyed_particle::<T, U>(&mut hot, &mut cold, key, steps, trails)
+        );
+        let interpolate = self.interpolate;
+        let lane = self.lane_for(key);
+        if interpolate {
+            lane.previous.push(PreviousState::from(&hot));
+        }
+        lane.hot.push(hot);
+        lane.cold.push(cold);
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&BallisticHot, &BallisticCold<T, U>)> {
+        self.lanes.iter().flat_map(FlagLane::iter)
+    }
+
+    pub(crate) fn iter_with_previous(
+        &self,
+    ) -> impl Iterator<Item = (&BallisticHot, &BallisticCold<T, U>, &PreviousState)> {
+        self.lanes.iter().flat_map(|lane| {
+            lane.hot
+                .iter()
+                .zip(lane.cold.iter())
+                .zip(lane.previous.iter())
+                .map(|((hot, cold), previous)| (hot, cold, previous))
+        })
+    }
+
+    pub(crate) fn advance(
+        &mut self,
+        steps: u32,
+        trails: &mut TrailPool,
+        types: &mut TypeTable<T>,
+        handles: &mut HandleTable,
+        deaths: &mut DeathLog<T, U>,
+    ) {
+        for lane in &mut self.lanes {
+            dispatch_lane_key!(
+                lane.key,
+                advance_flag_lane::<T, U>(lane, steps, trails, types, handles, deaths)
+            );
+        }
+        if self.interpolate {
+            self.sync_previous();
+        }
+    }
+
+    pub(crate) fn step(
+        &mut self,
+        trails: &mut TrailPool,
+        types: &mut TypeTable<T>,
+        handles: &mut HandleTable,
+        deaths: &mut DeathLog<T, U>,
+    ) {
+        let record_trails = types.has_trails();
+        for lane in &mut self.lanes {
+            if self.interpolate {
+                dispatch_lane_key!(
+                    lane.key,
+                    step_flag_lane_interpolated::<T, U>(
+                        lane,
+                        trails,
+                        types,
+                        handles,
+                        deaths,
+                        record_trails
+                    )
+                );
+            } else {
+                dispatch_lane_key!(
+                    lane.key,
+                    step_flag_lane::<T, U>(lane, trails, types, handles, deaths, record_trails)
+                );
+            }
+        }
+    }
+
+    // Removes every particle `keep` rejects, compacting each sub-lane the way
+    // `step` does. Returns how many were removed.
+    pub(crate) fn retain(
+        &mut self,
+        mut keep: impl FnMut(ParticleRenderData<T, U>) -> bool,
+        trails: &mut TrailPool,
+        types: &mut TypeTable<T>,
+        handles: &mut HandleTable,
+        deaths: &mut DeathLog<T, U>,
+    ) -> usize {
+        let mut removed = 0;
+        for lane in &mut self.lanes {
+            let mut i = 0;
+            while i < lane.len() {
+                if !keep((&lane.hot[i], &lane.cold[i]).into()) {
+                    lane.remove(i, DeathCause::Killed, trails, types, handles, deaths);
+                    removed += 1;
+                    continue;
+                }
+                i += 1;
+            }
+        }
+        removed
+    }
+
+    // Moves particles given a velocity after spawn out of sub-lanes that never
+    // integrate it, keeping their interpolation state. Velocity is the only
+    // rate `ParticleMut` can write, so it is the only flag to pick up.
+    pub(crate) fn promote_moving(&mut self, handles: &mut HandleTable) {
+        let mut moving = Vec::new();
+        for lane in &mut self.lanes {
+            if (lane.key & HAS_VELOCITY) != 0 {
+                continue;
+            }
+            let mut i = 0;
+            while i < lane.len() {
+                if lane.hot[i].velocity == Vec2::ZERO {
+                    i += 1;
+                    continue;
+                }
+                let mut core = ParticleCore::from_parts(
+                    &lane.hot[i],
+                    &lane.cold[i],
+                    lane.key & !ORIENTED_LANE,
+                    (lane.key & ORIENTED_LANE) != 0,
+                );
+                core.flags |= HAS_VELOCITY;
+                let previous = lane
+                    .previous
+                    .get(i)
+                    .copied()
+                    .unwrap_or_else(|| PreviousState::from(&lane.hot[i]));
+                lane.swap_remove(i, handles);
+                moving.push((core, previous));
+            }
+        }
+        for (core, previous) in moving {
+            let location = self.push_with_previous(core, previous);
+            handles.relocate(core.handle, location);
+        }
+    }
+
+    // `step` under time controls, looking up each particle's scale. The lane
+    // key is read at run time; this path is not monomorphized.
+    pub(crate) fn step_scaled(
+        &mut self,
+        trails: &mut TrailPool,
+        types: &mut TypeTable<T>,
+        handles: &mut HandleTable,
+        deaths: &mut DeathLog<T, U>,
+        time: &TimeControls,
+    ) {
+        let interpolate = self.interpolate;
+        for lane in &mut self.lanes {
+            let mut i = 0;
+            while i < lane.len() {
+                if lane.hot[i].counter == 0 {
+                    lane.remove(i, DeathCause::Expired, trails, types, handles, deaths);
+                    continue;
+                }
+
+                let hot = &mut lane.hot[i];
+                let cold = &mut lane.cold[i];
+                if interpolate {
+                    lane.previous[i] = PreviousState::from(&*hot);
+                }
+                let scale = time.scale(types, cold.type_slot, cold.draw_layer, hot.pos);
+                step_ballistic_scaled(hot, cold, lane.key, scale);
+                if cold.trail != NO_TRAIL && scale > 0.0 {
+                    trails.record(cold.trail, hot.pos);
+                }
+                i += 1;
+            }
+        }
+    }
+}
+
+fn advance_flag_lane<T, U, const KEY: u16>(
+    lane: &mut FlagLane<T, U>,
+    steps: u32,
+    trails: &mut TrailPool,
+    types: &mut TypeTable<T>,
+    handles: &mut HandleTable,
+    deaths: &mut DeathLog<T, U>,
+) where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    let mut i = 0;
+    while i < lane.len() {
+        if lane.hot[i].counter < steps {
+            // Deaths are recorded at the state the particle died in.
+            if deaths.is_recording() {
+                let counter = lane.hot[i].counter;
+                advance_keyed_particle::<T, U, KEY>(
+                    &mut lane.hot[i],
+                    &mut lane.cold[i],
+                    lane.key,
+                    counter,
+                    trails,
+                );
+            }
+            lane.remove(i, DeathCause::Expired, trails, types, handles, deaths);
+            continue;
+        }
+
+        advance_keyed_particle::<T, U, KEY>(
+            &mut lane.hot[i],
+            &mut lane.cold[i],
+            lane.key,
+            steps,
+            trails,
+        );
+        i += 1;
+    }
+}
+
+// Uses the closed form where it is exact and steps one at a time otherwise.
+#[inline(always)]
+fn advance_keyed_particle<T, U, const KEY: u16>(
+    hot: &mut BallisticHot,
+    cold: &mut BallisticCold<T, U>,
+    key: u16,
+    steps: u32,
+    trails: &mut TrailPool,
+) where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    let key = resolve_lane_key::<KEY>(key);
+    let mut core =
+        ParticleCore::from_parts(hot, cold, key & !ORIENTED_LANE, (key & ORIENTED_LANE) != 0);
+    if cold.trail == NO_TRAIL && closed_form_exact(&core, steps) {
+        advance_closed_form(&mut core, steps);
+        (*hot, *cold) = core.split();
+    } else {
+        for _ in 0..steps {
+            step_keyed_particle::<T, U, KEY>(hot, cold, key);
+            if cold.trail != NO_TRAIL {
+                trails.record(cold.trail, hot.pos);
+            }
+        }
+    }
+}
+
+fn step_flag_lane<T, U, const KEY: u16>(
+    lane: &mut FlagLane<T, U>,
+    trails: &mut TrailPool,
+    types: &mut TypeTable<T>,
+    handles: &mut HandleTable,
+    deaths: &mut DeathLog<T, U>,
+    record_trails: bool,
+) where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    step_lane::<T, U, KEY, false>(lane, trails, types, handles, deaths, record_trails);
+}
+
+fn step_flag_lane_interpolated<T, U, const KEY: u16>(
+    lane: &mut FlagLane<T, U>,
+    trails: &mut TrailPool,
+    types: &mut TypeTable<T>,
+    handles: &mut HandleTable,
+    deaths: &mut DeathLog<T, U>,
+    record_trails: bool,
+) where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    step_lane::<T, U, KEY, true>(lane, trails, types, handles, deaths, record_trails);
+}
+
+// Interpolation is a const parameter so the default loop carries no trace of it.
+#[inline(always)]
+fn step_lane<T, U, const KEY: u16, const INTERPOLATE: bool>(
+    lane: &mut FlagLane<T, U>,
+    trails: &mut TrailPool,
+    types: &mut TypeTable<T>,
+    handles: &mut HandleTable,
+    deaths: &mut DeathLog<T, U>,
+    record_trails: bool,
+) where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    let mut i = 0;
+    while i < lane.len() {
+        if lane.hot[i].counter == 0 {
+            lane.remove(i, DeathCause::Expired, trails, types, handles, deaths);
+            continue;
+        }
+
+        let hot = &mut lane.hot[i];
+        if INTERPOLATE {
+            lane.previous[i] = PreviousState::from(&*hot);
+        }
+        step_keyed_particle::<T, U, KEY>(hot, &mut lane.cold[i], lane.key);
+        // Trail slots live in the cold record, so lanes skip the lookup
+        // entirely while no type has a trail style.
+        if record_trails {
+            let trail = lane.cold[i].trail;
+            if trail != NO_TRAIL {
+                trails.record(trail, hot.pos);
+            }
+        }
+        i += 1;
+    }
+}
+
+// With `KEY` known at compile time every channel test folds away, leaving a
+// static lane with nothing but the counter decrement. Under
+// `DYNAMIC_LANE_KEY` the tests read `key` instead. The cold record is only
+// read by lanes with rotation/alpha acceleration or an orientation pass, and
+// by types with a step hook.
+#[inline(always)]
+pub(crate) fn step_keyed_particle<T, U, const KEY: u16>(
+    particle: &mut BallisticHot,
+    cold: &mut BallisticCold<T, U>,
+    key: u16,
+) where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    let key = resolve_lane_key::<KEY>(key);
+    particle.counter -= 1;
+
+    if (key & HAS_VELOCITY) != 0 {
+        if (key & HAS_ACCELERATION) != 0 {
+            particle.velocity += particle.acceleration;
+        }
+        particle.pos += particle.velocity;
+    }
+
+    if (key & HAS_SIZE_VELOCITY) != 0 {
+        if (key & HAS_SIZE_ACCELERATION) != 0 {
+            particle.size_velocity += particle.size_acceleration;
+        }
+        particle.size += particle.size_velocity;
+        particle.size = particle.size.max(Vec2::ZERO);
+    }
+
+    if (key & HAS_ROTATION_VELOCITY) != 0 {
+        if (key & HAS_ROTATION_ACCELERATION) != 0 {
+            particle.rotation_velocity += cold.rotation_acceleration;
+        }
+        particle.rotation += particle.rotation_velocity;
+    }
+
+    if (key & HAS_ALPHA_VELOCITY) != 0 {
+        if (key & HAS_ALPHA_ACCELERATION) != 0 {
+            particle.alpha_velocity += cold.alpha_acceleration;
+        }
+        particle.alpha = (particle.alpha + particle.alpha_velocity).clamp(0.0, 1.0);
+    }
+
+    if (key & ORIENTED_LANE) != 0 {
+        orient_ballistic(particle, cold);
+    }
+
+    if T::STEP_HOOK {
+        let particle_type = cold.particle_type;
+        particle_type.on_step(&mut ParticleMut::ballistic(particle, cold));
+    }
+}
+
+#[inline(always)]
+pub(crate) fn orient_ballistic<T, U>(particle: &mut BallisticHot, cold: &mut BallisticCold<T, U>)
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    let velocity = particle.velocity;
+    if let Some(rotation) = facing_rotation(cold.orientation, particle.pos, velocity, velocity) {
+        particle.rotation = rotation;
+    }
+    if cold.stretch != 0.0 {
+        cold.stretch_scale = 1.0 + velocity.length() * cold.stretch;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{Orientation, ParticleSpawn};
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    struct Spark;
+    impl ParticleTypeTrait for Spark {}
+
+    #[test]
+    fn shared_loop_matches_the_monomorphized_keys() {
+        let spawn = ParticleSpawn::new(Spark, 10, Vec2::new(3.0, 4.0), Vec2::splat(2.0))
+            .with_velocity(Vec2::new(1.0, -0.5))
+            .with_acceleration(Vec2::new(0.1, 0.2))
+            .with_size_velocity(0.3)
+            .with_size_acceleration(-0.05)
+            .with_rotation_velocity(0.2)
+            .with_rotation_acceleration(0.01)
+            .with_alpha(0.5)
+            .with_alpha_velocity(-0.1)
+            .with_alpha_acceleration(0.02)
+            .with_orientation(Orientation::AlignToVelocity)
+            .with_stretch(0.5);
+        let (hot, cold) = ParticleCore::from_spawn(&spawn).split();
+
+        for key in 0..LANE_KEY_COUNT as u16 {
+            let (mut keyed_hot, mut keyed_cold) = (hot, cold);
+            let (mut shared_hot, mut shared_cold) = (hot, cold);
+            for _ in 0..3 {
+                dispatch_lane_key!(
+                    key,
+                    step_keyed_particle::<Spark, ()>(&mut keyed_hot, &mut keyed_cold, key)
+                );
+                step_keyed_particle::<Spark, (), DYNAMIC_LANE_KEY>(
+                    &mut shared_hot,
+                    &mut shared_cold,
+                    key,
+                );
+            }
+
+            assert_eq!(keyed_hot.counter, shared_hot.counter, "key {key:#x}");
+            assert_eq!(keyed_hot.pos, shared_hot.pos, "key {key:#x}");
+            assert_eq!(keyed_hot.velocity, shared_hot.velocity, "key {key:#x}");
+            assert_eq!(keyed_hot.size, shared_hot.size, "key {key:#x}");
+            assert_eq!(keyed_hot.rotation, shared_hot.rotation, "key {key:#x}");
+            assert_eq!(keyed_hot.alpha, shared_hot.alpha, "key {key:#x}");
+            assert_eq!(
+                keyed_cold.stretch_scale, shared_cold.stretch_scale,
+                "key {key:#x}"
+            );
+        }
+    }
+
+    #[test]
+    fn rare_combinations_step_every_channel() {
+        // Size acceleration without velocity is not in the monomorphized list.
+        let key = HAS_SIZE_VELOCITY | HAS_SIZE_ACCELERATION | HAS_ALPHA_VELOCITY;
+        let spawn = ParticleSpawn::new(Spark, 10, Vec2::ZERO, Vec2::splat(2.0))
+            .with_size_velocity(1.0)
+            .with_size_acceleration(0.5)
+            .with_alpha(0.5)
+            .with_alpha_velocity(0.1);
+        let core = ParticleCore::from_spawn(&spawn);
+        assert_eq!(BallisticLanes::<Spark, ()>::lane_key(&core), key);
+        let (mut hot, mut cold) = core.split();
+
+        dispatch_lane_key!(
+            key,
+            step_keyed_particle::<Spark, ()>(&mut hot, &mut cold, key)
+        );
+        dispatch_lane_key!(
+            key,
+            step_keyed_particle::<Spark, ()>(&mut hot, &mut cold, key)
+        );
+
+        assert_eq!(hot.counter, 8);
+        assert_eq!(hot.size, Vec2::splat(2.0 + 1.5 + 2.0));
+        assert!((hot.alpha - 0.7).abs() < 1e-6);
+    }
+}
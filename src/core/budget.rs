@@ -0,0 +1,570 @@
+use std::hash::Hash;
+use std::mem;
+
+use glam::Vec2;
+
+use super::death::DeathCause;
+use super::handle::{ParticleLocation, ANALYTIC_LANE, SPLINE_LANE};
+use super::particle_model::{ParticlePayload, ParticleSpawn, ParticleTypeTrait};
+use super::particle_system::ParticleSystem;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OverflowPolicy {
+    RejectNew,
+    EvictOldest,
+    EvictLowestAlpha,
+    EvictSmallest,
+    // Spawns are always accepted below `start_fraction * max_particles`, then
+    // accepted with a probability that falls linearly to zero at the cap.
+    Thin { start_fraction: f32 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ParticleBudget {
+    pub max_particles: usize,
+    pub policy: OverflowPolicy,
+}
+
+impl ParticleBudget {
+    pub fn new(max_particles: usize, policy: OverflowPolicy) -> Self {
+        Self {
+            max_particles,
+            policy,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BudgetStats {
+    pub rejected: u64,
+    pub evicted: u64,
+    pub thinned: u64,
+}
+
+impl BudgetStats {
+    pub fn dropped(&self) -> u64 {
+        self.rejected + self.evicted + self.thinned
+    }
+}
+
+// Room each lane has before it reallocates. Ballistic particles are split
+// into one sub-lane per flag combination, so `ballistic` is the smallest of
+// those: what any one combination is sure to fit.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LaneCapacity {
+    pub ballistic: usize,
+    pub analytic: usize,
+    pub spline: usize,
+}
+
+impl<T, U> ParticleSystem<T, U>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    // Budgets cap the live particle count.
+    pub fn set_budget(&mut self, budget: ParticleBudget) {
+        self.budget = Some(budget);
+        self.refresh_budgets_active();
+    }
+
+    pub fn clear_budget(&mut self) {
+        self.budget = None;
+        self.refresh_budgets_active();
+    }
+
+    pub fn set_type_budget(&mut self, particle_type: T, budget: ParticleBudget)
+    where
+        T: Hash + Eq,
+    {
+        self.type_settings_mut(particle_type).budget = Some(budget);
+        self.refresh_budgets_active();
+    }
+
+    pub fn clear_type_budget(&mut self, particle_type: T)
+    where
+        T: Hash + Eq,
+    {
+        self.type_settings_mut(particle_type).budget = None;
+        self.refresh_budgets_active();
+    }
+
+    pub fn budget_stats(&self) -> BudgetStats {
+        self.budget_stats
+    }
+
+    pub fn type_budget_stats(&self, particle_type: T) -> BudgetStats {
+        self.types
+            .get(self.types.slot(particle_type))
+            .map_or(BudgetStats::default(), |settings| settings.stats)
+    }
+
+    pub fn reset_budget_stats(&mut self) {
+        self.budget_stats = BudgetStats::default();
+        for settings in &mut self.types.settings {
+            settings.stats = BudgetStats::default();
+        }
+    }
+
+    pub fn capacity(&self) -> LaneCapacity {
+        LaneCapacity {
+            ballistic: self.ballistic.lane_capacity(),
+            analytic: self.analytic.capacity(),
+            spline: self.spline_particles.capacity(),
+        }
+    }
+
+    // Sizes every lane in use for the whole system budget: each ballistic
+    // sub-lane, plus the analytic and spline lanes if they hold any room yet.
+    // Lanes first used later grow straight to the budget on their first
+    // particle. Within the budget, a lane then never reallocates, whichever
+    // flag combinations share it out. That costs the budget's worth of memory
+    // per lane in use.
+    pub fn reserve_budget(&mut self) {
+        if let Some(budget) = self.budget {
+            let max = budget.max_particles;
+            self.ballistic.reserve_each(max);
+            self.analytic.reserve_each(max);
+            self.spline_reserved = self.spline_reserved.max(max);
+            if self.spline_particles.capacity() > 0 {
+                self.grow_spline(max);
+            }
+        }
+    }
+
+    fn refresh_budgets_active(&mut self) {
+        self.budgets_active = self.budget.is_some()
+            || self
+                .types
+                .settings
+                .iter()
+                .any(|settings| settings.budget.is_some());
+    }
+
+    // Filters `spawns` down to what the budgets admit, evicting live particles
+    // where the policy asks for it.
+    pub(crate) fn admit_spawns(&mut self, spawns: &mut Vec<ParticleSpawn<T, U>>) {
+        for slot in 0..self.types.settings.len() {
+            let settings = self.types.settings[slot];
+            if let Some(budget) = settings.budget {
+                let slot = slot as u16;
+                let mut stats = settings.stats;
+                self.admit_group(spawns, Some(slot), settings.live, budget, &mut stats);
+                self.types.settings[slot as usize].stats = stats;
+            }
+        }
+
+        if let Some(budget) = self.budget {
+            let mut stats = self.budget_stats;
+            self.admit_group(spawns, None, self.len(), budget, &mut stats);
+            self.budget_stats = stats;
+        }
+    }
+
+    fn admit_group(
+        &mut self,
+        spawns: &mut Vec<ParticleSpawn<T, U>>,
+        slot: Option<u16>,
+        mut live: usize,
+        budget: ParticleBudget,
+        stats: &mut BudgetStats,
+    ) {
+        let max = budget.max_particles;
+        match budget.policy {
+            OverflowPolicy::RejectNew => {
+                spawns.retain(|spawn| {
+                    if !self.in_group(spawn, slot) {
+                        return true;
+                    }
+                    if live < max {
+                        live += 1;
+                        true
+                    } else {
+                        stats.rejected += 1;
+                        false
+                    }
+                });
+            }
+            OverflowPolicy::Thin { start_fraction } => {
+                let threshold = (max as f32 * start_fraction.clamp(0.0, 1.0)) as usize;
+                let mut rng = self.thin_rng;
+                spawns.retain(|spawn| {
+                    if !self.in_group(spawn, slot) {
+                        return true;
+                    }
+                    let keep = if live < threshold {
+                        true
+                    } else if live >= max {
+                        false
+                    } else {
+                        rng ^= rng << 13;
+                        rng ^= rng >> 17;
+                        rng ^= rng << 5;
+                        let roll = (rng >> 8) as f32 / (1u32 << 24) as f32;
+                        roll < (max - live) as f32 / (max - threshold) as f32
+                    };
+                    if keep {
+                        live += 1;
+                    } else {
+                        stats.thinned += 1;
+                    }
+                    keep
+                });
+                self.thin_rng = rng;
+            }
+            policy => {
+                let incoming = spawns
+                    .iter()
+                    .filter(|spawn| self.in_group(spawn, slot))
+                    .count();
+                let over = (live + incoming).saturating_sub(max);
+                let evict = over.min(live);
+                self.evict(slot, evict, policy);
+                stats.evicted += evict as u64;
+
+                let excess = over - evict;
+                if excess > 0 {
+                    let mut keep = incoming - excess;
+                    spawns.retain(|spawn| {
+                        if !self.in_group(spawn, slot) {
+                            return true;
+                        }
+                        if keep > 0 {
+                            keep -= 1;
+                            true
+                        } else {
+                            false
+                        }
+                    });
+                    stats.rejected += excess as u64;
+                }
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn in_group(&self, spawn: &ParticleSpawn<T, U>, slot: Option<u16>) -> bool {
+        match slot {
+            None => true,
+            Some(slot) => self.types.slot(spawn.particle_type) == slot,
+        }
+    }
+
+    fn evict(&mut self, slot: Option<u16>, count: usize, policy: OverflowPolicy) {
+        if count == 0 {
+            return;
+        }
+
+        // Lowest key goes first. Ages survive the tick counter wrapping, where
+        // spawn ticks would not.
+        let tick = self.tick;
+        let key = |alpha: f32, size: Vec2, spawn_tick: u32| -> u32 {
+            match policy {
+                OverflowPolicy::EvictLowestAlpha => alpha.max(0.0).to_bits(),
+                OverflowPolicy::EvictSmallest => (size.x * size.y).max(0.0).to_bits(),
+                _ => u32::MAX - tick.wrapping_sub(spawn_tick),
+            }
+        };
+        let in_slot = |type_slot: u16| slot.is_none_or(|slot| type_slot == slot);
+
+        let mut candidates = mem::take(&mut self.evict_scratch);
+        candidates.clear();
+        for (lane, flag_lane) in self.ballistic.lanes.iter().enumerate() {
+            for (i, (hot, cold)) in flag_lane.iter().enumerate() {
+                if in_slot(cold.type_slot) {
+                    let key = key(hot.alpha, hot.size, cold.spawn_tick);
+                    candidates.push((key, lane as u16, i as u32));
+                }
+            }
+        }
+        for (i, core) in self.analytic.iter_indexed_at(self.tick) {
+            if in_slot(core.type_slot) {
+                let key = key(core.alpha, core.size, core.spawn_tick);
+                candidates.push((key, ANALYTIC_LANE, i as u32));
+            }
+        }
+        for (i, particle) in self.spline_particles.iter().enumerate() {
+            let core = &particle.core;
+            if in_slot(core.type_slot) {
+                let key = key(core.alpha, core.size, core.spawn_tick);
+                candidates.push((key, SPLINE_LANE, i as u32));
+            }
+        }
+
+        if count < candidates.len() {
+            candidates.select_nth_unstable(count - 1);
+            candidates.truncate(count);
+        }
+
+        // Removing highest indices first keeps the remaining victim indices valid
+        // across `swap_remove`.
+        candidates.sort_unstable_by_key(|&(_, lane, index)| std::cmp::Reverse((lane, index)));
+        for &(_, lane, index) in &candidates {
+            self.remove_particle(ParticleLocation { lane, index }, DeathCause::Evicted);
+        }
+
+        self.evict_scratch = candidates;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec2;
+
+    use super::{OverflowPolicy, ParticleBudget};
+    use crate::core::{ParticleHandle, ParticleSpawn, ParticleSystem, ParticleTypeTrait};
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    enum Kind {
+        Spark,
+        Smoke,
+    }
+
+    impl ParticleTypeTrait for Kind {}
+
+    fn spark() -> ParticleSpawn<Kind> {
+        ParticleSpawn::new(Kind::Spark, 100, Vec2::ZERO, Vec2::ONE)
+    }
+
+    // One particle in each of a static, a moving and the analytic lane, so
+    // victims come from different lanes.
+    fn spread(
+        system: &mut ParticleSystem<Kind>,
+        spawns: [ParticleSpawn<Kind>; 3],
+    ) -> Vec<ParticleHandle> {
+        let [fixed, moving, analytic] = spawns;
+        let spawns = [
+            fixed,
+            moving.with_velocity(Vec2::X),
+            analytic.with_velocity(Vec2::Y).with_analytic(),
+        ];
+        spawns
+            .into_iter()
+            .map(|spawn| {
+                let handle = system.spawn_with_handle(spawn).unwrap();
+                system.step();
+                handle
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reject_new_drops_the_spawn() {
+        let mut system = ParticleSystem::new();
+        system.set_budget(ParticleBudget::new(3, OverflowPolicy::RejectNew));
+        let kept = spread(&mut system, [spark(), spark(), spark()]);
+
+        assert!(system.spawn_with_handle(spark()).is_none());
+        system.spawn_batch([spark(), spark()]);
+        assert!(kept.iter().all(|&handle| system.contains(handle)));
+        assert_eq!(system.len(), 3);
+        assert_eq!(system.budget_stats().rejected, 3);
+    }
+
+    #[test]
+    fn evict_oldest_removes_the_earliest_spawn() {
+        let mut system = ParticleSystem::new();
+        system.set_budget(ParticleBudget::new(3, OverflowPolicy::EvictOldest));
+        let old = spread(&mut system, [spark(), spark(), spark()]);
+
+        let new = system.spawn_with_handle(spark()).unwrap();
+        assert!(!system.contains(old[0]));
+        assert!(system.contains(old[1]) && system.contains(old[2]));
+        system.step();
+
+        system.spawn(spark());
+        assert!(!system.contains(old[1]));
+        assert!(system.contains(old[2]) && system.contains(new));
+        assert_eq!(system.len(), 3);
+        assert_eq!(system.budget_stats().evicted, 2);
+    }
+
+    #[test]
+    fn evict_lowest_alpha_removes_the_faintest() {
+        let mut system = ParticleSystem::new();
+        system.set_budget(ParticleBudget::new(3, OverflowPolicy::EvictLowestAlpha));
+        let old = spread(
+            &mut system,
+            [
+                spark().with_alpha(0.6),
+                spark().with_alpha(0.4),
+                spark().with_alpha(0.2),
+            ],
+        );
+
+        system.spawn(spark().with_alpha(0.5));
+        assert!(!system.contains(old[2]));
+        system.spawn(spark().with_alpha(0.9));
+        assert!(!system.contains(old[1]));
+        assert!(system.contains(old[0]));
+        assert_eq!(system.len(), 3);
+    }
+
+    #[test]
+    fn evict_smallest_removes_the_least_area() {
+        let mut system = ParticleSystem::new();
+        system.set_budget(ParticleBudget::new(3, OverflowPolicy::EvictSmallest));
+        let old = spread(
+            &mut system,
+            [
+                ParticleSpawn::new(Kind::Spark, 100, Vec2::ZERO, Vec2::new(1.0, 2.0)),
+                ParticleSpawn::new(Kind::Spark, 100, Vec2::ZERO, Vec2::new(4.0, 4.0)),
+                ParticleSpawn::new(Kind::Spark, 100, Vec2::ZERO, Vec2::new(3.0, 1.0)),
+            ],
+        );
+
+        system.spawn(ParticleSpawn::new(
+            Kind::Spark,
+            100,
+            Vec2::ZERO,
+            Vec2::splat(5.0),
+        ));
+        assert!(!system.contains(old[0]));
+        system.spawn(ParticleSpawn::new(
+            Kind::Spark,
+            100,
+            Vec2::ZERO,
+            Vec2::splat(5.0),
+        ));
+        assert!(!system.contains(old[2]));
+        assert!(system.contains(old[1]));
+        assert_eq!(system.len(), 3);
+    }
+
+    #[test]
+    fn thin_stays_under_the_cap() {
+        let mut system = ParticleSystem::new();
+        system.set_budget(ParticleBudget::new(
+            20,
+            OverflowPolicy::Thin {
+                start_fraction: 0.5,
+            },
+        ));
+        system.spawn_batch((0..100).map(|_| spark()));
+
+        let live = system.len();
+        assert!((10..=20).contains(&live), "{live} live");
+        assert_eq!(system.budget_stats().thinned, 100 - live as u64);
+    }
+
+    #[test]
+    fn type_budget_only_evicts_its_type() {
+        let mut system = ParticleSystem::new();
+        system.set_type_budget(
+            Kind::Smoke,
+            ParticleBudget::new(2, OverflowPolicy::EvictOldest),
+        );
+        let spark = system.spawn_with_handle(spark()).unwrap();
+        system.step();
+        let smoke = ParticleSpawn::new(Kind::Smoke, 100, Vec2::ZERO, Vec2::ONE);
+        let oldest = system.spawn_with_handle(smoke).unwrap();
+        system.step();
+        system.spawn(smoke);
+        system.step();
+        system.spawn(smoke);
+
+        assert!(system.contains(spark));
+        assert!(!system.contains(oldest));
+        assert_eq!(system.len(), 3);
+        assert_eq!(system.type_budget_stats(Kind::Smoke).evicted, 1);
+        assert_eq!(system.type_budget_stats(Kind::Spark).evicted, 0);
+    }
+
+    #[test]
+    fn evict_oldest_survives_the_tick_wrapping() {
+        let mut system = ParticleSystem::new();
+        system.tick = u32::MAX - 1;
+        system.set_budget(ParticleBudget::new(3, OverflowPolicy::EvictOldest));
+        // Spawned at ticks `MAX - 1`, `MAX` and `0`.
+        let old = spread(&mut system, [spark(), spark(), spark()]);
+
+        system.spawn(spark());
+        assert!(!system.contains(old[0]));
+        system.spawn(spark());
+        assert!(!system.contains(old[1]));
+        assert!(system.contains(old[2]));
+    }
+
+    // Every flag combination the frame below spawns, one particle each.
+    fn mixed(i: usize, path: crate::core::SplinePathId) -> ParticleSpawn<Kind> {
+        let spawn = spark();
+        match i % 7 {
+            0 => spawn,
+            1 => spawn.with_velocity(Vec2::X),
+            2 => spawn.with_velocity(Vec2::X).with_acceleration(Vec2::NEG_Y),
+            3 => spawn.with_size_velocity(-0.001).with_alpha_velocity(-0.001),
+            4 => spawn
+                .with_velocity(Vec2::Y)
+                .with_orientation(crate::core::Orientation::AlignToVelocity),
+            5 => spawn.with_velocity(Vec2::Y).with_analytic(),
+            _ => spawn
+                .with_spline_path(crate::core::SplinePathState {
+                    t: 0.0,
+                    strength: 1.0,
+                    path,
+                    arc_length: false,
+                })
+                .with_spline_velocity(0.01),
+        }
+    }
+
+    fn lane_capacities(system: &ParticleSystem<Kind>) -> Vec<(usize, usize, usize)> {
+        let mut capacities: Vec<_> = system
+            .ballistic
+            .lanes
+            .iter()
+            .map(|lane| {
+                (
+                    lane.hot.capacity(),
+                    lane.cold.capacity(),
+                    lane.previous.capacity(),
+                )
+            })
+            .collect();
+        capacities.push((
+            system.analytic.timing.capacity(),
+            system.analytic.spawns.capacity(),
+            0,
+        ));
+        capacities.push((
+            system.spline_particles.capacity(),
+            system.spline_previous.capacity(),
+            0,
+        ));
+        capacities
+    }
+
+    #[test]
+    fn full_budget_frames_keep_lane_capacity() {
+        let max = 700;
+        let mut system = ParticleSystem::new();
+        system.set_interpolation(true);
+        let path =
+            system.add_spline_path(crate::core::SplinePath::polyline(&[Vec2::ZERO, Vec2::X]));
+        system.set_budget(ParticleBudget::new(max, OverflowPolicy::EvictOldest));
+        // Only some combinations are in use when the budget is reserved.
+        system.spawn_batch((0..4).map(|i| mixed(i, path)));
+        system.reserve_budget();
+        let reserved = lane_capacities(&system);
+        assert!(system.capacity().ballistic >= max);
+
+        // A mixed frame sizes the lanes it uses first to the budget...
+        system.spawn_batch((0..max).map(|i| mixed(i, path)));
+        system.step();
+        let capacities = lane_capacities(&system);
+        assert_eq!(capacities[..4], reserved[..4]);
+        let capacity = system.capacity();
+        assert!(capacity.ballistic >= max);
+        assert!(capacity.analytic >= max && capacity.spline >= max);
+
+        // ...and frames that pile the whole budget into one flag combination
+        // at a time move nothing.
+        for combination in 0..7 {
+            system.spawn_batch((0..max).map(|_| mixed(combination, path)));
+            system.step();
+            assert_eq!(system.len(), max);
+        }
+        assert_eq!(lane_capacities(&system), capacities);
+        assert_eq!(system.capacity(), capacity);
+    }
+}
@@ -0,0 +1,387 @@
+use glam::{Affine2, Vec2};
+
+use super::anchor::NO_ANCHOR;
+use super::interpolation::PreviousState;
+use super::particle_model::{
+    Orientation, ParticleCore, ParticlePayload, ParticleTypeTrait, SplineMotion,
+    HAS_SPLINE_ARC_LENGTH,
+};
+use super::particle_system::ParticleSystem;
+use super::particle_view::ParticleMut;
+
+// A similarity transform applied to the whole simulation. `rotation` is in
+// degrees like particle rotations, and `length_scale` is the transform's
+// uniform scale.
+#[derive(Clone, Copy, Debug)]
+struct FrameChange {
+    transform: Affine2,
+    rotation: f32,
+    length_scale: f32,
+}
+
+impl FrameChange {
+    #[inline(always)]
+    fn point(&self, point: Vec2) -> Vec2 {
+        self.transform.transform_point2(point)
+    }
+
+    #[inline(always)]
+    fn vector(&self, vector: Vec2) -> Vec2 {
+        self.transform.transform_vector2(vector)
+    }
+
+    #[inline(always)]
+    fn orientation(&self, orientation: Orientation) -> Orientation {
+        match orientation {
+            Orientation::FacePoint(point) => Orientation::FacePoint(self.point(point)),
+            orientation => orientation,
+        }
+    }
+
+    #[inline(always)]
+    fn previous(&self, previous: &mut PreviousState) {
+        previous.pos = self.point(previous.pos);
+        previous.rotation += self.rotation;
+    }
+
+    #[inline(always)]
+    fn core<T, U>(&self, core: &mut ParticleCore<T, U>)
+    where
+        T: ParticleTypeTrait,
+        U: ParticlePayload,
+    {
+        core.pos = self.point(core.pos);
+        core.velocity = self.vector(core.velocity);
+        core.acceleration = self.vector(core.acceleration);
+        core.rotation += self.rotation;
+        core.orientation = self.orientation(core.orientation);
+    }
+
+    // An unanchored control point is a position and moves with the frame. An
+    // anchored one is an offset from an anchor the caller moves, so it only
+    // turns and scales. The linear part is the same either way; the per-point
+    // anchor mask decides where the translation lands in the coefficients.
+    fn spline(&self, spline: &mut SplineMotion) {
+        let offset = self.transform.translation;
+        let anchored = spline
+            .anchors
+            .map(|anchor| if anchor == NO_ANCHOR { 0.0 } else { 1.0 });
+        let a = self.vector(spline.bezier_a);
+        let b = self.vector(spline.bezier_b);
+        let c = self.vector(spline.bezier_c);
+        if spline.path.is_none() {
+            // `a = p1 - 2 p2 + p3`, `b = 2 (p2 - p1)` and `c = p1`, with the
+            // translation on the unanchored points only.
+            let [start, control, end] = anchored.map(|anchored| 1.0 - anchored);
+            spline.bezier_a = a + offset * (start - 2.0 * control + end);
+            spline.bezier_b = b + offset * (2.0 * (control - start));
+            spline.bezier_c = c + offset * start;
+        } else {
+            // The shared path already moved, so every anchor the caller moves
+            // would shift the particle a second time. The correction terms
+            // take that back out.
+            let [start, control, end] = anchored;
+            spline.bezier_a = a;
+            spline.bezier_b = b - offset * (end - start);
+            spline.bezier_c = c - offset * (control + start);
+        }
+        if spline.has(HAS_SPLINE_ARC_LENGTH) {
+            spline.t *= self.length_scale;
+            spline.velocity *= self.length_scale;
+            spline.acceleration *= self.length_scale;
+        }
+    }
+}
+
+impl<T, U> ParticleSystem<T, U>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    // Runs `f` on every live particle. Analytic particles have no stored state
+    // to edit, so they move to the ballistic lane first. Particles given a
+    // velocity here start moving on the next step.
+    pub fn for_each_particle_mut(&mut self, mut f: impl FnMut(&mut ParticleMut<'_, T, U>)) {
+        self.materialize_analytic();
+        for lane in &mut self.ballistic.lanes {
+            for (hot, cold) in lane.hot.iter_mut().zip(lane.cold.iter_mut()) {
+                f(&mut ParticleMut::ballistic(hot, cold));
+            }
+        }
+        for particle in &mut self.spline_particles {
+            f(&mut ParticleMut::spline(particle));
+        }
+        self.ballistic.promote_moving(&mut self.handles);
+        self.invalidate_spatial_index();
+    }
+
+    // Shifts the whole simulation by `offset`, e.g. to rebase a floating-origin
+    // world around the camera. Interpolation state and trails move too, so
+    // nothing streaks across the jump, and spline curves, paths and shockwaves
+    // go with their particles. Anchors and time dilation zones are gameplay
+    // positions and are left to the caller; anchored control points stay at
+    // their offsets from wherever the caller puts the anchors.
+    pub fn translate(&mut self, offset: Vec2) {
+        self.change_frame(FrameChange {
+            transform: Affine2::from_translation(offset),
+            rotation: 0.0,
+            length_scale: 1.0,
+        });
+    }
+
+    // `translate` for a rotation of `degrees` around `pivot`. Velocities,
+    // accelerations and particle rotations turn with it.
+    pub fn rotate_around(&mut self, pivot: Vec2, degrees: f32) {
+        self.change_frame(FrameChange {
+            transform: Affine2::from_translation(pivot)
+                * Affine2::from_angle(degrees.to_radians())
+                * Affine2::from_translation(-pivot),
+            rotation: degrees,
+            length_scale: 1.0,
+        });
+    }
+
+    // `translate` for a uniform scale by `factor` around `pivot`. Velocities and
+    // accelerations scale with it; particle sizes do not.
+    pub fn scale_around(&mut self, pivot: Vec2, factor: f32) {
+        debug_assert!(factor > 0.0, "scale factor must be positive");
+        self.change_frame(FrameChange {
+            transform: Affine2::from_translation(pivot)
+                * Affine2::from_scale(Vec2::splat(factor))
+                * Affine2::from_translation(-pivot),
+            rotation: 0.0,
+            length_scale: factor,
+        });
+    }
+
+    // Analytic particles are evaluated from their spawn state, which transforms
+    // exactly, so they stay in their lane.
+    fn change_frame(&mut self, frame: FrameChange) {
+        for lane in &mut self.ballistic.lanes {
+            for (hot, cold) in lane.hot.iter_mut().zip(lane.cold.iter_mut()) {
+                hot.pos = frame.point(hot.pos);
+                hot.velocity = frame.vector(hot.velocity);
+                hot.acceleration = frame.vector(hot.acceleration);
+                hot.rotation += frame.rotation;
+                cold.orientation = frame.orientation(cold.orientation);
+            }
+            for previous in &mut lane.previous {
+                frame.previous(previous);
+            }
+        }
+        for core in &mut self.analytic.spawns {
+            frame.core(core);
+        }
+        for particle in &mut self.spline_particles {
+            frame.core(&mut particle.core);
+            frame.spline(&mut particle.spline);
+        }
+        for previous in &mut self.spline_previous {
+            frame.previous(previous);
+        }
+        for particle in &mut self.deaths.dying {
+            particle.pos = frame.point(particle.pos);
+            particle.rotation += frame.rotation;
+        }
+        for path in &mut self.spline_paths {
+            path.transform(frame.transform, frame.length_scale);
+        }
+        for wave in &mut self.shockwaves {
+            wave.transform(frame.transform, frame.length_scale);
+        }
+        self.trails.transform(frame.transform);
+        self.invalidate_spatial_index();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Affine2, Vec2};
+
+    use crate::core::{
+        AnchorId, Orientation, ParticleHandle, ParticleSpawn, ParticleSystem, ParticleTypeTrait,
+        SplinePath, SplinePathState, SplineState,
+    };
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    struct Bead;
+    impl ParticleTypeTrait for Bead {}
+
+    const EPSILON: f32 = 1e-3;
+
+    // Which of start, control and end follow an anchor.
+    const MASKS: [[bool; 3]; 5] = [
+        [false, false, false],
+        [false, false, true],
+        [true, false, false],
+        [false, true, true],
+        [true, true, true],
+    ];
+
+    fn anchored(
+        spawn: ParticleSpawn<Bead>,
+        mask: [bool; 3],
+        anchors: [AnchorId; 3],
+    ) -> ParticleSpawn<Bead> {
+        let mut spawn = spawn;
+        if mask[0] {
+            spawn = spawn.with_spline_start_anchor(anchors[0]);
+        }
+        if mask[1] {
+            spawn = spawn.with_spline_control_anchor(anchors[1]);
+        }
+        if mask[2] {
+            spawn = spawn.with_spline_end_anchor(anchors[2]);
+        }
+        spawn
+    }
+
+    // Spawns a frozen particle on a curve and on a shared path for every mask.
+    // Strength `1` snaps each onto its curve every step, so its position is
+    // the curve point itself.
+    fn setup() -> (ParticleSystem<Bead>, Vec<ParticleHandle>, [AnchorId; 3]) {
+        let mut system = ParticleSystem::new();
+        let anchors = [
+            system.add_anchor(Vec2::new(-4.0, 2.0)),
+            system.add_anchor(Vec2::new(6.0, 12.0)),
+            system.add_anchor(Vec2::new(20.0, -8.0)),
+        ];
+        let path = system.add_spline_path(SplinePath::catmull_rom(&[
+            Vec2::ZERO,
+            Vec2::new(10.0, 5.0),
+            Vec2::new(25.0, -5.0),
+            Vec2::new(40.0, 0.0),
+        ]));
+        let curve = ParticleSpawn::new(Bead, 100, Vec2::ZERO, Vec2::ONE).with_spline(SplineState {
+            t: 0.3,
+            strength: 1.0,
+            point_1: Vec2::new(1.0, 2.0),
+            point_2: Vec2::new(10.0, 20.0),
+            point_3: Vec2::new(30.0, -3.0),
+        });
+        let on_path = ParticleSpawn::new(Bead, 100, Vec2::ZERO, Vec2::ONE).with_spline_path(
+            SplinePathState {
+                t: 0.6,
+                strength: 1.0,
+                path,
+                arc_length: false,
+            },
+        );
+
+        let handles = MASKS
+            .iter()
+            .flat_map(|&mask| {
+                [
+                    anchored(curve, mask, anchors),
+                    anchored(on_path, mask, anchors),
+                ]
+            })
+            .map(|spawn| system.spawn_with_handle(spawn).unwrap())
+            .collect();
+        (system, handles, anchors)
+    }
+
+    // Applies `change` to the system and `transform` to the anchors, as a
+    // caller moving its gameplay positions along would, and checks that every
+    // particle lands on the transformed curve.
+    fn check_frame_change(change: impl FnOnce(&mut ParticleSystem<Bead>), transform: Affine2) {
+        let (mut system, handles, anchors) = setup();
+        system.step();
+        let before: Vec<_> = handles
+            .iter()
+            .map(|&handle| system.get(handle).unwrap().pos)
+            .collect();
+
+        change(&mut system);
+        for anchor in anchors {
+            let moved = transform.transform_point2(system.anchor(anchor));
+            system.set_anchor(anchor, moved);
+        }
+        system.step();
+
+        for (i, (&handle, &pos)) in handles.iter().zip(&before).enumerate() {
+            let expected = transform.transform_point2(pos);
+            let actual = system.get(handle).unwrap().pos;
+            let (mask, kind) = (MASKS[i / 2], ["curve", "path"][i % 2]);
+            assert!(
+                actual.abs_diff_eq(expected, EPSILON),
+                "{kind} {mask:?}: {actual} vs {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn translate_moves_curves_once_per_mask() {
+        let offset = Vec2::new(100.0, -30.0);
+        check_frame_change(
+            |system| system.translate(offset),
+            Affine2::from_translation(offset),
+        );
+    }
+
+    #[test]
+    fn rotate_turns_curves_once_per_mask() {
+        let pivot = Vec2::new(5.0, -7.0);
+        check_frame_change(
+            |system| system.rotate_around(pivot, 90.0),
+            Affine2::from_translation(pivot)
+                * Affine2::from_angle(90f32.to_radians())
+                * Affine2::from_translation(-pivot),
+        );
+    }
+
+    #[test]
+    fn scale_grows_curves_once_per_mask() {
+        let pivot = Vec2::new(-3.0, 8.0);
+        check_frame_change(
+            |system| system.scale_around(pivot, 2.0),
+            Affine2::from_translation(pivot)
+                * Affine2::from_scale(Vec2::splat(2.0))
+                * Affine2::from_translation(-pivot),
+        );
+    }
+
+    #[test]
+    fn repeated_frame_changes_compose() {
+        let first = Vec2::new(7.0, 7.0);
+        let second = Affine2::from_angle(45f32.to_radians());
+        check_frame_change(
+            |system| {
+                system.translate(first);
+                system.rotate_around(Vec2::ZERO, 45.0);
+            },
+            second * Affine2::from_translation(first),
+        );
+    }
+
+    #[test]
+    fn anchored_path_tangents_survive_a_translation() {
+        let (mut system, _, anchors) = setup();
+        let path = system.add_spline_path(SplinePath::polyline(&[Vec2::ZERO, Vec2::X * 50.0]));
+        let spawn = ParticleSpawn::new(Bead, 100, Vec2::ZERO, Vec2::ONE).with_spline_path(
+            SplinePathState {
+                t: 0.5,
+                strength: 1.0,
+                path,
+                arc_length: false,
+            },
+        );
+        let facing = system
+            .spawn_with_handle(
+                spawn
+                    .with_spline_start_anchor(anchors[0])
+                    .with_orientation(Orientation::AlignToSplineTangent),
+            )
+            .unwrap();
+        system.step();
+        let rotation = system.get(facing).unwrap().rotation;
+
+        let offset = Vec2::new(-60.0, 25.0);
+        system.translate(offset);
+        for anchor in anchors {
+            system.set_anchor(anchor, system.anchor(anchor) + offset);
+        }
+        system.step();
+        assert!((system.get(facing).unwrap().rotation - rotation).abs() < 1e-3);
+    }
+}
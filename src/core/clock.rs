@@ -0,0 +1,325 @@
+use super::emitter::Emitter;
+use super::particle_model::{ParticlePayload, ParticleTypeTrait};
+use super::particle_system::ParticleSystem;
+
+// What to do when a frame owes more steps than the simulation should run at
+// once, so a slow frame cannot make the next one slower still.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CatchUpPolicy {
+    // Run everything owed, however long it takes.
+    Unbounded,
+    // Run at most `max_steps` and drop the rest of the backlog; the
+    // simulation falls behind wall time instead of spiralling.
+    DropExcess { max_steps: u32 },
+    // Run at most `max_steps` and carry the rest into later frames, keeping at
+    // most `max_steps` steps of backlog.
+    Carry { max_steps: u32 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrameSteps {
+    pub steps: u32,
+    // How far the frame sits between the last two steps, for
+    // `for_each_particle_interpolated`.
+    pub alpha: f32,
+}
+
+// Fixed-step accumulator: feed it real frame time, run the steps it returns,
+// then draw at its interpolation factor.
+#[derive(Clone, Debug)]
+pub struct SimulationClock {
+    timestep: f32,
+    time_scale: f32,
+    policy: CatchUpPolicy,
+    paused: bool,
+    queued_steps: u32,
+    accumulator: f32,
+    dropped_steps: u64,
+}
+
+impl SimulationClock {
+    pub fn new(timestep: f32) -> Self {
+        debug_assert!(timestep > 0.0, "timestep must be positive");
+        Self {
+            timestep,
+            time_scale: 1.0,
+            policy: CatchUpPolicy::DropExcess { max_steps: 4 },
+            paused: false,
+            queued_steps: 0,
+            accumulator: 0.0,
+            dropped_steps: 0,
+        }
+    }
+
+    pub fn with_policy(mut self, policy: CatchUpPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn with_time_scale(mut self, time_scale: f32) -> Self {
+        self.set_time_scale(time_scale);
+        self
+    }
+
+    pub fn timestep(&self) -> f32 {
+        self.timestep
+    }
+
+    pub fn policy(&self) -> CatchUpPolicy {
+        self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: CatchUpPolicy) {
+        self.policy = policy;
+    }
+
+    pub fn time_scale(&self) -> f32 {
+        self.time_scale
+    }
+
+    // Scales frame time before it is accumulated; the timestep itself never
+    // changes, so slow motion runs fewer steps rather than shorter ones.
+    pub fn set_time_scale(&mut self, time_scale: f32) {
+        self.time_scale = time_scale.max(0.0);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    // Pausing keeps the accumulator, so the interpolation factor holds still
+    // and resuming picks up where the clock stopped.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    // Queues one step for the next `advance`, paused or not.
+    pub fn step_once(&mut self) {
+        self.queued_steps = self.queued_steps.saturating_add(1);
+    }
+
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / self.timestep).clamp(0.0, 1.0)
+    }
+
+    // Steps the catch-up policy has thrown away since the clock was created.
+    pub fn dropped_steps(&self) -> u64 {
+        self.dropped_steps
+    }
+
+    // Accumulates `frame_time` seconds and returns how many steps to run now.
+    pub fn advance(&mut self, frame_time: f32) -> FrameSteps {
+        let queued = std::mem::take(&mut self.queued_steps);
+        if self.paused {
+            return FrameSteps {
+                steps: queued,
+                alpha: self.alpha(),
+            };
+        }
+
+        let frame_time = if frame_time.is_finite() {
+            frame_time.max(0.0)
+        } else {
+            0.0
+        };
+        self.accumulator += frame_time * self.time_scale;
+
+        let owed = (self.accumulator / self.timestep).floor();
+        let mut steps = owed.min(u32::MAX as f32) as u32;
+        self.accumulator -= steps as f32 * self.timestep;
+        // Rounding can leave the remainder a hair outside `0..timestep`.
+        self.accumulator = self.accumulator.clamp(0.0, self.timestep);
+
+        match self.policy {
+            CatchUpPolicy::Unbounded => {}
+            CatchUpPolicy::DropExcess { max_steps } => {
+                if steps > max_steps {
+                    self.dropped_steps += (steps - max_steps) as u64;
+                    steps = max_steps;
+                    self.accumulator = 0.0;
+                }
+            }
+            CatchUpPolicy::Carry { max_steps } => {
+                if steps > max_steps {
+                    let backlog = steps - max_steps;
+                    let carried = backlog.min(max_steps);
+                    self.dropped_steps += (backlog - carried) as u64;
+                    self.accumulator += carried as f32 * self.timestep;
+                    steps = max_steps;
+                }
+            }
+        }
+
+        FrameSteps {
+            steps: steps.saturating_add(queued),
+            alpha: self.alpha(),
+        }
+    }
+}
+
+impl<T, U> ParticleSystem<T, U>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    // Runs the steps `clock` owes for `frame_time` seconds.
+    pub fn update(&mut self, clock: &mut SimulationClock, frame_time: f32) -> FrameSteps {
+        let frame = clock.advance(frame_time);
+        for _ in 0..frame.steps {
+            self.step();
+        }
+        frame
+    }
+
+    // `update` with `emitter` emitting before each step.
+    pub fn update_with(
+        &mut self,
+        clock: &mut SimulationClock,
+        frame_time: f32,
+        emitter: &mut impl Emitter<T, U>,
+    ) -> FrameSteps {
+        let frame = clock.advance(frame_time);
+        for _ in 0..frame.steps {
+            self.emit(emitter);
+            self.step();
+        }
+        frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A power of two, so accumulated frame times stay exact.
+    const STEP: f32 = 0.25;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    struct Tick;
+    impl ParticleTypeTrait for Tick {}
+
+    fn clock(policy: CatchUpPolicy) -> SimulationClock {
+        SimulationClock::new(STEP).with_policy(policy)
+    }
+
+    #[test]
+    fn unbounded_runs_every_owed_step() {
+        let mut clock = clock(CatchUpPolicy::Unbounded);
+        let frame = clock.advance(2.5);
+        assert_eq!(
+            frame,
+            FrameSteps {
+                steps: 10,
+                alpha: 0.0
+            }
+        );
+
+        assert_eq!(clock.advance(0.375).steps, 1);
+        assert_eq!(clock.alpha(), 0.5);
+        assert_eq!(
+            clock.advance(0.125),
+            FrameSteps {
+                steps: 1,
+                alpha: 0.0
+            }
+        );
+        assert_eq!(clock.dropped_steps(), 0);
+    }
+
+    #[test]
+    fn drop_excess_discards_the_backlog() {
+        let mut clock = clock(CatchUpPolicy::DropExcess { max_steps: 4 });
+        // Ten and a half steps owed: the remainder goes with the backlog.
+        let frame = clock.advance(2.625);
+        assert_eq!(
+            frame,
+            FrameSteps {
+                steps: 4,
+                alpha: 0.0
+            }
+        );
+        assert_eq!(clock.dropped_steps(), 6);
+
+        assert_eq!(clock.advance(0.0).steps, 0);
+        assert_eq!(clock.advance(0.5).steps, 2);
+        assert_eq!(clock.dropped_steps(), 6);
+    }
+
+    #[test]
+    fn carry_spreads_the_backlog_over_later_frames() {
+        let mut clock = clock(CatchUpPolicy::Carry { max_steps: 4 });
+        // Ten and a half steps owed: four now, four carried, two dropped.
+        let frame = clock.advance(2.625);
+        assert_eq!(frame.steps, 4);
+        assert_eq!(frame.alpha, 1.0);
+        assert_eq!(clock.dropped_steps(), 2);
+
+        // The carried steps and the half-step remainder survive.
+        assert_eq!(
+            clock.advance(0.0),
+            FrameSteps {
+                steps: 4,
+                alpha: 0.5
+            }
+        );
+        assert_eq!(
+            clock.advance(0.125),
+            FrameSteps {
+                steps: 1,
+                alpha: 0.0
+            }
+        );
+        assert_eq!(clock.dropped_steps(), 2);
+    }
+
+    #[test]
+    fn pause_and_time_scale_hold_the_accumulator() {
+        let mut clock = SimulationClock::new(STEP).with_time_scale(0.5);
+        assert_eq!(
+            clock.advance(0.25),
+            FrameSteps {
+                steps: 0,
+                alpha: 0.5
+            }
+        );
+
+        clock.set_paused(true);
+        assert_eq!(
+            clock.advance(10.0),
+            FrameSteps {
+                steps: 0,
+                alpha: 0.5
+            }
+        );
+        clock.step_once();
+        assert_eq!(clock.advance(10.0).steps, 1);
+
+        clock.set_paused(false);
+        assert_eq!(
+            clock.advance(0.25),
+            FrameSteps {
+                steps: 1,
+                alpha: 0.0
+            }
+        );
+    }
+
+    #[test]
+    fn update_runs_the_owed_steps() {
+        let mut system = ParticleSystem::<Tick>::new();
+        let mut clock = clock(CatchUpPolicy::DropExcess { max_steps: 4 });
+
+        let mut emitted = 0;
+        let frame = system.update_with(&mut clock, 0.75, &mut |_: &mut Vec<_>| emitted += 1);
+        assert_eq!(frame.steps, 3);
+        assert_eq!(emitted, 3);
+        assert_eq!(system.tick(), 3);
+
+        system.update(&mut clock, 10.0);
+        assert_eq!(system.tick(), 7);
+    }
+}
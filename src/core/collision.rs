@@ -0,0 +1,379 @@
+use std::hash::Hash;
+use std::ops::Range;
+
+use glam::{IVec2, Vec2};
+
+use super::ballistic_lanes::BallisticLanes;
+use super::handle::ParticleLocation;
+use super::particle_model::{ParticlePayload, ParticleTypeTrait, HAS_VELOCITY};
+use super::particle_system::ParticleSystem;
+use super::particle_types::TypeTable;
+use super::spatial::SpatialIndex;
+
+// Circle-vs-circle contact between ballistic particles of the types marked
+// with `set_collides`. Each particle is a circle whose diameter is the larger
+// side of its size, with mass growing with its area. Overlapping pairs are
+// pushed apart by `position_correction` of their overlap and bounce off each
+// other with `restitution`, over `iterations` passes per step.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ParticleCollision {
+    pub restitution: f32,
+    pub position_correction: f32,
+    pub iterations: u32,
+}
+
+impl ParticleCollision {
+    pub fn new(restitution: f32) -> Self {
+        Self {
+            restitution,
+            position_correction: 0.8,
+            iterations: 4,
+        }
+    }
+
+    // The share of each pair's overlap removed per pass, `0..=1`. Below `1`
+    // piles settle without jitter over a few steps.
+    pub fn with_position_correction(mut self, position_correction: f32) -> Self {
+        self.position_correction = position_correction.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn with_iterations(mut self, iterations: u32) -> Self {
+        self.iterations = iterations;
+        self
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Body {
+    pos: Vec2,
+    velocity: Vec2,
+    radius: f32,
+    inverse_mass: f32,
+    location: ParticleLocation,
+}
+
+// The solver and its per-step scratch. The broadphase is a spatial hash with
+// cells as wide as the largest collider, so every contact lies within a cell's
+// 3 x 3 neighborhood. Pairs are found once per step and reused by every pass.
+#[derive(Clone, Debug)]
+pub(crate) struct CollisionSolver {
+    settings: ParticleCollision,
+    index: SpatialIndex,
+    cells: Vec<(IVec2, Range<usize>)>,
+    bodies: Vec<Body>,
+    pairs: Vec<(u32, u32)>,
+}
+
+impl CollisionSolver {
+    fn new(settings: ParticleCollision) -> Self {
+        Self {
+            settings,
+            index: SpatialIndex::new(1.0),
+            cells: Vec::new(),
+            bodies: Vec::new(),
+            pairs: Vec::new(),
+        }
+    }
+
+    // Returns whether a collider in a lane without velocity was set moving.
+    fn resolve<T, U>(&mut self, ballistic: &mut BallisticLanes<T, U>, types: &TypeTable<T>) -> bool
+    where
+        T: ParticleTypeTrait,
+        U: ParticlePayload,
+    {
+        let mut colliders = 0;
+        let mut max_diameter = 0.0f32;
+        for lane in &ballistic.lanes {
+            for (hot, cold) in lane.iter() {
+                if types.collides(cold.type_slot) {
+                    colliders += 1;
+                    max_diameter = max_diameter.max(hot.size.abs().max_element());
+                }
+            }
+        }
+        if colliders < 2 || max_diameter <= 0.0 {
+            return false;
+        }
+
+        self.index.set_cell_size(max_diameter);
+        for lane in &ballistic.lanes {
+            for (i, (hot, cold)) in lane.iter().enumerate() {
+                if types.collides(cold.type_slot) {
+                    self.index.stage(hot.pos, lane.index, i);
+                }
+            }
+        }
+        self.index.finish();
+
+        let keys = self.index.keys();
+        self.bodies.clear();
+        self.bodies.extend(keys.iter().map(|key| {
+            let hot = &ballistic.lanes[key.location.lane as usize].hot[key.location.index as usize];
+            let radius = hot.size.abs().max_element() * 0.5;
+            Body {
+                pos: hot.pos,
+                velocity: hot.velocity,
+                radius,
+                inverse_mass: if radius > 0.0 {
+                    1.0 / (radius * radius)
+                } else {
+                    0.0
+                },
+                location: key.location,
+            }
+        }));
+
+        self.pairs.clear();
+        let mut group_start = 0;
+        while group_start < keys.len() {
+            let cell = keys[group_start].cell;
+            let group_len = keys[group_start..]
+                .iter()
+                .take_while(|key| key.cell == cell)
+                .count();
+            self.index.neighborhood(cell, &mut self.cells);
+
+            for a in group_start..group_start + group_len {
+                let body = &self.bodies[a];
+                for (cell, run) in &self.cells {
+                    for b in run.clone() {
+                        let other = &self.bodies[b];
+                        let reach = body.radius + other.radius;
+                        if b > a
+                            && keys[b].cell == *cell
+                            && body.pos.distance_squared(other.pos) < reach * reach
+                        {
+                            self.pairs.push((a as u32, b as u32));
+                        }
+                    }
+                }
+            }
+            group_start += group_len;
+        }
+
+        for _ in 0..self.settings.iterations {
+            for &(a, b) in &self.pairs {
+                solve_pair(&mut self.bodies, a as usize, b as usize, &self.settings);
+            }
+        }
+
+        let mut moved_static = false;
+        for body in &self.bodies {
+            let lane = &mut ballistic.lanes[body.location.lane as usize];
+            let hot = &mut lane.hot[body.location.index as usize];
+            hot.pos = body.pos;
+            hot.velocity = body.velocity;
+            moved_static |= (lane.key & HAS_VELOCITY) == 0 && body.velocity != Vec2::ZERO;
+        }
+        moved_static
+    }
+}
+
+// Separates one overlapping pair along the line between their centers, in
+// proportion to their inverse masses, and turns their approach speed around
+// at `restitution`.
+#[inline(always)]
+fn solve_pair(bodies: &mut [Body], a: usize, b: usize, settings: &ParticleCollision) {
+    let (first, second) = bodies.split_at_mut(b);
+    let (a, b) = (&mut first[a], &mut second[0]);
+    let inverse_mass = a.inverse_mass + b.inverse_mass;
+    let offset = b.pos - a.pos;
+    let distance = offset.length();
+    let overlap = a.radius + b.radius - distance;
+    if overlap <= 0.0 || inverse_mass == 0.0 {
+        return;
+    }
+    // Coincident centers have no direction between them; split them sideways
+    // rather than leave them stuck.
+    let normal = if distance > 0.0 {
+        offset / distance
+    } else {
+        Vec2::X
+    };
+
+    let correction = normal * (overlap * settings.position_correction / inverse_mass);
+    a.pos -= correction * a.inverse_mass;
+    b.pos += correction * b.inverse_mass;
+
+    let approach = (b.velocity - a.velocity).dot(normal);
+    if approach < 0.0 {
+        let impulse = normal * (-(1.0 + settings.restitution) * approach / inverse_mass);
+        a.velocity -= impulse * a.inverse_mass;
+        b.velocity += impulse * b.inverse_mass;
+    }
+}
+
+impl<T, U> ParticleSystem<T, U>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    // Turns on collisions between particles of the types marked with
+    // `set_collides`, resolved at the end of every step. Like affectors, this
+    // moves `step` onto the stepwise path and keeps new spawns out of the
+    // analytic lane. Spline particles never collide.
+    pub fn set_collision(&mut self, collision: ParticleCollision) {
+        self.collision = Some(CollisionSolver::new(collision));
+        self.materialize_analytic();
+    }
+
+    pub fn clear_collision(&mut self) {
+        self.collision = None;
+    }
+
+    pub fn set_collides(&mut self, particle_type: T, collides: bool)
+    where
+        T: Hash + Eq,
+    {
+        self.type_settings_mut(particle_type).collides = collides;
+    }
+
+    pub(crate) fn run_collision(&mut self) {
+        let Some(mut solver) = self.collision.take() else {
+            return;
+        };
+        if solver.resolve(&mut self.ballistic, &self.types) {
+            self.ballistic.promote_moving(&mut self.handles);
+        }
+        self.collision = Some(solver);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{ParticleHandle, ParticleSpawn};
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    enum Kind {
+        Ball,
+        Ghost,
+    }
+    impl ParticleTypeTrait for Kind {}
+
+    fn spawn(
+        system: &mut ParticleSystem<Kind>,
+        kind: Kind,
+        pos: Vec2,
+        velocity: Vec2,
+    ) -> ParticleHandle {
+        let spawn = ParticleSpawn::new(kind, 100, pos, Vec2::ONE);
+        let spawn = if velocity == Vec2::ZERO {
+            spawn
+        } else {
+            spawn.with_velocity(velocity)
+        };
+        system.spawn_with_handle(spawn).unwrap()
+    }
+
+    fn system(restitution: f32) -> ParticleSystem<Kind> {
+        let mut system = ParticleSystem::new();
+        system.set_collides(Kind::Ball, true);
+        system.set_collision(
+            ParticleCollision::new(restitution)
+                .with_position_correction(1.0)
+                .with_iterations(1),
+        );
+        system
+    }
+
+    #[test]
+    fn elastic_pair_separates_and_bounces() {
+        let mut system = system(1.0);
+        let a = spawn(&mut system, Kind::Ball, Vec2::ZERO, Vec2::new(0.5, 0.0));
+        let b = spawn(
+            &mut system,
+            Kind::Ball,
+            Vec2::new(1.5, 0.0),
+            Vec2::new(-0.5, 0.0),
+        );
+        system.step();
+
+        // Integrated to half a diameter apart, then pushed to touching.
+        assert_eq!(system.get(a).unwrap().pos, Vec2::new(0.25, 0.0));
+        assert_eq!(system.get(b).unwrap().pos, Vec2::new(1.25, 0.0));
+
+        system.step();
+        assert_eq!(system.get(a).unwrap().pos, Vec2::new(-0.25, 0.0));
+        assert_eq!(system.get(b).unwrap().pos, Vec2::new(1.75, 0.0));
+    }
+
+    #[test]
+    fn inelastic_pair_stops_dead() {
+        let mut system = system(0.0);
+        let a = spawn(&mut system, Kind::Ball, Vec2::ZERO, Vec2::new(0.5, 0.0));
+        let b = spawn(
+            &mut system,
+            Kind::Ball,
+            Vec2::new(1.5, 0.0),
+            Vec2::new(-0.5, 0.0),
+        );
+        system.step();
+        system.step();
+
+        assert_eq!(system.get(a).unwrap().pos, Vec2::new(0.25, 0.0));
+        assert_eq!(system.get(b).unwrap().pos, Vec2::new(1.25, 0.0));
+    }
+
+    #[test]
+    fn resting_collider_is_knocked_moving() {
+        let mut system = system(1.0);
+        let ball = spawn(&mut system, Kind::Ball, Vec2::ZERO, Vec2::new(0.5, 0.0));
+        let rest = spawn(&mut system, Kind::Ball, Vec2::new(1.0, 0.0), Vec2::ZERO);
+        system.step();
+        system.step();
+
+        // Equal masses trade velocities; the resting one leaves its lane.
+        assert_eq!(system.get(ball).unwrap().pos, Vec2::new(0.25, 0.0));
+        assert_eq!(system.get(rest).unwrap().pos, Vec2::new(1.75, 0.0));
+    }
+
+    #[test]
+    fn only_marked_types_collide() {
+        let mut system = system(1.0);
+        let ball = spawn(&mut system, Kind::Ball, Vec2::ZERO, Vec2::ZERO);
+        let ghost = spawn(&mut system, Kind::Ghost, Vec2::new(0.5, 0.0), Vec2::ZERO);
+        system.step();
+
+        assert_eq!(system.get(ball).unwrap().pos, Vec2::ZERO);
+        assert_eq!(system.get(ghost).unwrap().pos, Vec2::new(0.5, 0.0));
+    }
+
+    #[test]
+    fn heavier_collider_moves_less() {
+        let mut system = system(0.0);
+        let small = spawn(&mut system, Kind::Ball, Vec2::ZERO, Vec2::ZERO);
+        let large = system
+            .spawn_with_handle(ParticleSpawn::new(
+                Kind::Ball,
+                100,
+                Vec2::new(1.0, 0.0),
+                Vec2::splat(2.0),
+            ))
+            .unwrap();
+        system.step();
+
+        // Half a unit of overlap, split one to four by inverse mass.
+        assert_eq!(system.get(small).unwrap().pos, Vec2::new(-0.4, 0.0));
+        assert_eq!(system.get(large).unwrap().pos, Vec2::new(1.1, 0.0));
+    }
+
+    #[test]
+    fn spawns_before_collision_and_clearing_it() {
+        let mut system = ParticleSystem::new();
+        system.set_collides(Kind::Ball, true);
+        let a = spawn(&mut system, Kind::Ball, Vec2::ZERO, Vec2::ZERO);
+        let b = spawn(&mut system, Kind::Ball, Vec2::new(0.5, 0.0), Vec2::ZERO);
+        system.set_collision(ParticleCollision::new(0.0).with_position_correction(1.0));
+        system.step();
+        assert_eq!(system.get(a).unwrap().pos, Vec2::new(-0.25, 0.0));
+        assert_eq!(system.get(b).unwrap().pos, Vec2::new(0.75, 0.0));
+
+        let c = spawn(&mut system, Kind::Ball, Vec2::new(0.25, 0.0), Vec2::ZERO);
+        system.clear_collision();
+        system.step();
+        assert_eq!(system.get(a).unwrap().pos, Vec2::new(-0.25, 0.0));
+        assert_eq!(system.get(c).unwrap().pos, Vec2::new(0.25, 0.0));
+    }
+}
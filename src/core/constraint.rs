@@ -0,0 +1,476 @@
+use glam::Vec2;
+
+use super::anchor::{AnchorId, AnchorTable};
+use super::ballistic_lanes::BallisticLanes;
+use super::handle::{HandleTable, ParticleHandle, ANALYTIC_LANE, SPLINE_LANE};
+use super::particle_model::{ParticlePayload, ParticleTypeTrait, SplineParticle, HAS_VELOCITY};
+use super::particle_system::ParticleSystem;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConstraintEnd {
+    Particle(ParticleHandle),
+    // Fixed to a live anchor position, which pins the other end to it.
+    Anchor(AnchorId),
+}
+
+// Keeps two ends `length` apart. `stiffness` is the share of the error
+// corrected per solver pass, `0..=1`, so how stiff a link ends up also
+// depends on its set's iteration count. A link stretched past
+// `break_stretch` times its length breaks; zero-length links such as pins
+// never do.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DistanceConstraint {
+    pub a: ConstraintEnd,
+    pub b: ConstraintEnd,
+    pub length: f32,
+    pub stiffness: f32,
+    pub break_stretch: f32,
+}
+
+impl DistanceConstraint {
+    pub fn new(a: ConstraintEnd, b: ConstraintEnd, length: f32) -> Self {
+        Self {
+            a,
+            b,
+            length,
+            stiffness: 1.0,
+            break_stretch: f32::INFINITY,
+        }
+    }
+
+    pub fn with_stiffness(mut self, stiffness: f32) -> Self {
+        self.stiffness = stiffness.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn with_break_stretch(mut self, break_stretch: f32) -> Self {
+        self.break_stretch = break_stretch;
+        self
+    }
+}
+
+// A group of links solved together, such as one rope or banner.
+#[derive(Clone, Debug)]
+pub struct ConstraintSet {
+    constraints: Vec<DistanceConstraint>,
+    iterations: u32,
+}
+
+impl Default for ConstraintSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConstraintSet {
+    pub fn new() -> Self {
+        Self {
+            constraints: Vec::new(),
+            iterations: 8,
+        }
+    }
+
+    // Solver passes per step. More passes make long chains stretch less.
+    pub fn with_iterations(mut self, iterations: u32) -> Self {
+        self.iterations = iterations;
+        self
+    }
+
+    pub fn add(&mut self, constraint: DistanceConstraint) {
+        self.constraints.push(constraint);
+    }
+
+    pub fn link(&mut self, a: ParticleHandle, b: ParticleHandle, length: f32) {
+        self.add(DistanceConstraint::new(
+            ConstraintEnd::Particle(a),
+            ConstraintEnd::Particle(b),
+            length,
+        ));
+    }
+
+    pub fn pin(&mut self, particle: ParticleHandle, anchor: AnchorId) {
+        self.add(DistanceConstraint::new(
+            ConstraintEnd::Particle(particle),
+            ConstraintEnd::Anchor(anchor),
+            0.0,
+        ));
+    }
+
+    // Links each particle to the next, `segment_length` apart.
+    pub fn chain(&mut self, particles: &[ParticleHandle], segment_length: f32, stiffness: f32) {
+        for pair in particles.windows(2) {
+            self.add(
+                DistanceConstraint::new(
+                    ConstraintEnd::Particle(pair[0]),
+                    ConstraintEnd::Particle(pair[1]),
+                    segment_length,
+                )
+                .with_stiffness(stiffness),
+            );
+        }
+    }
+
+    pub fn constraints(&self) -> &[DistanceConstraint] {
+        &self.constraints
+    }
+
+    pub fn constraints_mut(&mut self) -> &mut [DistanceConstraint] {
+        &mut self.constraints
+    }
+
+    pub fn len(&self) -> usize {
+        self.constraints.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.constraints.is_empty()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ConstraintSetId(pub(crate) u32);
+
+impl ConstraintSetId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+// A link that stretched past its break threshold, as it was before removal.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ConstraintBreak {
+    pub set: ConstraintSetId,
+    pub constraint: DistanceConstraint,
+    // Distance over rest length when it broke.
+    pub stretch: f32,
+}
+
+// Where a link end sits this step. Ballistic particles move; anchors and
+// spline particles hold their place and pull the other end to them.
+#[derive(Clone, Copy, Debug)]
+enum SolverEnd {
+    Ballistic { lane: u16, index: u32 },
+    Fixed(Vec2),
+}
+
+// Sets in the order they were added; removal leaves a hole so ids stay valid.
+// Broken links are removed and their events kept until drained.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ConstraintList {
+    sets: Vec<Option<ConstraintSet>>,
+    breaks: Vec<ConstraintBreak>,
+    active: bool,
+    ends: Vec<(SolverEnd, SolverEnd, f32, f32)>,
+}
+
+impl ConstraintList {
+    #[inline(always)]
+    pub(crate) fn is_active(&self) -> bool {
+        self.active
+    }
+
+    fn refresh(&mut self) {
+        self.active = self.sets.iter().any(Option::is_some);
+    }
+
+    // Returns whether a particle in a lane without velocity was set moving.
+    fn solve<T, U>(
+        &mut self,
+        ballistic: &mut BallisticLanes<T, U>,
+        spline_particles: &[SplineParticle<T, U>],
+        handles: &HandleTable,
+        anchors: &AnchorTable,
+    ) -> bool
+    where
+        T: ParticleTypeTrait,
+        U: ParticlePayload,
+    {
+        let mut moved_static = false;
+        for (set_index, set) in self.sets.iter_mut().enumerate() {
+            let Some(set) = set else {
+                continue;
+            };
+
+            self.ends.clear();
+            set.constraints.retain(|constraint| {
+                let resolve = |end: ConstraintEnd| match end {
+                    ConstraintEnd::Anchor(anchor) => Some(SolverEnd::Fixed(anchors.get(anchor))),
+                    ConstraintEnd::Particle(handle) => {
+                        let location = handles.location(handle)?;
+                        Some(match location.lane {
+                            SPLINE_LANE => {
+                                SolverEnd::Fixed(spline_particles[location.index as usize].core.pos)
+                            }
+                            ANALYTIC_LANE => unreachable!("constraints keep particles stepwise"),
+                            lane => SolverEnd::Ballistic {
+                                lane,
+                                index: location.index,
+                            },
+                        })
+                    }
+                };
+                // Links to dead particles go quietly; only stretching breaks.
+                let (Some(a), Some(b)) = (resolve(constraint.a), resolve(constraint.b)) else {
+                    return false;
+                };
+
+                let stretch =
+                    end_pos(ballistic, a).distance(end_pos(ballistic, b)) / constraint.length;
+                if constraint.length > 0.0 && stretch > constraint.break_stretch {
+                    self.breaks.push(ConstraintBreak {
+                        set: ConstraintSetId(set_index as u32),
+                        constraint: *constraint,
+                        stretch,
+                    });
+                    return false;
+                }
+                self.ends
+                    .push((a, b, constraint.length, constraint.stiffness));
+                true
+            });
+
+            for _ in 0..set.iterations {
+                for &(a, b, length, stiffness) in &self.ends {
+                    solve_link(ballistic, a, b, length, stiffness);
+                }
+            }
+
+            for &(a, b, _, _) in &self.ends {
+                for end in [a, b] {
+                    if let SolverEnd::Ballistic { lane, index } = end {
+                        let lane = &ballistic.lanes[lane as usize];
+                        moved_static |= (lane.key & HAS_VELOCITY) == 0
+                            && lane.hot[index as usize].velocity != Vec2::ZERO;
+                    }
+                }
+            }
+        }
+        moved_static
+    }
+}
+
+#[inline(always)]
+fn end_pos<T, U>(ballistic: &BallisticLanes<T, U>, end: SolverEnd) -> Vec2
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    match end {
+        SolverEnd::Ballistic { lane, index } => {
+            ballistic.lanes[lane as usize].hot[index as usize].pos
+        }
+        SolverEnd::Fixed(pos) => pos,
+    }
+}
+
+// Moves the ends along the link toward `length` apart, splitting the
+// correction between the ends that can move. Each correction also goes into
+// velocity, so particles carry the constrained motion into the next step the
+// way a Verlet integrator would.
+#[inline(always)]
+fn solve_link<T, U>(
+    ballistic: &mut BallisticLanes<T, U>,
+    a: SolverEnd,
+    b: SolverEnd,
+    length: f32,
+    stiffness: f32,
+) where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    let movable = |end: SolverEnd| match end {
+        SolverEnd::Ballistic { .. } => 1.0,
+        SolverEnd::Fixed(_) => 0.0,
+    };
+    let weight = movable(a) + movable(b);
+    let offset = end_pos(ballistic, b) - end_pos(ballistic, a);
+    let distance = offset.length();
+    if weight == 0.0 || distance == 0.0 {
+        return;
+    }
+
+    let correction = offset * ((distance - length) / distance * stiffness / weight);
+    for (end, shift) in [(a, correction), (b, -correction)] {
+        if let SolverEnd::Ballistic { lane, index } = end {
+            let hot = &mut ballistic.lanes[lane as usize].hot[index as usize];
+            hot.pos += shift;
+            hot.velocity += shift;
+        }
+    }
+}
+
+impl<T, U> ParticleSystem<T, U>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    // Sets are solved in the order they were added, after particles move each
+    // step. Like affectors, constraints move `step` onto the stepwise path and
+    // keep new spawns out of the analytic lane.
+    pub fn add_constraints(&mut self, set: ConstraintSet) -> ConstraintSetId {
+        let id = ConstraintSetId(self.constraints.sets.len() as u32);
+        self.constraints.sets.push(Some(set));
+        self.constraints.refresh();
+        self.materialize_analytic();
+        id
+    }
+
+    pub fn remove_constraints(&mut self, id: ConstraintSetId) -> Option<ConstraintSet> {
+        let set = self.constraints.sets.get_mut(id.index())?.take();
+        self.constraints.refresh();
+        set
+    }
+
+    // Links to particles that died and links that broke are no longer in the
+    // set.
+    pub fn constraints(&self, id: ConstraintSetId) -> Option<&ConstraintSet> {
+        self.constraints.sets.get(id.index())?.as_ref()
+    }
+
+    pub fn constraints_mut(&mut self, id: ConstraintSetId) -> Option<&mut ConstraintSet> {
+        self.constraints.sets.get_mut(id.index())?.as_mut()
+    }
+
+    // Links broken since the last drain, oldest first.
+    pub fn drain_constraint_breaks(&mut self) -> impl Iterator<Item = ConstraintBreak> + '_ {
+        self.constraints.breaks.drain(..)
+    }
+
+    pub(crate) fn run_constraints(&mut self) {
+        let moved_static = self.constraints.solve(
+            &mut self.ballistic,
+            &self.spline_particles,
+            &self.handles,
+            &self.anchors,
+        );
+        if moved_static {
+            self.ballistic.promote_moving(&mut self.handles);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec2;
+
+    use super::{ConstraintEnd, ConstraintSet, DistanceConstraint};
+    use crate::core::{ParticleSpawn, ParticleSystem, ParticleTypeTrait};
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct Bead;
+
+    impl ParticleTypeTrait for Bead {}
+
+    fn bead(pos: Vec2) -> ParticleSpawn<Bead> {
+        ParticleSpawn::new(Bead, 1000, pos, Vec2::ONE)
+    }
+
+    #[test]
+    fn pin_follows_its_anchor() {
+        let mut system = ParticleSystem::new();
+        let anchor = system.add_anchor(Vec2::ZERO);
+        // Starts in a lane without velocity, so the pin has to set it moving.
+        let particle = system.spawn_with_handle(bead(Vec2::ZERO)).unwrap();
+        let mut set = ConstraintSet::new();
+        set.pin(particle, anchor);
+        system.add_constraints(set);
+
+        for target in [Vec2::new(5.0, 0.0), Vec2::new(-3.0, 8.0)] {
+            system.set_anchor(anchor, target);
+            system.step();
+            let pos = system.get(particle).unwrap().pos;
+            assert!(pos.abs_diff_eq(target, 1e-4), "{pos} vs {target}");
+        }
+    }
+
+    #[test]
+    fn pinned_chain_hangs_from_its_anchor() {
+        let mut system = ParticleSystem::new();
+        let top = Vec2::new(0.0, 100.0);
+        let anchor = system.add_anchor(top);
+        let beads: Vec<_> = (0..6)
+            .map(|i| {
+                let spawn = bead(top + Vec2::new(i as f32 * 4.0, 0.0))
+                    .with_velocity(Vec2::ZERO)
+                    .with_acceleration(Vec2::new(0.0, -0.1));
+                system.spawn_with_handle(spawn).unwrap()
+            })
+            .collect();
+        let mut set = ConstraintSet::new().with_iterations(16);
+        set.pin(beads[0], anchor);
+        set.chain(&beads, 4.0, 1.0);
+        let id = system.add_constraints(set);
+
+        for _ in 0..200 {
+            system.step();
+        }
+        let pos: Vec<_> = beads
+            .iter()
+            .map(|&bead| system.get(bead).unwrap().pos)
+            .collect();
+        // Links solved after the pin leave a small residual on the top bead.
+        assert!(pos[0].abs_diff_eq(top, 0.05), "{pos:?}");
+        assert!(pos[5].y < pos[0].y - 10.0, "chain did not fall: {pos:?}");
+        for pair in pos.windows(2) {
+            let length = pair[0].distance(pair[1]);
+            assert!((length - 4.0).abs() < 0.5, "link {length}");
+        }
+        assert_eq!(system.constraints(id).unwrap().len(), 6);
+        assert_eq!(system.drain_constraint_breaks().count(), 0);
+    }
+
+    #[test]
+    fn overstretched_link_breaks_once() {
+        let mut system = ParticleSystem::new();
+        let a = system.spawn_with_handle(bead(Vec2::ZERO)).unwrap();
+        let b = system
+            .spawn_with_handle(bead(Vec2::new(10.0, 0.0)).with_velocity(Vec2::new(6.0, 0.0)))
+            .unwrap();
+        let link =
+            DistanceConstraint::new(ConstraintEnd::Particle(a), ConstraintEnd::Particle(b), 10.0)
+                .with_stiffness(0.1)
+                .with_break_stretch(1.5);
+        let mut set = ConstraintSet::new().with_iterations(1);
+        set.add(link);
+        let id = system.add_constraints(set);
+
+        let mut breaks = Vec::new();
+        for _ in 0..10 {
+            system.step();
+            breaks.extend(system.drain_constraint_breaks());
+        }
+        assert_eq!(breaks.len(), 1);
+        assert_eq!(breaks[0].set, id);
+        assert_eq!(breaks[0].constraint, link);
+        assert!(breaks[0].stretch > 1.5);
+        assert!(system.constraints(id).unwrap().is_empty());
+
+        // Once broken, the ends move freely.
+        let gap = system.get(b).unwrap().pos.x - system.get(a).unwrap().pos.x;
+        system.step();
+        let wider = system.get(b).unwrap().pos.x - system.get(a).unwrap().pos.x;
+        assert!(wider > gap);
+    }
+
+    #[test]
+    fn links_to_dead_particles_drop_without_breaking() {
+        let mut system = ParticleSystem::new();
+        let a = system.spawn_with_handle(bead(Vec2::ZERO)).unwrap();
+        let b = system
+            .spawn_with_handle(ParticleSpawn::new(Bead, 2, Vec2::new(50.0, 0.0), Vec2::ONE))
+            .unwrap();
+        let mut set = ConstraintSet::new();
+        set.add(
+            DistanceConstraint::new(ConstraintEnd::Particle(a), ConstraintEnd::Particle(b), 50.0)
+                .with_break_stretch(2.0),
+        );
+        let id = system.add_constraints(set);
+
+        for _ in 0..5 {
+            system.step();
+        }
+        assert!(!system.contains(b));
+        assert!(system.constraints(id).unwrap().is_empty());
+        assert_eq!(system.drain_constraint_breaks().count(), 0);
+    }
+}
@@ -0,0 +1,215 @@
+use glam::Vec2;
+
+use super::particle_model::{ParticlePayload, ParticleRenderData, ParticleTypeTrait};
+use super::particle_system::ParticleSystem;
+use super::region::Rect;
+
+// What counts as visible for `for_each_particle_culled`: any part of the
+// particle's rotated rect within `margin` of `rect`, drawn at least
+// `min_pixel_size` pixels across at `pixels_per_unit` and at least
+// `min_alpha` opaque. The thresholds cull nothing by default.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ViewCull {
+    pub rect: Rect,
+    pub margin: f32,
+    pub pixels_per_unit: f32,
+    pub min_pixel_size: f32,
+    pub min_alpha: f32,
+}
+
+impl ViewCull {
+    pub fn new(rect: Rect, margin: f32) -> Self {
+        Self {
+            rect,
+            margin,
+            pixels_per_unit: 1.0,
+            min_pixel_size: 0.0,
+            min_alpha: 0.0,
+        }
+    }
+
+    // Culls particles whose larger side covers less than `min_pixel_size`
+    // pixels at the camera's `pixels_per_unit` zoom.
+    pub fn with_min_pixel_size(mut self, pixels_per_unit: f32, min_pixel_size: f32) -> Self {
+        self.pixels_per_unit = pixels_per_unit;
+        self.min_pixel_size = min_pixel_size;
+        self
+    }
+
+    pub fn with_min_alpha(mut self, min_alpha: f32) -> Self {
+        self.min_alpha = min_alpha;
+        self
+    }
+
+    // Particles are rects of `size` centered on `pos`, turned by `rotation`
+    // degrees. The bounding circle settles most of them without the sine
+    // and cosine.
+    #[inline(always)]
+    pub fn is_visible<T, U>(&self, particle: &ParticleRenderData<T, U>) -> bool
+    where
+        T: ParticleTypeTrait,
+        U: ParticlePayload,
+    {
+        if particle.alpha < self.min_alpha
+            || particle.size.abs().max_element() * self.pixels_per_unit < self.min_pixel_size
+        {
+            return false;
+        }
+
+        let view = self.rect.expand(self.margin);
+        if view.contains(particle.pos) {
+            return true;
+        }
+        let half = particle.size.abs() * 0.5;
+        if !view.expand(half.length()).contains(particle.pos) {
+            return false;
+        }
+
+        let (sin, cos) = particle.rotation.to_radians().sin_cos();
+        let (sin, cos) = (sin.abs(), cos.abs());
+        let extent = Vec2::new(cos * half.x + sin * half.y, sin * half.x + cos * half.y);
+        (particle.pos + extent).cmpge(view.min).all()
+            && (particle.pos - extent).cmple(view.max).all()
+    }
+}
+
+impl<T, U> ParticleSystem<T, U>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    // Like `for_each_particle`, skipping particles entirely outside `rect`
+    // grown by `margin`. Returns how many were skipped.
+    pub fn for_each_particle_in_view(
+        &self,
+        rect: Rect,
+        margin: f32,
+        f: impl FnMut(ParticleRenderData<T, U>),
+    ) -> usize {
+        self.for_each_particle_culled(&ViewCull::new(rect, margin), f)
+    }
+
+    pub fn for_each_particle_culled(
+        &self,
+        cull: &ViewCull,
+        mut f: impl FnMut(ParticleRenderData<T, U>),
+    ) -> usize {
+        let mut culled = 0;
+        self.for_each_particle(|particle| {
+            if cull.is_visible(&particle) {
+                f(particle);
+            } else {
+                culled += 1;
+            }
+        });
+        culled
+    }
+
+    // Culls on the interpolated state, so particles crossing the view edge
+    // between steps appear on the frame they enter it.
+    pub fn for_each_particle_interpolated_culled(
+        &self,
+        alpha: f32,
+        cull: &ViewCull,
+        mut f: impl FnMut(ParticleRenderData<T, U>),
+    ) -> usize {
+        let mut culled = 0;
+        self.for_each_particle_interpolated(alpha, |particle| {
+            if cull.is_visible(&particle) {
+                f(particle);
+            } else {
+                culled += 1;
+            }
+        });
+        culled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::ParticleSpawn;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    struct Speck;
+    impl ParticleTypeTrait for Speck {}
+
+    fn view() -> Rect {
+        Rect::new(Vec2::ZERO, Vec2::splat(10.0))
+    }
+
+    fn speck(pos: Vec2, size: Vec2, rotation: f32) -> ParticleRenderData<Speck> {
+        ParticleRenderData {
+            particle_type: Speck,
+            counter: 1,
+            pos,
+            size,
+            rotation,
+            draw_layer: 0,
+            alpha: 1.0,
+            payload: (),
+        }
+    }
+
+    #[test]
+    fn rotated_rects_are_tested_past_the_bounding_circle() {
+        let cull = ViewCull::new(view(), 0.0);
+        // Inside the bounding circle's reach either way; only the long side
+        // reaches back into view.
+        let bar = |rotation| speck(Vec2::new(11.5, 5.0), Vec2::new(4.0, 0.5), rotation);
+        assert!(cull.is_visible(&bar(0.0)));
+        assert!(!cull.is_visible(&bar(90.0)));
+        assert!(cull.is_visible(&bar(180.0)));
+
+        // A square off the corner only reaches in when turned onto its point.
+        let square = |rotation| speck(Vec2::splat(11.2), Vec2::splat(2.0), rotation);
+        assert!(!cull.is_visible(&square(0.0)));
+        assert!(cull.is_visible(&square(45.0)));
+        assert!(!cull.is_visible(&speck(Vec2::splat(12.0), Vec2::splat(2.0), 45.0)));
+    }
+
+    #[test]
+    fn margin_and_thresholds() {
+        let point = speck(Vec2::new(11.0, 5.0), Vec2::ZERO, 0.0);
+        assert!(ViewCull::new(view(), 1.0).is_visible(&point));
+        assert!(!ViewCull::new(view(), 0.5).is_visible(&point));
+
+        let cull = ViewCull::new(view(), 0.0)
+            .with_min_pixel_size(4.0, 2.0)
+            .with_min_alpha(0.1);
+        let dot = |size: f32, alpha| ParticleRenderData {
+            alpha,
+            ..speck(Vec2::splat(5.0), Vec2::new(size, 0.1), 0.0)
+        };
+        assert!(cull.is_visible(&dot(0.5, 0.1)));
+        assert!(!cull.is_visible(&dot(0.4, 1.0)));
+        assert!(!cull.is_visible(&dot(1.0, 0.05)));
+    }
+
+    #[test]
+    fn interpolated_culling_uses_the_drawn_position() {
+        let mut system = ParticleSystem::new();
+        system.set_interpolation(true);
+        system.spawn(
+            ParticleSpawn::new(Speck, 10, Vec2::new(8.0, 5.0), Vec2::ONE)
+                .with_velocity(Vec2::new(4.0, 0.0)),
+        );
+        system.spawn(ParticleSpawn::new(Speck, 10, Vec2::splat(5.0), Vec2::ONE));
+        system.step();
+
+        let cull = ViewCull::new(view(), 0.0);
+        let mut drawn = Vec::new();
+        assert_eq!(
+            system.for_each_particle_culled(&cull, |p| drawn.push(p.pos)),
+            1
+        );
+        assert_eq!(drawn, [Vec2::splat(5.0)]);
+
+        drawn.clear();
+        let culled =
+            system.for_each_particle_interpolated_culled(0.25, &cull, |p| drawn.push(p.pos));
+        assert_eq!(culled, 0);
+        assert_eq!(drawn.len(), 2);
+        assert_eq!(system.for_each_particle_in_view(view(), 2.0, |_| {}), 0);
+    }
+}
@@ -0,0 +1,109 @@
+use super::handle::ParticleHandle;
+use super::particle_model::{ParticlePayload, ParticleRenderData, ParticleTypeTrait};
+use super::particle_system::ParticleSystem;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeathCause {
+    Expired,
+    Evicted,
+    // Removed by `retain` or one of the `kill_*` calls.
+    Killed,
+}
+
+// A particle as it was when it died. `handle` is set for particles spawned
+// with one and no longer resolves.
+#[derive(Clone, Copy, Debug)]
+pub struct ParticleDeath<T, U = ()>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    pub handle: Option<ParticleHandle>,
+    pub cause: DeathCause,
+    pub particle: ParticleRenderData<T, U>,
+}
+
+// Collects particles as they are removed: final states for interpolation,
+// cleared every step, and death events, kept until drained. It also runs the
+// type's death hook. Nothing is read back unless one of these is on, which
+// keeps removal to a single branch.
+#[derive(Clone, Debug)]
+pub(crate) struct DeathLog<T, U>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    pub(crate) dying: Vec<ParticleRenderData<T, U>>,
+    pub(crate) events: Vec<ParticleDeath<T, U>>,
+    pub(crate) keep_dying: bool,
+    pub(crate) keep_events: bool,
+}
+
+impl<T, U> DeathLog<T, U>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    pub(crate) fn new() -> Self {
+        Self {
+            dying: Vec::new(),
+            events: Vec::new(),
+            keep_dying: false,
+            keep_events: false,
+        }
+    }
+
+    // Whether removals need the particle's final state.
+    #[inline(always)]
+    pub(crate) fn is_recording(&self) -> bool {
+        T::DEATH_HOOK || self.keep_dying || self.keep_events
+    }
+
+    #[cold]
+    pub(crate) fn record(
+        &mut self,
+        particle: ParticleRenderData<T, U>,
+        handle: Option<ParticleHandle>,
+        cause: DeathCause,
+    ) {
+        if T::DEATH_HOOK {
+            particle.particle_type.on_death(&particle, cause);
+        }
+        // Only deaths during a step are drawn on their way out; evictions
+        // happen between steps.
+        if self.keep_dying && cause == DeathCause::Expired {
+            self.dying.push(particle);
+        }
+        if self.keep_events {
+            self.events.push(ParticleDeath {
+                handle,
+                cause,
+                particle,
+            });
+        }
+    }
+}
+
+impl<T, U> ParticleSystem<T, U>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    // Records a `ParticleDeath` for every particle that expires, is evicted or
+    // is killed from here on. `clear` is not a death.
+    pub fn set_death_events(&mut self, enabled: bool) {
+        self.deaths.keep_events = enabled;
+        if !enabled {
+            self.deaths.events.clear();
+        }
+    }
+
+    pub fn death_events(&self) -> bool {
+        self.deaths.keep_events
+    }
+
+    // Deaths since the last drain, oldest first.
+    pub fn drain_deaths(&mut self) -> impl Iterator<Item = ParticleDeath<T, U>> + '_ {
+        self.deaths.events.drain(..)
+    }
+}
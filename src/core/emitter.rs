@@ -0,0 +1,232 @@
+use std::mem;
+
+use super::particle_model::{
+    ParticlePayload, ParticleSpawn, ParticleTypeTrait, SplineMotion, SplineParticle,
+};
+use super::particle_system::{step_spline_particle, ParticleSystem};
+use super::trail::NO_TRAIL;
+
+// Produces the spawns for one simulation step. Emitters own whatever state
+// they need (timers, rng, placement), so stepping one is deterministic given
+// that state.
+pub trait Emitter<T, U = ()>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    fn emit(&mut self, out: &mut Vec<ParticleSpawn<T, U>>);
+}
+
+impl<T, U, F> Emitter<T, U> for F
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+    F: FnMut(&mut Vec<ParticleSpawn<T, U>>),
+{
+    fn emit(&mut self, out: &mut Vec<ParticleSpawn<T, U>>) {
+        self(out)
+    }
+}
+
+impl<T, U, E> Emitter<T, U> for Vec<E>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+    E: Emitter<T, U>,
+{
+    fn emit(&mut self, out: &mut Vec<ParticleSpawn<T, U>>) {
+        for emitter in self {
+            emitter.emit(out);
+        }
+    }
+}
+
+// A placed effect: an emitter plus how long it has been running. Without a
+// duration it loops until dropped.
+#[derive(Clone, Debug)]
+pub struct EffectInstance<E> {
+    pub emitter: E,
+    pub duration: Option<u32>,
+    elapsed: u32,
+}
+
+impl<E> EffectInstance<E> {
+    pub fn new(emitter: E) -> Self {
+        Self {
+            emitter,
+            duration: None,
+            elapsed: 0,
+        }
+    }
+
+    pub fn with_duration(mut self, steps: u32) -> Self {
+        self.duration = Some(steps);
+        self
+    }
+
+    pub fn elapsed(&self) -> u32 {
+        self.elapsed
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.duration
+            .is_some_and(|duration| self.elapsed >= duration)
+    }
+
+    // Starts the effect `steps` steps in, as if it had been emitting into
+    // `system` all along. See `ParticleSystem::prewarm`.
+    pub fn prewarm<T, U>(&mut self, system: &mut ParticleSystem<T, U>, steps: u32)
+    where
+        T: ParticleTypeTrait,
+        U: ParticlePayload,
+        E: Emitter<T, U>,
+    {
+        system.prewarm(self, steps);
+    }
+}
+
+impl<T, U, E> Emitter<T, U> for EffectInstance<E>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+    E: Emitter<T, U>,
+{
+    fn emit(&mut self, out: &mut Vec<ParticleSpawn<T, U>>) {
+        if self.is_finished() {
+            return;
+        }
+        self.emitter.emit(out);
+        self.elapsed = self.elapsed.saturating_add(1);
+    }
+}
+
+impl<T, U> ParticleSystem<T, U>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    // Spawns one step's worth of `emitter` output. Call once per step, before
+    // `step`.
+    pub fn emit(&mut self, emitter: &mut impl Emitter<T, U>) {
+        let mut spawns = mem::take(&mut self.emit_scratch);
+        spawns.clear();
+        emitter.emit(&mut spawns);
+        self.spawn_batch(spawns.drain(..));
+        self.emit_scratch = spawns;
+    }
+
+    // Runs `steps` iterations of `emit` + `step` for `emitter` alone and adds
+    // the survivors, leaving particles already in the system and the tick
+    // untouched. Each spawn is fast-forwarded on its own: ones that would have
+    // died are never allocated, ballistic ones use the closed form where it is
+    // exact, and the rest are stepped individually. Budgets are applied per
+    // emitted batch to the survivors only. Time controls are not applied to
+    // the fast-forward.
+    pub fn prewarm(&mut self, emitter: &mut impl Emitter<T, U>, steps: u32) {
+        let mut spawns = mem::take(&mut self.emit_scratch);
+        for emitted_at in 0..steps {
+            let age = steps - emitted_at;
+            spawns.clear();
+            emitter.emit(&mut spawns);
+            spawns.retain(|spawn| spawn.counter >= age);
+            if self.budgets_active {
+                self.admit_spawns(&mut spawns);
+            }
+            for spawn in spawns.drain(..) {
+                self.push_aged(&spawn, age);
+            }
+        }
+        self.emit_scratch = spawns;
+    }
+
+    fn push_aged(&mut self, spawn: &ParticleSpawn<T, U>, age: u32) {
+        let mut core = self.new_core(spawn);
+        core.spawn_tick = self.tick.wrapping_sub(age);
+
+        if spawn.is_spline() {
+            let mut particle = SplineParticle {
+                core,
+                spline: SplineMotion::from_spawn(spawn),
+            };
+            for _ in 0..age {
+                step_spline_particle(&mut particle, &self.spline_paths, &self.anchors);
+                if particle.core.trail != NO_TRAIL {
+                    self.trails.record(particle.core.trail, particle.core.pos);
+                }
+            }
+            self.push_spline(particle);
+            return;
+        }
+
+        if spawn.analytic && !self.stepwise() {
+            match self.analytic.try_push(core) {
+                Ok(()) => return,
+                Err(rejected) => core = rejected,
+            }
+        }
+        self.ballistic.push_advanced(core, age, &mut self.trails);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec2;
+
+    use super::*;
+    use crate::core::{OverflowPolicy, ParticleBudget};
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    enum Kind {
+        Warm,
+        Fresh,
+    }
+    impl ParticleTypeTrait for Kind {}
+
+    // Two per step: one static, one in the analytic lane.
+    fn warm(out: &mut Vec<ParticleSpawn<Kind>>) {
+        let spawn = ParticleSpawn::new(Kind::Warm, 100, Vec2::ZERO, Vec2::ONE);
+        out.push(spawn);
+        out.push(spawn.with_velocity(Vec2::X).with_analytic());
+    }
+
+    fn counters(system: &ParticleSystem<Kind>, kind: Kind) -> Vec<u32> {
+        let mut counters = Vec::new();
+        system.for_each_particle(|p| {
+            if p.particle_type == kind {
+                counters.push(p.counter);
+            }
+        });
+        counters.sort_unstable();
+        counters
+    }
+
+    #[test]
+    fn prewarm_ages_particles_without_moving_the_tick() {
+        let mut system = ParticleSystem::new();
+        system.prewarm(&mut warm, 3);
+
+        assert_eq!(system.tick(), 0);
+        assert_eq!(counters(&system, Kind::Warm), [97, 97, 98, 98, 99, 99]);
+        let mut positions = Vec::new();
+        system.for_each_particle(|p| positions.push(p.pos.x));
+        positions.sort_by(f32::total_cmp);
+        assert_eq!(positions, [0.0, 0.0, 0.0, 1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn evict_oldest_takes_prewarmed_particles_first() {
+        let mut system = ParticleSystem::new();
+        system.set_budget(ParticleBudget::new(6, OverflowPolicy::EvictOldest));
+        system.prewarm(&mut warm, 3);
+        assert_eq!(system.len(), 6);
+
+        let fresh = ParticleSpawn::new(Kind::Fresh, 100, Vec2::ZERO, Vec2::ONE);
+        system.spawn_batch([fresh; 2]);
+        assert_eq!(counters(&system, Kind::Warm), [98, 98, 99, 99]);
+
+        system.step();
+        system.spawn_batch([fresh; 3]);
+        assert_eq!(counters(&system, Kind::Warm), [98]);
+        assert_eq!(counters(&system, Kind::Fresh), [99, 99, 100, 100, 100]);
+    }
+}
@@ -0,0 +1,303 @@
+use std::hash::Hash;
+use std::mem;
+use std::ops::Range;
+
+use glam::{IVec2, Vec2};
+
+use super::ballistic_lanes::BallisticLanes;
+use super::handle::ParticleLocation;
+use super::particle_model::{ParticlePayload, ParticleTypeTrait, HAS_VELOCITY};
+use super::particle_system::ParticleSystem;
+use super::spatial::SpatialIndex;
+
+// Boids steering for one particle type. Each step, every member looks at
+// flockmates within `neighbor_radius` and turns its velocity away from them
+// (`separation`), toward their average velocity (`alignment`) and toward their
+// center (`cohesion`). Speeds are capped at `max_speed`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Flocking {
+    pub neighbor_radius: f32,
+    pub max_neighbors: u32,
+    pub separation: f32,
+    pub alignment: f32,
+    pub cohesion: f32,
+    pub max_speed: f32,
+}
+
+impl Flocking {
+    pub fn new(neighbor_radius: f32, separation: f32, alignment: f32, cohesion: f32) -> Self {
+        Self {
+            neighbor_radius,
+            max_neighbors: u32::MAX,
+            separation,
+            alignment,
+            cohesion,
+            max_speed: f32::INFINITY,
+        }
+    }
+
+    // Stops looking after `max_neighbors` flockmates. These are the first
+    // found, not the nearest, which is fine for dense flocks and bounds the
+    // cost of crowded cells.
+    pub fn with_max_neighbors(mut self, max_neighbors: u32) -> Self {
+        self.max_neighbors = max_neighbors;
+        self
+    }
+
+    pub fn with_max_speed(mut self, max_speed: f32) -> Self {
+        self.max_speed = max_speed;
+        self
+    }
+}
+
+// A flock and its per-step scratch: a spatial hash of its members with cells
+// one neighbor radius across, and the steering for each member in lane order.
+// Members are matched by the type slot of the flocking type.
+#[derive(Clone, Debug)]
+pub(crate) struct Flock {
+    type_slot: u16,
+    flocking: Flocking,
+    index: SpatialIndex,
+    cells: Vec<(IVec2, Range<usize>)>,
+    steering: Vec<Vec2>,
+}
+
+impl Flock {
+    fn new(type_slot: u16, flocking: Flocking) -> Self {
+        Self {
+            type_slot,
+            flocking,
+            index: SpatialIndex::new(flocking.neighbor_radius),
+            cells: Vec::new(),
+            steering: Vec::new(),
+        }
+    }
+
+    // Steering is computed from every member's velocity before any of them
+    // changes, so the result does not depend on lane order. Returns whether a
+    // member in a lane without velocity was steered.
+    fn steer<T, U>(&mut self, ballistic: &mut BallisticLanes<T, U>) -> bool
+    where
+        T: ParticleTypeTrait,
+        U: ParticlePayload,
+    {
+        for lane in &ballistic.lanes {
+            for (i, (hot, cold)) in lane.iter().enumerate() {
+                if cold.type_slot == self.type_slot {
+                    self.index.stage(hot.pos, lane.index, i);
+                }
+            }
+        }
+        self.index.finish();
+
+        let flocking = self.flocking;
+        let radius_sq = flocking.neighbor_radius * flocking.neighbor_radius;
+        let velocity_of = |location: ParticleLocation| {
+            ballistic.lanes[location.lane as usize].hot[location.index as usize].velocity
+        };
+        let keys = self.index.keys();
+        self.steering.clear();
+        let mut group_start = 0;
+        while group_start < keys.len() {
+            let cell = keys[group_start].cell;
+            let group_len = keys[group_start..]
+                .iter()
+                .take_while(|key| key.cell == cell)
+                .count();
+            self.index.neighborhood(cell, &mut self.cells);
+
+            for member in &keys[group_start..group_start + group_len] {
+                let mut separation = Vec2::ZERO;
+                let mut velocity = Vec2::ZERO;
+                let mut center = Vec2::ZERO;
+                let mut neighbors = 0;
+                'cells: for (cell, run) in &self.cells {
+                    for key in &keys[run.clone()] {
+                        if neighbors >= flocking.max_neighbors {
+                            break 'cells;
+                        }
+                        let offset = member.pos - key.pos;
+                        let distance_sq = offset.length_squared();
+                        if key.cell != *cell
+                            || distance_sq > radius_sq
+                            || key.location == member.location
+                        {
+                            continue;
+                        }
+                        if distance_sq > 0.0 {
+                            separation += offset / distance_sq;
+                        }
+                        velocity += velocity_of(key.location);
+                        center += key.pos;
+                        neighbors += 1;
+                    }
+                }
+
+                self.steering.push(if neighbors == 0 {
+                    Vec2::ZERO
+                } else {
+                    let count = neighbors as f32;
+                    separation * flocking.separation
+                        + (velocity / count - velocity_of(member.location)) * flocking.alignment
+                        + (center / count - member.pos) * flocking.cohesion
+                });
+            }
+            group_start += group_len;
+        }
+
+        let mut steered_static = false;
+        for (member, &steer) in keys.iter().zip(&self.steering) {
+            let lane = &mut ballistic.lanes[member.location.lane as usize];
+            let hot = &mut lane.hot[member.location.index as usize];
+            hot.velocity = (hot.velocity + steer).clamp_length_max(flocking.max_speed);
+            steered_static |= (lane.key & HAS_VELOCITY) == 0 && hot.velocity != Vec2::ZERO;
+        }
+        steered_static
+    }
+}
+
+impl<T, U> ParticleSystem<T, U>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    // Makes ballistic particles of `particle_type` flock, replacing any
+    // flocking already set for it. The steering runs at the start of every
+    // step, before integration. Like affectors, flocking moves `step` onto the
+    // stepwise path and keeps the type's particles out of the analytic lane.
+    pub fn set_flocking(&mut self, particle_type: T, flocking: Flocking)
+    where
+        T: Hash + Eq,
+    {
+        debug_assert!(
+            flocking.neighbor_radius > 0.0,
+            "flocking needs a neighbor radius"
+        );
+        let type_slot = self.type_slot_mut(particle_type);
+        let flock = Flock::new(type_slot, flocking);
+        match self
+            .flocks
+            .iter_mut()
+            .find(|flock| flock.type_slot == type_slot)
+        {
+            Some(existing) => *existing = flock,
+            None => self.flocks.push(flock),
+        }
+        self.materialize_analytic();
+    }
+
+    pub fn remove_flocking(&mut self, particle_type: T) {
+        let type_slot = self.types.slot(particle_type);
+        self.flocks.retain(|flock| flock.type_slot != type_slot);
+    }
+
+    pub fn clear_flocking(&mut self) {
+        self.flocks.clear();
+    }
+
+    pub(crate) fn run_flocking(&mut self) {
+        let mut flocks = mem::take(&mut self.flocks);
+        let mut steered_static = false;
+        for flock in &mut flocks {
+            steered_static |= flock.steer(&mut self.ballistic);
+        }
+        self.flocks = flocks;
+        if steered_static {
+            self.ballistic.promote_moving(&mut self.handles);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{ParticleHandle, ParticleSpawn};
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    enum Kind {
+        Bird,
+        Leaf,
+    }
+    impl ParticleTypeTrait for Kind {}
+
+    fn spawn(
+        system: &mut ParticleSystem<Kind>,
+        kind: Kind,
+        pos: Vec2,
+        velocity: Vec2,
+    ) -> ParticleHandle {
+        let spawn = ParticleSpawn::new(kind, 100, pos, Vec2::ONE);
+        let spawn = if velocity == Vec2::ZERO {
+            spawn
+        } else {
+            spawn.with_velocity(velocity)
+        };
+        system.spawn_with_handle(spawn).unwrap()
+    }
+
+    // Velocity over the last step.
+    fn velocity(system: &mut ParticleSystem<Kind>, handle: ParticleHandle) -> Vec2 {
+        let before = system.get(handle).unwrap().pos;
+        system.step();
+        system.get(handle).unwrap().pos - before
+    }
+
+    #[test]
+    fn separation_pushes_resting_members_apart() {
+        let mut system = ParticleSystem::new();
+        let left = spawn(&mut system, Kind::Bird, Vec2::ZERO, Vec2::ZERO);
+        let right = spawn(&mut system, Kind::Bird, Vec2::new(0.5, 0.0), Vec2::ZERO);
+        system.set_flocking(Kind::Bird, Flocking::new(1.0, 0.25, 0.0, 0.0));
+        system.step();
+
+        assert_eq!(system.get(left).unwrap().pos, Vec2::new(-0.5, 0.0));
+        assert_eq!(system.get(right).unwrap().pos, Vec2::new(1.0, 0.0));
+    }
+
+    #[test]
+    fn alignment_and_cohesion_reach_across_cells() {
+        let mut system = ParticleSystem::new();
+        // Straddling a cell boundary, one neighbor radius apart.
+        let a = spawn(&mut system, Kind::Bird, Vec2::new(1.0, 0.0), Vec2::X);
+        let b = spawn(&mut system, Kind::Bird, Vec2::new(3.0, 0.0), Vec2::Y);
+        system.set_flocking(Kind::Bird, Flocking::new(2.0, 0.0, 0.5, 0.25));
+        system.step();
+
+        // Alignment meets half way and cohesion closes a quarter of the gap,
+        // both from the velocities before either member changed.
+        assert_eq!(system.get(a).unwrap().pos, Vec2::new(2.0, 0.5));
+        assert_eq!(system.get(b).unwrap().pos, Vec2::new(3.0, 0.5));
+    }
+
+    #[test]
+    fn only_flockmates_in_range_steer() {
+        let mut system = ParticleSystem::new();
+        let bird = spawn(&mut system, Kind::Bird, Vec2::ZERO, Vec2::ZERO);
+        let far = spawn(&mut system, Kind::Bird, Vec2::new(5.0, 0.0), Vec2::ZERO);
+        let leaf = spawn(&mut system, Kind::Leaf, Vec2::new(0.5, 0.0), Vec2::ZERO);
+        system.set_flocking(Kind::Bird, Flocking::new(2.0, 1.0, 1.0, 1.0));
+        system.step();
+
+        assert_eq!(system.get(bird).unwrap().pos, Vec2::ZERO);
+        assert_eq!(system.get(far).unwrap().pos, Vec2::new(5.0, 0.0));
+        assert_eq!(system.get(leaf).unwrap().pos, Vec2::new(0.5, 0.0));
+    }
+
+    #[test]
+    fn max_speed_caps_and_removal_stops_steering() {
+        let mut system = ParticleSystem::new();
+        let a = spawn(&mut system, Kind::Bird, Vec2::ZERO, Vec2::new(-3.0, 0.0));
+        let b = spawn(&mut system, Kind::Bird, Vec2::new(1.0, 0.0), Vec2::ZERO);
+        system.set_flocking(
+            Kind::Bird,
+            Flocking::new(10.0, 0.0, 0.0, 8.0).with_max_speed(2.0),
+        );
+        // `a` is pulled from -3 to +5, then capped.
+        assert_eq!(velocity(&mut system, a), Vec2::new(2.0, 0.0));
+        assert_eq!(system.get(b).unwrap().pos, Vec2::new(-1.0, 0.0));
+
+        system.remove_flocking(Kind::Bird);
+        assert_eq!(velocity(&mut system, a), Vec2::new(2.0, 0.0));
+        assert_eq!(velocity(&mut system, b), Vec2::new(-2.0, 0.0));
+    }
+}
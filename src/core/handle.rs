@@ -0,0 +1,341 @@
+use super::analytic::advance_closed_form;
+use super::particle_model::{
+    ParticlePayload, ParticleRenderData, ParticleSpawn, ParticleTypeTrait,
+};
+use super::particle_system::ParticleSystem;
+
+// Stays valid while the particle lives; lookups after it dies return `None`,
+// even once its slot has been reused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ParticleHandle {
+    index: u32,
+    generation: u32,
+}
+
+pub(crate) const NO_HANDLE: u32 = u32::MAX;
+
+// Non-ballistic lanes, in place of a ballistic sub-lane index.
+pub(crate) const SPLINE_LANE: u16 = u16::MAX;
+pub(crate) const ANALYTIC_LANE: u16 = u16::MAX - 1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct ParticleLocation {
+    pub(crate) lane: u16,
+    pub(crate) index: u32,
+}
+
+impl ParticleLocation {
+    #[inline(always)]
+    pub(crate) fn new(lane: u16, index: usize) -> Self {
+        Self {
+            lane,
+            index: index as u32,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct HandleSlot {
+    generation: u32,
+    location: ParticleLocation,
+}
+
+// Maps handles to where their particle currently sits. Particles only carry a
+// slot index when spawned with a handle, and every lane removal relocates the
+// particle swapped into the hole, so lookups are a plain index.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct HandleTable {
+    slots: Vec<HandleSlot>,
+    free: Vec<u32>,
+    live: usize,
+    // Bumped whenever any particle is removed or moves, handle or not, so
+    // anything holding lane locations can tell they went stale.
+    layout: u32,
+}
+
+impl HandleTable {
+    #[cfg(feature = "parallel")]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.live == 0
+    }
+
+    pub(crate) fn acquire(&mut self, location: ParticleLocation) -> u32 {
+        self.live += 1;
+        if let Some(slot) = self.free.pop() {
+            self.slots[slot as usize].location = location;
+            return slot;
+        }
+        self.slots.push(HandleSlot {
+            generation: 0,
+            location,
+        });
+        (self.slots.len() - 1) as u32
+    }
+
+    #[inline(always)]
+    pub(crate) fn handle(&self, slot: u32) -> Option<ParticleHandle> {
+        (slot != NO_HANDLE).then(|| ParticleHandle {
+            index: slot,
+            generation: self.slots[slot as usize].generation,
+        })
+    }
+
+    pub(crate) fn location(&self, handle: ParticleHandle) -> Option<ParticleLocation> {
+        self.slots
+            .get(handle.index as usize)
+            .filter(|slot| slot.generation == handle.generation)
+            .map(|slot| slot.location)
+    }
+
+    #[inline(always)]
+    pub(crate) fn layout(&self) -> u32 {
+        self.layout
+    }
+
+    #[inline(always)]
+    pub(crate) fn relocate(&mut self, slot: u32, location: ParticleLocation) {
+        self.layout = self.layout.wrapping_add(1);
+        if slot != NO_HANDLE {
+            self.slots[slot as usize].location = location;
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn release(&mut self, slot: u32) {
+        self.layout = self.layout.wrapping_add(1);
+        if slot != NO_HANDLE {
+            let entry = &mut self.slots[slot as usize];
+            entry.generation = entry.generation.wrapping_add(1);
+            self.free.push(slot);
+            self.live -= 1;
+        }
+    }
+
+    // Invalidates every outstanding handle.
+    pub(crate) fn clear(&mut self) {
+        self.free.clear();
+        for (slot, entry) in self.slots.iter_mut().enumerate() {
+            entry.generation = entry.generation.wrapping_add(1);
+            self.free.push(slot as u32);
+        }
+        self.live = 0;
+        self.layout = self.layout.wrapping_add(1);
+    }
+}
+
+impl<T, U> ParticleSystem<T, U>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    // Like `spawn`, returning a handle for later lookups, or `None` when a
+    // budget rejected the spawn. Only particles spawned this way are tracked.
+    pub fn spawn_with_handle(&mut self, spawn: ParticleSpawn<T, U>) -> Option<ParticleHandle> {
+        let spawn = if self.budgets_active {
+            let mut spawns = std::mem::take(&mut self.spawn_scratch);
+            spawns.clear();
+            spawns.push(spawn);
+            self.admit_spawns(&mut spawns);
+            let admitted = spawns.pop();
+            self.spawn_scratch = spawns;
+            admitted?
+        } else {
+            spawn
+        };
+
+        let location = self.push_spawn(spawn);
+        let slot = self.handles.acquire(location);
+        match location.lane {
+            SPLINE_LANE => self.spline_particles[location.index as usize].core.handle = slot,
+            ANALYTIC_LANE => self.analytic.spawns[location.index as usize].handle = slot,
+            lane => self.ballistic.lanes[lane as usize].cold[location.index as usize].handle = slot,
+        }
+        self.handles.handle(slot)
+    }
+
+    pub fn contains(&self, handle: ParticleHandle) -> bool {
+        self.handles.location(handle).is_some()
+    }
+
+    pub fn get(&self, handle: ParticleHandle) -> Option<ParticleRenderData<T, U>> {
+        self.render_data_at(self.handles.location(handle)?)
+    }
+
+    pub(crate) fn render_data_at(
+        &self,
+        location: ParticleLocation,
+    ) -> Option<ParticleRenderData<T, U>> {
+        let index = location.index as usize;
+        Some(match location.lane {
+            SPLINE_LANE => (&self.spline_particles[index].core).into(),
+            ANALYTIC_LANE => {
+                let mut core = self.analytic.spawns[index];
+                let age = self.analytic.timing[index].age(self.tick)?;
+                advance_closed_form(&mut core, age);
+                (&core).into()
+            }
+            lane => {
+                let lane = &self.ballistic.lanes[lane as usize];
+                (&lane.hot[index], &lane.cold[index]).into()
+            }
+        })
+    }
+
+    #[inline(always)]
+    pub(crate) fn handle_at(&self, location: ParticleLocation) -> Option<ParticleHandle> {
+        let index = location.index as usize;
+        self.handles.handle(match location.lane {
+            SPLINE_LANE => self.spline_particles[index].core.handle,
+            ANALYTIC_LANE => self.analytic.spawns[index].handle,
+            lane => self.ballistic.lanes[lane as usize].cold[index].handle,
+        })
+    }
+
+    pub fn payload(&self, handle: ParticleHandle) -> Option<U> {
+        let location = self.handles.location(handle)?;
+        let index = location.index as usize;
+        Some(match location.lane {
+            SPLINE_LANE => self.spline_particles[index].core.payload,
+            ANALYTIC_LANE => self.analytic.spawns[index].payload,
+            lane => self.ballistic.lanes[lane as usize].cold[index].payload,
+        })
+    }
+
+    pub fn set_payload(&mut self, handle: ParticleHandle, payload: U) -> bool {
+        let Some(location) = self.handles.location(handle) else {
+            return false;
+        };
+        let index = location.index as usize;
+        match location.lane {
+            SPLINE_LANE => self.spline_particles[index].core.payload = payload,
+            ANALYTIC_LANE => self.analytic.spawns[index].payload = payload,
+            lane => self.ballistic.lanes[lane as usize].cold[index].payload = payload,
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec2;
+
+    use super::*;
+    use crate::core::{DeathCause, OverflowPolicy, ParticleBudget, ParticleSpawn, SplineState};
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    struct Dot;
+    impl ParticleTypeTrait for Dot {}
+
+    // Payload `id`, lane by `id % 3`, and a lifetime that kills every other
+    // particle first so removals swap survivors into the holes.
+    fn tagged(id: u32) -> ParticleSpawn<Dot, u32> {
+        let counter = if id.is_multiple_of(2) { 2 } else { 20 };
+        let spawn =
+            ParticleSpawn::new(Dot, counter, Vec2::new(id as f32, 0.0), Vec2::ONE).with_payload(id);
+        match id % 3 {
+            0 => spawn,
+            1 => spawn.with_velocity(Vec2::ZERO).with_analytic(),
+            _ => spawn.with_spline(SplineState {
+                t: 0.0,
+                strength: 0.0,
+                point_1: Vec2::ZERO,
+                point_2: Vec2::ZERO,
+                point_3: Vec2::ZERO,
+            }),
+        }
+    }
+
+    #[test]
+    fn handles_follow_particles_through_removals() {
+        let mut system = ParticleSystem::new();
+        let handles: Vec<_> = (0..30)
+            .map(|id| system.spawn_with_handle(tagged(id)).unwrap())
+            .collect();
+        for _ in 0..3 {
+            system.step();
+        }
+
+        assert_eq!(system.len(), 15);
+        for (id, &handle) in handles.iter().enumerate() {
+            let id = id as u32;
+            if id.is_multiple_of(2) {
+                assert!(!system.contains(handle));
+                assert_eq!(system.payload(handle), None);
+                continue;
+            }
+            assert_eq!(system.payload(handle), Some(id));
+            let p = system.get(handle).unwrap();
+            assert_eq!((p.payload, p.pos.x, p.counter), (id, id as f32, 17));
+        }
+    }
+
+    #[test]
+    fn payload_writes_survive_later_moves() {
+        let mut system = ParticleSystem::new();
+        let handles: Vec<_> = (0..12)
+            .map(|id| system.spawn_with_handle(tagged(id)).unwrap())
+            .collect();
+        for &handle in &handles {
+            let id = system.payload(handle).unwrap();
+            assert!(system.set_payload(handle, id + 100));
+        }
+        for _ in 0..3 {
+            system.step();
+        }
+
+        for (id, &handle) in handles.iter().enumerate().filter(|(id, _)| id % 2 == 1) {
+            assert_eq!(system.payload(handle), Some(id as u32 + 100));
+        }
+        assert!(!system.set_payload(handles[0], 7));
+    }
+
+    #[test]
+    fn stale_handles_miss_reused_slots() {
+        let mut system = ParticleSystem::new();
+        let old = system.spawn_with_handle(tagged(0)).unwrap();
+        for _ in 0..3 {
+            system.step();
+        }
+        let new = system.spawn_with_handle(tagged(3)).unwrap();
+
+        assert_eq!(new.index, old.index);
+        assert!(!system.contains(old));
+        assert_eq!(system.get(old).map(|p| p.payload), None);
+        assert_eq!(system.payload(new), Some(3));
+
+        system.clear();
+        assert!(!system.contains(new));
+    }
+
+    #[test]
+    fn death_events_carry_handles_and_payloads() {
+        let mut system = ParticleSystem::new();
+        system.set_death_events(true);
+        system.set_budget(ParticleBudget::new(3, OverflowPolicy::EvictOldest));
+        let expiring = system.spawn_with_handle(tagged(0)).unwrap();
+        system.spawn(tagged(1));
+        system.step();
+        let evicted = system.spawn_with_handle(tagged(5)).unwrap();
+        system.step();
+        system.step();
+        system.spawn_batch([tagged(7), tagged(9)]);
+        system.kill_by_type(Dot);
+
+        let mut deaths: Vec<_> = system
+            .drain_deaths()
+            .map(|death| (death.handle, death.cause, death.particle.payload))
+            .collect();
+        // Kills come out in lane order.
+        deaths.sort_by_key(|&(_, _, id)| id);
+        assert_eq!(
+            deaths,
+            [
+                (Some(expiring), DeathCause::Expired, 0),
+                (None, DeathCause::Evicted, 1),
+                (Some(evicted), DeathCause::Killed, 5),
+                (None, DeathCause::Killed, 7),
+                (None, DeathCause::Killed, 9),
+            ]
+        );
+    }
+}
@@ -0,0 +1,319 @@
+use std::mem;
+
+use glam::{Affine2, Vec2};
+
+use super::particle_model::{ParticlePayload, ParticleTypeTrait};
+use super::particle_system::ParticleSystem;
+
+// How much of an impulse reaches `distance` from its center: all of it within
+// `radius - falloff`, blending down to none at `radius`.
+#[inline(always)]
+fn impulse_weight(distance: f32, radius: f32, falloff: f32) -> f32 {
+    if distance >= radius {
+        return 0.0;
+    }
+
+    let inner = (radius - falloff).max(0.0);
+    if distance <= inner {
+        return 1.0;
+    }
+    1.0 - (distance - inner) / (radius - inner)
+}
+
+// A ring expanding from `center` by `speed` each step until it reaches
+// `radius`, pushing particles outward by `strength` as its front passes them.
+// Particles pushed faster than the ring can be caught by it again.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Shockwave {
+    pub center: Vec2,
+    pub radius: f32,
+    pub speed: f32,
+    pub strength: f32,
+    pub falloff: f32,
+}
+
+impl Shockwave {
+    pub fn new(center: Vec2, radius: f32, speed: f32, strength: f32) -> Self {
+        Self {
+            center,
+            radius,
+            speed,
+            strength,
+            falloff: 0.0,
+        }
+    }
+
+    // Fades the push out over the outer `falloff` of the radius.
+    pub fn with_falloff(mut self, falloff: f32) -> Self {
+        self.falloff = falloff;
+        self
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ActiveShockwave {
+    wave: Shockwave,
+    // Distance the ring has covered so far.
+    front: f32,
+}
+
+impl ActiveShockwave {
+    // The push for a particle at `pos` as the ring moves out to `front`.
+    #[inline(always)]
+    fn impulse(&self, pos: Vec2, front: f32) -> Vec2 {
+        let offset = pos - self.wave.center;
+        let distance = offset.length();
+        if distance < self.front || distance >= front {
+            return Vec2::ZERO;
+        }
+        offset.normalize_or_zero()
+            * self.wave.strength
+            * impulse_weight(distance, self.wave.radius, self.wave.falloff)
+    }
+
+    #[inline(always)]
+    fn next_front(&self) -> f32 {
+        (self.front + self.wave.speed).min(self.wave.radius)
+    }
+
+    // Follows a frame change from `translate` and friends.
+    pub(crate) fn transform(&mut self, transform: Affine2, length_scale: f32) {
+        self.wave.center = transform.transform_point2(self.wave.center);
+        self.wave.radius *= length_scale;
+        self.wave.speed *= length_scale;
+        self.wave.strength *= length_scale;
+        self.wave.falloff *= length_scale;
+        self.front *= length_scale;
+    }
+}
+
+impl<T, U> ParticleSystem<T, U>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    // Adds `impulse` to every particle's velocity; see `apply_impulse_field`.
+    pub fn apply_impulse(&mut self, impulse: Vec2) {
+        self.apply_impulse_field(|_| impulse);
+    }
+
+    // Pushes particles within `radius` of `center` away from it by `strength`,
+    // fading out over the outer `falloff` of the radius. A negative strength
+    // pulls them in.
+    pub fn apply_radial_impulse(&mut self, center: Vec2, radius: f32, strength: f32, falloff: f32) {
+        self.apply_impulse_field(|pos| {
+            let offset = pos - center;
+            let weight = impulse_weight(offset.length(), radius, falloff);
+            if weight == 0.0 {
+                return Vec2::ZERO;
+            }
+            offset.normalize_or_zero() * strength * weight
+        });
+    }
+
+    // `apply_radial_impulse` with the same `impulse` direction everywhere.
+    pub fn apply_directional_impulse(
+        &mut self,
+        center: Vec2,
+        radius: f32,
+        impulse: Vec2,
+        falloff: f32,
+    ) {
+        self.apply_impulse_field(|pos| {
+            impulse * impulse_weight(pos.distance(center), radius, falloff)
+        });
+    }
+
+    // Starts a ring that travels outward over the next steps. Shockwaves are
+    // not particles: `clear` drops them, and `advance` runs step by step while
+    // any is in flight.
+    pub fn spawn_shockwave(&mut self, wave: Shockwave) {
+        if wave.speed > 0.0 && wave.radius > 0.0 {
+            self.shockwaves.push(ActiveShockwave { wave, front: 0.0 });
+        }
+    }
+
+    pub fn shockwave_count(&self) -> usize {
+        self.shockwaves.len()
+    }
+
+    pub fn clear_shockwaves(&mut self) {
+        self.shockwaves.clear();
+    }
+
+    // Moves every ring one step out, pushing whatever its front sweeps over.
+    pub(crate) fn run_shockwaves(&mut self) {
+        let mut waves = mem::take(&mut self.shockwaves);
+        self.apply_impulse_field(|pos| {
+            waves
+                .iter()
+                .map(|wave| wave.impulse(pos, wave.next_front()))
+                .sum()
+        });
+        for wave in &mut waves {
+            wave.front = wave.next_front();
+        }
+        waves.retain(|wave| wave.front < wave.wave.radius);
+        self.shockwaves = waves;
+    }
+
+    // Adds `field(pos)` to each particle's velocity. Spline particles are
+    // displaced by it instead and pulled back at their spline strength, so a
+    // strength of `1` keeps them on their path. Analytic particles only leave
+    // their lane when the field reaches them.
+    fn apply_impulse_field(&mut self, field: impl Fn(Vec2) -> Vec2) {
+        if self
+            .analytic
+            .iter_at(self.tick)
+            .any(|core| field(core.pos) != Vec2::ZERO)
+        {
+            self.materialize_analytic();
+        }
+        for lane in &mut self.ballistic.lanes {
+            for hot in &mut lane.hot {
+                hot.velocity += field(hot.pos);
+            }
+        }
+        for particle in &mut self.spline_particles {
+            particle.core.pos += field(particle.core.pos);
+        }
+        if !self.spline_particles.is_empty() {
+            self.invalidate_spatial_index();
+        }
+        self.ballistic.promote_moving(&mut self.handles);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{ParticleHandle, ParticleSpawn, SplineState};
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    struct Grit;
+    impl ParticleTypeTrait for Grit {}
+
+    fn grit(pos: Vec2) -> ParticleSpawn<Grit> {
+        ParticleSpawn::new(Grit, 100, pos, Vec2::ONE)
+    }
+
+    fn spawn_at(system: &mut ParticleSystem<Grit>, positions: &[Vec2]) -> Vec<ParticleHandle> {
+        positions
+            .iter()
+            .map(|&pos| system.spawn_with_handle(grit(pos)).unwrap())
+            .collect()
+    }
+
+    fn moved(system: &ParticleSystem<Grit>, handle: ParticleHandle, from: Vec2) -> Vec2 {
+        system.get(handle).unwrap().pos - from
+    }
+
+    #[test]
+    fn uniform_impulses_reach_every_lane() {
+        let mut system = ParticleSystem::new();
+        let resting = system.spawn_with_handle(grit(Vec2::ZERO)).unwrap();
+        let analytic = system
+            .spawn_with_handle(grit(Vec2::ZERO).with_velocity(Vec2::Y).with_analytic())
+            .unwrap();
+        let spline = system
+            .spawn_with_handle(grit(Vec2::ZERO).with_spline(SplineState {
+                t: 0.0,
+                strength: 0.0,
+                point_1: Vec2::ZERO,
+                point_2: Vec2::ZERO,
+                point_3: Vec2::ZERO,
+            }))
+            .unwrap();
+        system.apply_impulse(Vec2::X);
+        system.step();
+        system.step();
+
+        assert_eq!(system.get(resting).unwrap().pos, Vec2::new(2.0, 0.0));
+        assert_eq!(system.get(analytic).unwrap().pos, Vec2::new(2.0, 2.0));
+        // Spline particles are displaced once rather than given velocity.
+        assert_eq!(system.get(spline).unwrap().pos, Vec2::X);
+    }
+
+    #[test]
+    fn radial_impulses_fade_over_the_falloff() {
+        let mut system = ParticleSystem::new();
+        let positions = [
+            Vec2::new(2.0, 0.0),
+            Vec2::new(0.0, 8.0),
+            Vec2::new(12.0, 0.0),
+        ];
+        let handles = spawn_at(&mut system, &positions);
+        system.apply_radial_impulse(Vec2::ZERO, 10.0, 2.0, 4.0);
+        system.step();
+
+        let pushes: Vec<_> = handles
+            .iter()
+            .zip(positions)
+            .map(|(&handle, from)| moved(&system, handle, from))
+            .collect();
+        assert_eq!(pushes[0], Vec2::new(2.0, 0.0));
+        assert!(pushes[1].abs_diff_eq(Vec2::new(0.0, 1.0), 1e-5));
+        assert_eq!(pushes[2], Vec2::ZERO);
+
+        // Negative strength pulls in; directional pushes share one direction.
+        system.apply_radial_impulse(Vec2::ZERO, 100.0, -1.0, 0.0);
+        system.apply_directional_impulse(Vec2::ZERO, 100.0, Vec2::Y, 0.0);
+        let before = system.get(handles[2]).unwrap().pos;
+        system.step();
+        assert_eq!(moved(&system, handles[2], before), Vec2::new(-1.0, 1.0));
+    }
+
+    #[test]
+    fn shockwave_fronts_push_each_particle_once() {
+        let mut system = ParticleSystem::new();
+        let positions = [
+            Vec2::new(3.0, 0.0),
+            Vec2::new(0.0, 7.0),
+            Vec2::new(13.0, 0.0),
+        ];
+        let handles = spawn_at(&mut system, &positions);
+        system.spawn_shockwave(Shockwave::new(Vec2::ZERO, 12.0, 5.0, 1.0));
+        // A ring that cannot move is dropped.
+        system.spawn_shockwave(Shockwave::new(Vec2::ZERO, 12.0, 0.0, 1.0));
+        assert_eq!(system.shockwave_count(), 1);
+
+        // The front covers `0..5`, then `5..10`, then `10..12`.
+        let mut counts = Vec::new();
+        for _ in 0..3 {
+            system.step();
+            counts.push(system.shockwave_count());
+        }
+        assert_eq!(counts, [1, 1, 0]);
+        assert_eq!(
+            moved(&system, handles[0], positions[0]),
+            Vec2::new(3.0, 0.0)
+        );
+        assert_eq!(
+            moved(&system, handles[1], positions[1]),
+            Vec2::new(0.0, 2.0)
+        );
+        assert_eq!(moved(&system, handles[2], positions[2]), Vec2::ZERO);
+    }
+
+    #[test]
+    fn advance_steps_through_a_shockwave() {
+        let build = || {
+            let mut system = ParticleSystem::new();
+            spawn_at(&mut system, &[Vec2::new(1.0, 0.0), Vec2::new(0.0, 6.0)]);
+            system.spawn_shockwave(Shockwave::new(Vec2::ZERO, 10.0, 4.0, 1.0).with_falloff(5.0));
+            system
+        };
+        let mut stepped = build();
+        for _ in 0..4 {
+            stepped.step();
+        }
+        let mut advanced = build();
+        advanced.advance(4);
+
+        let mut positions = [Vec::new(), Vec::new()];
+        stepped.for_each_particle(|p| positions[0].push(p.pos));
+        advanced.for_each_particle(|p| positions[1].push(p.pos));
+        assert_eq!(positions[0], positions[1]);
+        assert_eq!(advanced.shockwave_count(), 0);
+    }
+}
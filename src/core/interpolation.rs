@@ -0,0 +1,236 @@
+use glam::Vec2;
+
+use super::particle_model::{
+    BallisticHot, ParticleCore, ParticlePayload, ParticleRenderData, ParticleTypeTrait,
+};
+use super::particle_system::ParticleSystem;
+
+// Render state from before the last step, kept per particle while
+// interpolation is enabled.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct PreviousState {
+    pub(crate) pos: Vec2,
+    pub(crate) size: Vec2,
+    pub(crate) rotation: f32,
+    pub(crate) alpha: f32,
+}
+
+impl From<&BallisticHot> for PreviousState {
+    #[inline(always)]
+    fn from(value: &BallisticHot) -> Self {
+        Self {
+            pos: value.pos,
+            size: value.size,
+            rotation: value.rotation,
+            alpha: value.alpha,
+        }
+    }
+}
+
+impl<T, U> From<&ParticleCore<T, U>> for PreviousState
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    #[inline(always)]
+    fn from(value: &ParticleCore<T, U>) -> Self {
+        Self {
+            pos: value.pos,
+            size: value.size,
+            rotation: value.rotation,
+            alpha: value.alpha,
+        }
+    }
+}
+
+impl<T, U> ParticleRenderData<T, U>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    // Blends from `previous` towards `self` by `t`. `previous.size` is unstretched,
+    // so it takes the current stretch; rotation follows the shorter arc.
+    #[inline(always)]
+    pub(crate) fn interpolated(
+        mut self,
+        previous: &PreviousState,
+        stretch_scale: f32,
+        t: f32,
+    ) -> Self {
+        let previous_size = Vec2::new(previous.size.x * stretch_scale, previous.size.y);
+        let turn = (self.rotation - previous.rotation + 180.0).rem_euclid(360.0) - 180.0;
+
+        self.pos = previous.pos.lerp(self.pos, t);
+        self.size = previous_size.lerp(self.size, t);
+        self.rotation = previous.rotation + turn * t;
+        self.alpha = previous.alpha + (self.alpha - previous.alpha) * t;
+        self
+    }
+}
+
+impl<T, U> ParticleSystem<T, U>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    // Keeps each particle's render state from before the last step so frames
+    // can be drawn between steps. Costs one extra record per particle and
+    // disables the parallel step path.
+    pub fn set_interpolation(&mut self, enabled: bool) {
+        self.interpolation = enabled;
+        self.ballistic.set_interpolation(enabled);
+        self.spline_previous.clear();
+        self.deaths.keep_dying = enabled;
+        self.deaths.dying.clear();
+        if enabled {
+            self.sync_spline_previous();
+        }
+    }
+
+    pub fn interpolation(&self) -> bool {
+        self.interpolation
+    }
+
+    // Like `for_each_particle`, drawn `alpha` of the way from the state before
+    // the last step to the current one (`0..=1`, typically the fixed-step
+    // accumulator over the timestep). Particles spawned since the last step
+    // sit at their spawn state, and particles the last step killed are drawn
+    // at their final state until the next step. Without interpolation enabled
+    // this is `for_each_particle`.
+    pub fn for_each_particle_interpolated(
+        &self,
+        alpha: f32,
+        mut f: impl FnMut(ParticleRenderData<T, U>),
+    ) {
+        if !self.interpolation {
+            self.for_each_particle(f);
+            return;
+        }
+
+        let t = alpha.clamp(0.0, 1.0);
+        for (hot, cold, previous) in self.ballistic.iter_with_previous() {
+            let data = ParticleRenderData::from((hot, cold));
+            f(data.interpolated(previous, cold.stretch_scale, t));
+        }
+
+        for (current, previous) in self.analytic.iter_with_previous(self.tick) {
+            let data = ParticleRenderData::from(&current);
+            f(data.interpolated(&previous, current.stretch_scale, t));
+        }
+
+        for (p, previous) in self.spline_particles.iter().zip(&self.spline_previous) {
+            let data = ParticleRenderData::from(&p.core);
+            f(data.interpolated(previous, p.core.stretch_scale, t));
+        }
+
+        for data in &self.deaths.dying {
+            f(*data);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::ParticleSpawn;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    struct Mote;
+    impl ParticleTypeTrait for Mote {}
+
+    fn drawn(system: &ParticleSystem<Mote>, alpha: f32) -> Vec<ParticleRenderData<Mote>> {
+        let mut drawn = Vec::new();
+        system.for_each_particle_interpolated(alpha, |p| drawn.push(p));
+        drawn
+    }
+
+    fn blend(from: f32, to: f32, t: f32) -> f32 {
+        let current = ParticleRenderData {
+            particle_type: Mote,
+            counter: 1,
+            pos: Vec2::ZERO,
+            size: Vec2::ONE,
+            rotation: to,
+            draw_layer: 0,
+            alpha: 1.0,
+            payload: (),
+        };
+        let previous = PreviousState {
+            pos: Vec2::ZERO,
+            size: Vec2::ONE,
+            rotation: from,
+            alpha: 1.0,
+        };
+        current.interpolated(&previous, 1.0, t).rotation
+    }
+
+    #[test]
+    fn rotation_takes_the_shorter_arc() {
+        // Across the +-180 seam.
+        assert!((blend(170.0, -170.0, 0.5) - 180.0).abs() < 1e-4);
+        assert!((blend(-170.0, 170.0, 0.25) + 175.0).abs() < 1e-4);
+        // Across a full turn.
+        assert!((blend(350.0, 10.0, 0.5) - 360.0).abs() < 1e-4);
+        assert!((blend(10.0, 60.0, 0.5) - 35.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn frames_blend_between_steps() {
+        let mut system = ParticleSystem::new();
+        system.set_interpolation(true);
+        let spawn = ParticleSpawn::new(Mote, 10, Vec2::ZERO, Vec2::ONE)
+            .with_velocity(Vec2::new(2.0, 0.0))
+            .with_alpha_velocity(-0.5);
+        system.spawn(spawn);
+        system.spawn(spawn.with_analytic());
+        system.step();
+
+        for p in drawn(&system, 0.25) {
+            assert!((p.pos.x - 0.5).abs() < 1e-6);
+            assert!((p.alpha - 0.875).abs() < 1e-6);
+        }
+        // Spawned since the last step: no motion to blend yet.
+        system.spawn(spawn.with_velocity(Vec2::new(0.0, 2.0)));
+        let late = drawn(&system, 0.5);
+        assert_eq!(late.len(), 3);
+        assert!(late.iter().any(|p| p.pos == Vec2::ZERO));
+    }
+
+    #[test]
+    fn expired_particles_are_drawn_until_the_next_step() {
+        let mut system = ParticleSystem::new();
+        system.set_interpolation(true);
+        let spawn =
+            ParticleSpawn::new(Mote, 1, Vec2::ZERO, Vec2::ONE).with_velocity(Vec2::new(4.0, 0.0));
+        system.spawn(spawn);
+        system.spawn(ParticleSpawn::new(Mote, 1, Vec2::ONE, Vec2::ONE));
+        system.step();
+        system.step();
+
+        assert!(system.is_empty());
+        let dying = drawn(&system, 0.5);
+        assert_eq!(dying.len(), 2);
+        // Drawn at the final state, not blended.
+        assert!(dying.iter().any(|p| p.pos == Vec2::new(4.0, 0.0)));
+        assert!(dying.iter().all(|p| p.counter == 0));
+
+        system.step();
+        assert!(drawn(&system, 0.5).is_empty());
+    }
+
+    #[test]
+    fn only_expiry_leaves_a_last_frame() {
+        let mut system = ParticleSystem::new();
+        system.set_interpolation(true);
+        system.spawn(ParticleSpawn::new(Mote, 5, Vec2::ZERO, Vec2::ONE));
+        system.step();
+        assert_eq!(system.kill_by_type(Mote), 1);
+        assert!(drawn(&system, 0.5).is_empty());
+
+        system.set_interpolation(false);
+        system.spawn(ParticleSpawn::new(Mote, 1, Vec2::ZERO, Vec2::ONE));
+        system.step();
+        system.step();
+        assert!(drawn(&system, 0.5).is_empty());
+    }
+}
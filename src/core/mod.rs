@@ -1,5 +1,52 @@
+mod affector;
+mod analytic;
+mod anchor;
+mod ballistic_lanes;
+mod budget;
+mod bulk;
+mod clock;
+mod collision;
+mod constraint;
+mod culling;
+mod death;
+mod emitter;
+mod flocking;
+mod handle;
+mod impulse;
+mod interpolation;
+#[cfg(feature = "parallel")]
+mod parallel;
 mod particle_model;
 mod particle_system;
+mod particle_types;
+mod particle_view;
+mod region;
+mod removal;
+mod spatial;
+mod spline_path;
+mod time_scale;
+mod trail;
 
+pub use affector::{Affector, AffectorId, AffectorStage};
+pub use anchor::AnchorId;
+pub use budget::*;
+pub use clock::{CatchUpPolicy, FrameSteps, SimulationClock};
+pub use collision::ParticleCollision;
+pub use constraint::{
+    ConstraintBreak, ConstraintEnd, ConstraintSet, ConstraintSetId, DistanceConstraint,
+};
+pub use culling::ViewCull;
+pub use death::{DeathCause, ParticleDeath};
+pub use emitter::{EffectInstance, Emitter};
+pub use flocking::Flocking;
+pub use handle::ParticleHandle;
+pub use impulse::Shockwave;
+#[cfg(feature = "parallel")]
+pub use parallel::ParallelConfig;
 pub use particle_model::*;
 pub use particle_system::*;
+pub use particle_view::{ParticleBatch, ParticleMut};
+pub use region::Rect;
+pub use spline_path::*;
+pub use time_scale::{TimeDilationZone, TimeZoneId};
+pub use trail::{RibbonVertex, TrailSampling, TrailStyle, TrailView};
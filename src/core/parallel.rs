@@ -0,0 +1,179 @@
+use rayon::prelude::*;
+
+use super::ballistic_lanes::{
+    dispatch_lane_key, step_keyed_particle, DYNAMIC_LANE_KEY, ORIENTED_LANE,
+};
+use super::particle_model::{
+    BallisticCold, BallisticHot, ParticlePayload, ParticleTypeTrait, SplineParticle,
+    HAS_ACCELERATION, HAS_ALPHA_VELOCITY, HAS_ROTATION_ACCELERATION, HAS_ROTATION_VELOCITY,
+    HAS_SIZE_ACCELERATION, HAS_SIZE_VELOCITY, HAS_VELOCITY,
+};
+use super::particle_system::{retire_particle, step_spline_particle, ParticleSystem};
+
+// Below `threshold` total particles, or with a single worker thread, `step`
+// stays on the single-threaded path. The default threshold is provisional:
+// experiment 07 in `docs/benchmarks` only measured overhead on one core, so
+// it is not tuned against a multi-core speedup yet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParallelConfig {
+    pub threshold: usize,
+    pub chunk_size: usize,
+}
+
+impl Default for ParallelConfig {
+    fn default() -> Self {
+        Self {
+            // Provisional until measured on multi-core hardware.
+            threshold: 200_000,
+            chunk_size: 16_384,
+        }
+    }
+}
+
+impl<T, U> ParticleSystem<T, U>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    pub fn set_parallel_config(&mut self, config: ParallelConfig) {
+        self.parallel_config = config;
+    }
+
+    pub fn parallel_config(&self) -> ParallelConfig {
+        self.parallel_config
+    }
+
+    // Steps each lane in fixed-size chunks on the rayon pool. Deaths are
+    // compacted to the tail of their chunk, retired serially, and the live
+    // ranges are then merged back into a dense lane. Returns `false` when the
+    // single-threaded path should run instead.
+    pub(crate) fn step_parallel(&mut self) -> bool {
+        let config = self.parallel_config;
+        if self.len() < config.threshold
+            || rayon::current_num_threads() < 2
+            || self.types.has_trails()
+            || self.interpolation
+            || self.stepwise()
+            || self.deaths.is_recording()
+            || !self.handles.is_empty()
+        {
+            return false;
+        }
+
+        let chunk_size = config.chunk_size.max(1);
+
+        let mut alive = std::mem::take(&mut self.parallel_alive);
+        for lane in &mut self.ballistic.lanes {
+            let key = lane.key;
+            lane.hot
+                .par_chunks_mut(chunk_size)
+                .zip(lane.cold.par_chunks_mut(chunk_size))
+                .map(|(hot, cold)| {
+                    dispatch_lane_key!(key, step_keyed_chunk::<T, U>(hot, cold, key))
+                })
+                .collect_into_vec(&mut alive);
+            merge_chunks(&mut lane.cold, &alive, chunk_size, |p| {
+                retire_particle(
+                    p.trail,
+                    p.type_slot,
+                    p.handle,
+                    &mut self.trails,
+                    &mut self.types,
+                    &mut self.handles,
+                )
+            });
+            merge_chunks(&mut lane.hot, &alive, chunk_size, |_| {});
+        }
+
+        let paths = &self.spline_paths;
+        let anchors = &self.anchors;
+        self.spline_particles
+            .par_chunks_mut(chunk_size)
+            .map(|chunk| {
+                step_chunk(
+                    chunk,
+                    |p: &SplineParticle<T, U>| p.core.counter,
+                    |p| step_spline_particle(p, paths, anchors),
+                )
+            })
+            .collect_into_vec(&mut alive);
+        merge_chunks(&mut self.spline_particles, &alive, chunk_size, |p| {
+            retire_particle(
+                p.core.trail,
+                p.core.type_slot,
+                p.core.handle,
+                &mut self.trails,
+                &mut self.types,
+                &mut self.handles,
+            )
+        });
+
+        self.parallel_alive = alive;
+        true
+    }
+}
+
+// The ballistic variant of `step_chunk`, swapping both halves in lockstep.
+fn step_keyed_chunk<T, U, const KEY: u16>(
+    hot: &mut [BallisticHot],
+    cold: &mut [BallisticCold<T, U>],
+    key: u16,
+) -> usize
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    let mut end = hot.len();
+    let mut i = 0;
+    while i < end {
+        if hot[i].counter == 0 {
+            end -= 1;
+            hot.swap(i, end);
+            cold.swap(i, end);
+            continue;
+        }
+
+        step_keyed_particle::<T, U, KEY>(&mut hot[i], &mut cold[i], key);
+        i += 1;
+    }
+    end
+}
+
+// Same `swap_remove` strategy as the serial loop, bounded to one chunk.
+#[inline(always)]
+fn step_chunk<P>(chunk: &mut [P], counter: impl Fn(&P) -> u32, step: impl Fn(&mut P)) -> usize {
+    let mut end = chunk.len();
+    let mut i = 0;
+    while i < end {
+        if counter(&chunk[i]) == 0 {
+            end -= 1;
+            chunk.swap(i, end);
+            continue;
+        }
+
+        step(&mut chunk[i]);
+        i += 1;
+    }
+    end
+}
+
+fn merge_chunks<P: Copy>(
+    lane: &mut Vec<P>,
+    alive: &[usize],
+    chunk_size: usize,
+    mut retire: impl FnMut(&P),
+) {
+    let mut write = 0;
+    for (chunk, &count) in alive.iter().enumerate() {
+        let start = chunk * chunk_size;
+        let end = (start + chunk_size).min(lane.len());
+        for dead in &lane[start + count..end] {
+            retire(dead);
+        }
+        if write != start {
+            lane.copy_within(start..start + count, write);
+        }
+        write += count;
+    }
+    lane.truncate(write);
+}
@@ -1,6 +1,79 @@
-use glam::Vec2;
+use glam::{IVec2, Vec2};
 
-pub trait ParticleTypeTrait: Copy + Send + Sync + 'static {}
+use super::anchor::{AnchorId, AnchorTable, NO_ANCHOR};
+use super::death::DeathCause;
+use super::handle::NO_HANDLE;
+use super::particle_types::NO_TYPE_SLOT;
+use super::particle_view::ParticleMut;
+use super::spline_path::{SplinePath, SplinePathId};
+use super::trail::NO_TRAIL;
+
+// Implemented by the particle type tag. Every hook defaults to doing nothing
+// and is called through the concrete type, so types that leave them alone
+// compile to the same step loop as before.
+pub trait ParticleTypeTrait: Copy + Send + Sync + 'static {
+    // Set when overriding `on_step`. Stepped types stay out of the analytic
+    // lane and `advance` steps them one at a time.
+    const STEP_HOOK: bool = false;
+    // Set when overriding `on_death`; dying particles are only read back for
+    // types that ask.
+    const DEATH_HOOK: bool = false;
+
+    // Runs once the spawn is built and before it picks a lane, so velocity
+    // written here is integrated.
+    fn on_spawn<U>(self, _particle: &mut ParticleMut<'_, Self, U>)
+    where
+        U: ParticlePayload,
+    {
+    }
+
+    // Runs after each step's integration while `STEP_HOOK` is set. Paused
+    // particles are not stepped. Velocity written here is integrated from the
+    // next step on, including for particles spawned without one; the other
+    // rates are fixed at spawn.
+    fn on_step<U>(self, _particle: &mut ParticleMut<'_, Self, U>)
+    where
+        U: ParticlePayload,
+    {
+    }
+
+    // Runs with the particle's final state while `DEATH_HOOK` is set. `clear`
+    // is not a death.
+    fn on_death<U>(self, _particle: &ParticleRenderData<Self, U>, _cause: DeathCause)
+    where
+        U: ParticlePayload,
+    {
+    }
+
+    // Lifetime in steps for spawns built with `ParticleSpawn::of_type`.
+    fn default_lifetime(self) -> Option<u32> {
+        None
+    }
+
+    // Where the renderer finds this type's sprite, given the steps it has left.
+    fn atlas_region(self, _counter: u32) -> Option<AtlasRegion> {
+        None
+    }
+}
+
+// A sprite's rectangle in a texture atlas, in pixels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AtlasRegion {
+    pub pos: IVec2,
+    pub size: IVec2,
+}
+
+impl AtlasRegion {
+    pub const fn new(pos: IVec2, size: IVec2) -> Self {
+        Self { pos, size }
+    }
+}
+
+// Per-particle user data carried alongside the type tag, e.g. an owning entity
+// or a sound id. The default `()` takes no space.
+pub trait ParticlePayload: Copy + Send + Sync + 'static {}
+
+impl<U> ParticlePayload for U where U: Copy + Send + Sync + 'static {}
 
 pub(crate) const HAS_VELOCITY: u16 = 1 << 0;
 pub(crate) const HAS_ACCELERATION: u16 = 1 << 1;
@@ -13,6 +86,12 @@ pub(crate) const HAS_ALPHA_ACCELERATION: u16 = 1 << 7;
 
 pub(crate) const HAS_SPLINE_VELOCITY: u16 = 1 << 0;
 pub(crate) const HAS_SPLINE_ACCELERATION: u16 = 1 << 1;
+pub(crate) const HAS_SPLINE_ARC_LENGTH: u16 = 1 << 2;
+pub(crate) const HAS_SPLINE_ANCHORS: u16 = 1 << 3;
+
+const SPLINE_ANCHOR_START: usize = 0;
+const SPLINE_ANCHOR_CONTROL: usize = 1;
+const SPLINE_ANCHOR_END: usize = 2;
 
 #[derive(Clone, Copy, Debug)]
 pub struct SplineState {
@@ -23,10 +102,33 @@ pub struct SplineState {
     pub point_3: Vec2,
 }
 
+// With `arc_length` set, progress is distance-based: `t`, velocity and
+// acceleration are measured along the path instead of in parameter space.
+// Only shared paths keep the length table this needs.
+#[derive(Clone, Copy, Debug)]
+pub struct SplinePathState {
+    pub t: f32,
+    pub strength: f32,
+    pub path: SplinePathId,
+    pub arc_length: bool,
+}
+
+// Aligned rotations are written in degrees, the same unit `rotation` carries
+// everywhere else, with `0` pointing along +x.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Orientation {
+    #[default]
+    Free,
+    AlignToVelocity,
+    AlignToSplineTangent,
+    FacePoint(Vec2),
+}
+
 #[derive(Clone, Copy, Debug)]
-pub struct ParticleSpawn<T>
+pub struct ParticleSpawn<T, U = ()>
 where
     T: ParticleTypeTrait,
+    U: ParticlePayload,
 {
     pub particle_type: T,
     pub counter: u32,
@@ -43,9 +145,15 @@ where
     pub rotation_acceleration: Option<f32>,
     pub alpha_velocity: Option<f32>,
     pub alpha_acceleration: Option<f32>,
+    pub orientation: Orientation,
+    pub stretch: Option<f32>,
     pub spline: Option<SplineState>,
+    pub spline_path: Option<SplinePathState>,
     pub spline_velocity: Option<f32>,
     pub spline_acceleration: Option<f32>,
+    pub spline_anchors: [Option<AnchorId>; 3],
+    pub analytic: bool,
+    pub payload: U,
 }
 
 impl<T> ParticleSpawn<T>
@@ -69,9 +177,60 @@ where
             rotation_acceleration: None,
             alpha_velocity: None,
             alpha_acceleration: None,
+            orientation: Orientation::Free,
+            stretch: None,
             spline: None,
+            spline_path: None,
             spline_velocity: None,
             spline_acceleration: None,
+            spline_anchors: [None; 3],
+            analytic: false,
+            payload: (),
+        }
+    }
+
+    // `new` with the lifetime from `ParticleTypeTrait::default_lifetime`, or
+    // `None` for types without one.
+    pub fn of_type(particle_type: T, pos: Vec2, size: Vec2) -> Option<Self> {
+        let counter = particle_type.default_lifetime()?;
+        Some(Self::new(particle_type, counter, pos, size))
+    }
+}
+
+impl<T, U> ParticleSpawn<T, U>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    pub fn with_payload<V>(self, payload: V) -> ParticleSpawn<T, V>
+    where
+        V: ParticlePayload,
+    {
+        ParticleSpawn {
+            particle_type: self.particle_type,
+            counter: self.counter,
+            pos: self.pos,
+            size: self.size,
+            rotation: self.rotation,
+            draw_layer: self.draw_layer,
+            alpha: self.alpha,
+            velocity: self.velocity,
+            acceleration: self.acceleration,
+            size_velocity: self.size_velocity,
+            size_acceleration: self.size_acceleration,
+            rotation_velocity: self.rotation_velocity,
+            rotation_acceleration: self.rotation_acceleration,
+            alpha_velocity: self.alpha_velocity,
+            alpha_acceleration: self.alpha_acceleration,
+            orientation: self.orientation,
+            stretch: self.stretch,
+            spline: self.spline,
+            spline_path: self.spline_path,
+            spline_velocity: self.spline_velocity,
+            spline_acceleration: self.spline_acceleration,
+            spline_anchors: self.spline_anchors,
+            analytic: self.analytic,
+            payload,
         }
     }
 
@@ -130,11 +289,28 @@ where
         self
     }
 
+    pub fn with_orientation(mut self, orientation: Orientation) -> Self {
+        self.orientation = orientation;
+        self
+    }
+
+    // Rendered `size.x` becomes `size.x * (1 + speed * stretch)`, where speed is
+    // the distance moved in the last step.
+    pub fn with_stretch(mut self, stretch: f32) -> Self {
+        self.stretch = Some(stretch);
+        self
+    }
+
     pub fn with_spline(mut self, spline: SplineState) -> Self {
         self.spline = Some(spline);
         self
     }
 
+    pub fn with_spline_path(mut self, spline_path: SplinePathState) -> Self {
+        self.spline_path = Some(spline_path);
+        self
+    }
+
     pub fn with_spline_velocity(mut self, spline_velocity: f32) -> Self {
         self.spline_velocity = Some(spline_velocity);
         self
@@ -144,12 +320,45 @@ where
         self.spline_acceleration = Some(spline_acceleration);
         self
     }
+
+    // Anchored control points follow a live anchor position, with the value
+    // given in the spline state used as an offset from it. On a spline path
+    // the start and end anchors blend linearly over progress, and the control
+    // anchor moves the whole path.
+    pub fn with_spline_start_anchor(mut self, anchor: AnchorId) -> Self {
+        self.spline_anchors[SPLINE_ANCHOR_START] = Some(anchor);
+        self
+    }
+
+    pub fn with_spline_control_anchor(mut self, anchor: AnchorId) -> Self {
+        self.spline_anchors[SPLINE_ANCHOR_CONTROL] = Some(anchor);
+        self
+    }
+
+    pub fn with_spline_end_anchor(mut self, anchor: AnchorId) -> Self {
+        self.spline_anchors[SPLINE_ANCHOR_END] = Some(anchor);
+        self
+    }
+
+    // Requests the stateless analytic lane, which stores only the spawn state
+    // and evaluates it in closed form at render time. Spawns it cannot
+    // represent exactly (splines, trails, channels that would clamp part way
+    // through the lifetime) fall back to the stepped lanes.
+    pub fn with_analytic(mut self) -> Self {
+        self.analytic = true;
+        self
+    }
+
+    pub fn is_spline(&self) -> bool {
+        self.spline.is_some() || self.spline_path.is_some()
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
-pub(crate) struct ParticleCore<T>
+pub(crate) struct ParticleCore<T, U>
 where
     T: ParticleTypeTrait,
+    U: ParticlePayload,
 {
     pub(crate) particle_type: T,
     pub(crate) counter: u32,
@@ -166,14 +375,27 @@ where
     pub(crate) rotation_acceleration: f32,
     pub(crate) alpha_velocity: f32,
     pub(crate) alpha_acceleration: f32,
+    pub(crate) orientation: Orientation,
+    pub(crate) stretch: f32,
+    pub(crate) stretch_scale: f32,
+    pub(crate) oriented: bool,
+    pub(crate) trail: u32,
+    pub(crate) spawn_tick: u32,
+    // Lifetime already used towards the next counter decrement; only scaled
+    // time leaves a fraction here.
+    pub(crate) partial_step: f32,
+    pub(crate) type_slot: u16,
     pub(crate) flags: u16,
+    pub(crate) handle: u32,
+    pub(crate) payload: U,
 }
 
-impl<T> ParticleCore<T>
+impl<T, U> ParticleCore<T, U>
 where
     T: ParticleTypeTrait,
+    U: ParticlePayload,
 {
-    pub(crate) fn from_spawn(spawn: &ParticleSpawn<T>) -> Self {
+    pub(crate) fn from_spawn(spawn: &ParticleSpawn<T, U>) -> Self {
         let mut flags = 0u16;
 
         let velocity = if let Some(v) = spawn.velocity {
@@ -232,6 +454,8 @@ where
             0.0
         };
 
+        let stretch = spawn.stretch.unwrap_or(0.0);
+
         Self {
             particle_type: spawn.particle_type,
             counter: spawn.counter,
@@ -248,7 +472,133 @@ where
             rotation_acceleration,
             alpha_velocity,
             alpha_acceleration,
+            orientation: spawn.orientation,
+            stretch,
+            stretch_scale: 1.0,
+            oriented: spawn.orientation != Orientation::Free || stretch != 0.0,
+            trail: NO_TRAIL,
+            spawn_tick: 0,
+            partial_step: 0.0,
+            type_slot: NO_TYPE_SLOT,
             flags,
+            handle: NO_HANDLE,
+            payload: spawn.payload,
+        }
+    }
+}
+
+// The ballistic lane stores each particle as a hot record, holding what every
+// step reads, plus a cold record in a parallel array at the same index. Cold
+// fields are only touched on spawn, death, render, or by lanes whose key needs
+// them (rotation/alpha acceleration, orientation, trails).
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct BallisticHot {
+    pub(crate) counter: u32,
+    pub(crate) pos: Vec2,
+    pub(crate) size: Vec2,
+    pub(crate) rotation: f32,
+    pub(crate) alpha: f32,
+    pub(crate) velocity: Vec2,
+    pub(crate) acceleration: Vec2,
+    pub(crate) size_velocity: f32,
+    pub(crate) size_acceleration: f32,
+    pub(crate) rotation_velocity: f32,
+    pub(crate) alpha_velocity: f32,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct BallisticCold<T, U>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    pub(crate) particle_type: T,
+    pub(crate) draw_layer: u32,
+    pub(crate) rotation_acceleration: f32,
+    pub(crate) alpha_acceleration: f32,
+    pub(crate) orientation: Orientation,
+    pub(crate) stretch: f32,
+    pub(crate) stretch_scale: f32,
+    pub(crate) trail: u32,
+    pub(crate) spawn_tick: u32,
+    pub(crate) partial_step: f32,
+    pub(crate) type_slot: u16,
+    pub(crate) handle: u32,
+    pub(crate) payload: U,
+}
+
+impl<T, U> ParticleCore<T, U>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    pub(crate) fn split(&self) -> (BallisticHot, BallisticCold<T, U>) {
+        (
+            BallisticHot {
+                counter: self.counter,
+                pos: self.pos,
+                size: self.size,
+                rotation: self.rotation,
+                alpha: self.alpha,
+                velocity: self.velocity,
+                acceleration: self.acceleration,
+                size_velocity: self.size_velocity,
+                size_acceleration: self.size_acceleration,
+                rotation_velocity: self.rotation_velocity,
+                alpha_velocity: self.alpha_velocity,
+            },
+            BallisticCold {
+                particle_type: self.particle_type,
+                draw_layer: self.draw_layer,
+                rotation_acceleration: self.rotation_acceleration,
+                alpha_acceleration: self.alpha_acceleration,
+                orientation: self.orientation,
+                stretch: self.stretch,
+                stretch_scale: self.stretch_scale,
+                trail: self.trail,
+                spawn_tick: self.spawn_tick,
+                partial_step: self.partial_step,
+                type_slot: self.type_slot,
+                handle: self.handle,
+                payload: self.payload,
+            },
+        )
+    }
+
+    // Inverse of `split`; `flags` and `oriented` come from the sub-lane key.
+    pub(crate) fn from_parts(
+        hot: &BallisticHot,
+        cold: &BallisticCold<T, U>,
+        flags: u16,
+        oriented: bool,
+    ) -> Self {
+        Self {
+            particle_type: cold.particle_type,
+            counter: hot.counter,
+            pos: hot.pos,
+            size: hot.size,
+            rotation: hot.rotation,
+            draw_layer: cold.draw_layer,
+            alpha: hot.alpha,
+            velocity: hot.velocity,
+            acceleration: hot.acceleration,
+            size_velocity: hot.size_velocity,
+            size_acceleration: hot.size_acceleration,
+            rotation_velocity: hot.rotation_velocity,
+            rotation_acceleration: cold.rotation_acceleration,
+            alpha_velocity: hot.alpha_velocity,
+            alpha_acceleration: cold.alpha_acceleration,
+            orientation: cold.orientation,
+            stretch: cold.stretch,
+            stretch_scale: cold.stretch_scale,
+            oriented,
+            trail: cold.trail,
+            spawn_tick: cold.spawn_tick,
+            partial_step: cold.partial_step,
+            type_slot: cold.type_slot,
+            flags,
+            handle: cold.handle,
+            payload: cold.payload,
         }
     }
 }
@@ -257,27 +607,53 @@ where
 pub(crate) struct SplineMotion {
     pub(crate) t: f32,
     pub(crate) strength: f32,
+    // The curve's coefficients. Path particles have no curve of their own;
+    // for them these hold a correction to the anchor offset, left by frame
+    // changes.
     pub(crate) bezier_a: Vec2,
     pub(crate) bezier_b: Vec2,
     pub(crate) bezier_c: Vec2,
+    pub(crate) path: Option<SplinePathId>,
+    pub(crate) anchors: [u32; 3],
     pub(crate) velocity: f32,
     pub(crate) acceleration: f32,
     pub(crate) flags: u16,
 }
 
 impl SplineMotion {
-    pub(crate) fn from_spawn<T>(spawn: &ParticleSpawn<T>) -> Self
+    pub(crate) fn from_spawn<T, U>(spawn: &ParticleSpawn<T, U>) -> Self
     where
         T: ParticleTypeTrait,
+        U: ParticlePayload,
     {
-        let spline = spawn
-            .spline
-            .expect("internal error: spline lane requires spline state");
-        let bezier_c = spline.point_1;
-        let bezier_b = (spline.point_2 - spline.point_1) * 2.0;
-        let bezier_a = spline.point_1 - (spline.point_2 * 2.0) + spline.point_3;
-
         let mut flags = 0u16;
+        let (t, strength, path, bezier_a, bezier_b, bezier_c) =
+            if let Some(spline_path) = spawn.spline_path {
+                if spline_path.arc_length {
+                    flags |= HAS_SPLINE_ARC_LENGTH;
+                }
+                (
+                    spline_path.t,
+                    spline_path.strength,
+                    Some(spline_path.path),
+                    Vec2::ZERO,
+                    Vec2::ZERO,
+                    Vec2::ZERO,
+                )
+            } else {
+                let spline = spawn
+                    .spline
+                    .expect("internal error: spline lane requires spline state");
+                (
+                    spline.t,
+                    spline.strength,
+                    None,
+                    spline.point_1 - (spline.point_2 * 2.0) + spline.point_3,
+                    (spline.point_2 - spline.point_1) * 2.0,
+                    spline.point_1,
+                )
+            };
+
         let velocity = if let Some(v) = spawn.spline_velocity {
             flags |= HAS_SPLINE_VELOCITY;
             v
@@ -292,12 +668,21 @@ impl SplineMotion {
             0.0
         };
 
+        let anchors = spawn
+            .spline_anchors
+            .map(|anchor| anchor.map_or(NO_ANCHOR, |anchor| anchor.0));
+        if anchors.iter().any(|&anchor| anchor != NO_ANCHOR) {
+            flags |= HAS_SPLINE_ANCHORS;
+        }
+
         Self {
-            t: spline.t,
-            strength: spline.strength,
+            t,
+            strength,
             bezier_a,
             bezier_b,
             bezier_c,
+            path,
+            anchors,
             velocity,
             acceleration,
             flags,
@@ -313,21 +698,92 @@ impl SplineMotion {
     pub(crate) fn evaluate_bezier(&self, t: f32) -> Vec2 {
         ((self.bezier_a * t) + self.bezier_b) * t + self.bezier_c
     }
+
+    #[inline(always)]
+    pub(crate) fn evaluate(&self, t: f32, paths: &[SplinePath]) -> Vec2 {
+        match self.path {
+            None => self.evaluate_bezier(t),
+            Some(path) => paths[path.index()].point(t),
+        }
+    }
+
+    // Direction of travel at curve parameter `t`, `progress` along it. Bound
+    // anchors move the curve with progress, so their offset's derivative is
+    // part of the direction too.
+    #[inline(always)]
+    pub(crate) fn tangent(
+        &self,
+        t: f32,
+        progress: f32,
+        paths: &[SplinePath],
+        anchors: &AnchorTable,
+    ) -> Vec2 {
+        let tangent = match self.path {
+            None => (self.bezier_a * (2.0 * t)) + self.bezier_b,
+            Some(path) => paths[path.index()].tangent(t),
+        };
+        if !self.has(HAS_SPLINE_ANCHORS) {
+            return tangent;
+        }
+
+        // Under arc-length progress, progress advances at the curve's speed
+        // over its length rather than one-to-one with `t`.
+        let progress_rate = match self.path {
+            Some(path) if self.has(HAS_SPLINE_ARC_LENGTH) => {
+                let length = paths[path.index()].length();
+                if length > 0.0 {
+                    tangent.length() / length
+                } else {
+                    0.0
+                }
+            }
+            _ => 1.0,
+        };
+        tangent + self.anchor_offset_tangent(progress, anchors) * progress_rate
+    }
+
+    // `progress` is the normalized `0..=1` position along the curve.
+    #[inline(always)]
+    pub(crate) fn anchor_offset(&self, progress: f32, anchors: &AnchorTable) -> Vec2 {
+        let start = anchors.get_raw(self.anchors[SPLINE_ANCHOR_START]);
+        let control = anchors.get_raw(self.anchors[SPLINE_ANCHOR_CONTROL]);
+        let end = anchors.get_raw(self.anchors[SPLINE_ANCHOR_END]);
+        if self.path.is_none() {
+            calculate_bezier_point(progress, start, control, end)
+        } else {
+            control + start.lerp(end, progress) + self.evaluate_bezier(progress)
+        }
+    }
+
+    // Derivative of `anchor_offset` with respect to `progress`.
+    #[inline(always)]
+    fn anchor_offset_tangent(&self, progress: f32, anchors: &AnchorTable) -> Vec2 {
+        let start = anchors.get_raw(self.anchors[SPLINE_ANCHOR_START]);
+        let control = anchors.get_raw(self.anchors[SPLINE_ANCHOR_CONTROL]);
+        let end = anchors.get_raw(self.anchors[SPLINE_ANCHOR_END]);
+        if self.path.is_none() {
+            (control - start) * (2.0 * (1.0 - progress)) + (end - control) * (2.0 * progress)
+        } else {
+            end - start + (self.bezier_a * (2.0 * progress)) + self.bezier_b
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
-pub(crate) struct SplineParticle<T>
+pub(crate) struct SplineParticle<T, U>
 where
     T: ParticleTypeTrait,
+    U: ParticlePayload,
 {
-    pub(crate) core: ParticleCore<T>,
+    pub(crate) core: ParticleCore<T, U>,
     pub(crate) spline: SplineMotion,
 }
 
 #[derive(Clone, Copy, Debug)]
-pub struct ParticleRenderData<T>
+pub struct ParticleRenderData<T, U = ()>
 where
     T: ParticleTypeTrait,
+    U: ParticlePayload,
 {
     pub particle_type: T,
     pub counter: u32,
@@ -336,26 +792,261 @@ where
     pub rotation: f32,
     pub draw_layer: u32,
     pub alpha: f32,
+    pub payload: U,
 }
 
-impl<T> From<&ParticleCore<T>> for ParticleRenderData<T>
+impl<T, U> From<&ParticleCore<T, U>> for ParticleRenderData<T, U>
 where
     T: ParticleTypeTrait,
+    U: ParticlePayload,
 {
-    fn from(value: &ParticleCore<T>) -> Self {
+    fn from(value: &ParticleCore<T, U>) -> Self {
         Self {
             particle_type: value.particle_type,
             counter: value.counter,
             pos: value.pos,
-            size: value.size,
+            size: Vec2::new(value.size.x * value.stretch_scale, value.size.y),
             rotation: value.rotation,
             draw_layer: value.draw_layer,
             alpha: value.alpha,
+            payload: value.payload,
+        }
+    }
+}
+
+impl<T, U> From<(&BallisticHot, &BallisticCold<T, U>)> for ParticleRenderData<T, U>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    fn from((hot, cold): (&BallisticHot, &BallisticCold<T, U>)) -> Self {
+        Self {
+            particle_type: cold.particle_type,
+            counter: hot.counter,
+            pos: hot.pos,
+            size: Vec2::new(hot.size.x * cold.stretch_scale, hot.size.y),
+            rotation: hot.rotation,
+            draw_layer: cold.draw_layer,
+            alpha: hot.alpha,
+            payload: cold.payload,
         }
     }
 }
 
+// Rotation in degrees facing `direction`, or `None` when there is nothing to face.
+#[inline(always)]
+pub(crate) fn facing_rotation(
+    orientation: Orientation,
+    pos: Vec2,
+    motion: Vec2,
+    tangent: Vec2,
+) -> Option<f32> {
+    let direction = match orientation {
+        Orientation::Free => Vec2::ZERO,
+        Orientation::AlignToVelocity => motion,
+        Orientation::AlignToSplineTangent => tangent,
+        Orientation::FacePoint(point) => point - pos,
+    };
+    (direction != Vec2::ZERO).then(|| direction.y.atan2(direction.x).to_degrees())
+}
+
 pub fn calculate_bezier_point(t: f32, point_1: Vec2, point_2: Vec2, point_3: Vec2) -> Vec2 {
     let one_minus_t = 1.0 - t;
     (point_1 * one_minus_t * one_minus_t) + (point_2 * 2.0 * one_minus_t * t) + (point_3 * t * t)
 }
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec2;
+
+    use super::{Orientation, ParticlePayload, ParticleSpawn, ParticleTypeTrait, SplineState};
+    use crate::core::ParticleMut;
+    use crate::core::{ParticleHandle, ParticleSystem};
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct Shard;
+
+    impl ParticleTypeTrait for Shard {}
+
+    fn shard() -> ParticleSpawn<Shard> {
+        ParticleSpawn::new(Shard, 100, Vec2::ZERO, Vec2::new(2.0, 1.0))
+    }
+
+    fn angle_between(a: f32, b: f32) -> f32 {
+        (a - b + 180.0).rem_euclid(360.0) - 180.0
+    }
+
+    // Rotation against the direction moved over the last step.
+    fn heading_error(system: &mut ParticleSystem<Shard>, handle: ParticleHandle) -> f32 {
+        let before = system.get(handle).unwrap().pos;
+        system.step();
+        let particle = system.get(handle).unwrap();
+        let motion = particle.pos - before;
+        angle_between(particle.rotation, motion.y.atan2(motion.x).to_degrees()).abs()
+    }
+
+    #[test]
+    fn align_to_velocity_and_stretch() {
+        let mut system = ParticleSystem::new();
+        let spawn = |analytic| {
+            let spawn = shard()
+                .with_velocity(Vec2::new(3.0, 0.0))
+                .with_acceleration(Vec2::new(0.0, 0.5))
+                .with_orientation(Orientation::AlignToVelocity)
+                .with_stretch(0.5);
+            if analytic {
+                spawn.with_analytic()
+            } else {
+                spawn
+            }
+        };
+        let stepped = system.spawn_with_handle(spawn(false)).unwrap();
+        let analytic = system.spawn_with_handle(spawn(true)).unwrap();
+
+        for _ in 0..6 {
+            system.step();
+        }
+        let velocity = Vec2::new(3.0, 0.5 * 6.0);
+        let expected = velocity.y.atan2(velocity.x).to_degrees();
+        for handle in [stepped, analytic] {
+            let particle = system.get(handle).unwrap();
+            assert!(angle_between(particle.rotation, expected).abs() < 1e-3);
+            let stretched = 2.0 * (1.0 + velocity.length() * 0.5);
+            assert!(
+                (particle.size.x - stretched).abs() < 1e-3,
+                "{}",
+                particle.size.x
+            );
+            assert_eq!(particle.size.y, 1.0);
+        }
+    }
+
+    #[test]
+    fn face_point_and_free() {
+        let mut system = ParticleSystem::new();
+        let facing = system
+            .spawn_with_handle(
+                shard()
+                    .with_velocity(Vec2::new(0.0, 1.0))
+                    .with_orientation(Orientation::FacePoint(Vec2::new(10.0, 5.0))),
+            )
+            .unwrap();
+        let free = system
+            .spawn_with_handle(
+                shard()
+                    .with_velocity(Vec2::new(0.0, 1.0))
+                    .with_rotation(30.0)
+                    .with_rotation_velocity(2.0),
+            )
+            .unwrap();
+
+        for _ in 0..5 {
+            system.step();
+        }
+        // At (0, 5), the point lies straight along +x.
+        assert!(angle_between(system.get(facing).unwrap().rotation, 0.0).abs() < 1e-3);
+        assert!((system.get(free).unwrap().rotation - 40.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn align_to_spline_tangent_follows_the_curve() {
+        let mut system = ParticleSystem::new();
+        let curve = SplineState {
+            t: 0.0,
+            strength: 1.0,
+            point_1: Vec2::ZERO,
+            point_2: Vec2::new(50.0, 80.0),
+            point_3: Vec2::new(100.0, 0.0),
+        };
+        let spawn = |spline| {
+            shard()
+                .with_spline(spline)
+                .with_spline_velocity(0.02)
+                .with_orientation(Orientation::AlignToSplineTangent)
+        };
+        let plain = system.spawn_with_handle(spawn(curve)).unwrap();
+
+        // All of an anchored curve's shape can come from its anchors.
+        let start = system.add_anchor(Vec2::new(0.0, 0.0));
+        let control = system.add_anchor(Vec2::new(-40.0, 60.0));
+        let end = system.add_anchor(Vec2::new(80.0, 100.0));
+        let anchored = system
+            .spawn_with_handle(
+                spawn(SplineState {
+                    point_1: Vec2::ZERO,
+                    point_2: Vec2::ZERO,
+                    point_3: Vec2::ZERO,
+                    ..curve
+                })
+                .with_spline_start_anchor(start)
+                .with_spline_control_anchor(control)
+                .with_spline_end_anchor(end),
+            )
+            .unwrap();
+
+        // The step's chord trails the end tangent by half a step's turn. Each
+        // check steps once, so both stay short of the curve end.
+        system.step();
+        for _ in 0..20 {
+            assert!(heading_error(&mut system, plain) < 2.5);
+            assert!(heading_error(&mut system, anchored) < 2.5);
+        }
+    }
+
+    // Lights after its second step; only lit fuses have a default lifetime.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    enum Fuse {
+        Lit,
+        Unlit,
+    }
+
+    impl ParticleTypeTrait for Fuse {
+        const STEP_HOOK: bool = true;
+
+        fn on_step<U>(self, particle: &mut ParticleMut<'_, Self, U>)
+        where
+            U: ParticlePayload,
+        {
+            if particle.counter() == 8 {
+                particle.set_velocity(Vec2::X);
+            }
+        }
+
+        fn default_lifetime(self) -> Option<u32> {
+            (self == Fuse::Lit).then_some(10)
+        }
+    }
+
+    #[test]
+    fn of_type_needs_a_default_lifetime() {
+        let spawn = ParticleSpawn::of_type(Fuse::Lit, Vec2::ONE, Vec2::ONE).unwrap();
+        assert_eq!(spawn.counter, 10);
+        assert!(ParticleSpawn::of_type(Fuse::Unlit, Vec2::ONE, Vec2::ONE).is_none());
+    }
+
+    #[test]
+    fn velocity_set_by_the_step_hook_is_integrated() {
+        let mut system = ParticleSystem::new();
+        system.set_interpolation(true);
+        let fuse = ParticleSpawn::of_type(Fuse::Lit, Vec2::ZERO, Vec2::ONE).unwrap();
+        let fixed = system.spawn_with_handle(fuse).unwrap();
+        let fading = system
+            .spawn_with_handle(fuse.with_alpha(0.5).with_alpha_velocity(0.1))
+            .unwrap();
+        let analytic = system
+            .spawn_with_handle(fuse.with_velocity(Vec2::ZERO).with_analytic())
+            .unwrap();
+        for _ in 0..3 {
+            system.step();
+        }
+
+        for handle in [fixed, fading, analytic] {
+            assert_eq!(system.get(handle).unwrap().pos, Vec2::X);
+        }
+        // Promotion keeps the other channels and the state to blend from.
+        assert!((system.get(fading).unwrap().alpha - 0.8).abs() < 1e-6);
+        let mut drawn = Vec::new();
+        system.for_each_particle_interpolated(0.5, |p| drawn.push(p.pos.x));
+        assert_eq!(drawn, [0.5; 3]);
+    }
+}
@@ -1,71 +1,224 @@
+use std::hash::Hash;
+
 use glam::Vec2;
 
+use super::affector::AffectorList;
+use super::analytic::{advance_closed_form, AnalyticLane};
+use super::anchor::{AnchorId, AnchorTable};
+use super::ballistic_lanes::BallisticLanes;
+use super::budget::{BudgetStats, ParticleBudget};
+use super::collision::CollisionSolver;
+use super::constraint::ConstraintList;
+use super::death::{DeathCause, DeathLog};
+use super::flocking::Flock;
+use super::handle::{HandleTable, ParticleLocation, ANALYTIC_LANE, SPLINE_LANE};
+use super::impulse::ActiveShockwave;
+use super::interpolation::PreviousState;
+#[cfg(feature = "parallel")]
+use super::parallel::ParallelConfig;
 use super::particle_model::{
-    ParticleCore, ParticleRenderData, ParticleSpawn, ParticleTypeTrait, SplineMotion,
-    SplineParticle, HAS_ACCELERATION, HAS_ALPHA_ACCELERATION, HAS_ALPHA_VELOCITY,
-    HAS_ROTATION_ACCELERATION, HAS_ROTATION_VELOCITY, HAS_SIZE_ACCELERATION, HAS_SIZE_VELOCITY,
-    HAS_SPLINE_ACCELERATION, HAS_SPLINE_VELOCITY, HAS_VELOCITY,
+    facing_rotation, Orientation, ParticleCore, ParticlePayload, ParticleRenderData, ParticleSpawn,
+    ParticleTypeTrait, SplineMotion, SplineParticle, HAS_ACCELERATION, HAS_ALPHA_ACCELERATION,
+    HAS_ALPHA_VELOCITY, HAS_ROTATION_ACCELERATION, HAS_ROTATION_VELOCITY, HAS_SIZE_ACCELERATION,
+    HAS_SIZE_VELOCITY, HAS_SPLINE_ACCELERATION, HAS_SPLINE_ANCHORS, HAS_SPLINE_ARC_LENGTH,
+    HAS_SPLINE_VELOCITY, HAS_VELOCITY,
 };
-
-pub struct ParticleSystem<T>
+use super::particle_types::{TypeSettings, TypeTable, NO_TYPE_SLOT};
+use super::particle_view::ParticleMut;
+use super::spatial::SpatialIndex;
+use super::spline_path::{SplinePath, SplinePathId};
+use super::time_scale::TimeControls;
+use super::trail::{TrailPool, TrailStyle, TrailView, NO_TRAIL};
+
+// The internal tick starts half-way round, so spawn ticks that `prewarm`
+// backdates on a fresh system stay below it instead of wrapping.
+pub(crate) const TICK_ORIGIN: u32 = 1 << 31;
+
+pub struct ParticleSystem<T, U = ()>
 where
     T: ParticleTypeTrait,
+    U: ParticlePayload,
 {
-    ballistic_particles: Vec<ParticleCore<T>>,
-    spline_particles: Vec<SplineParticle<T>>,
+    pub(crate) ballistic: BallisticLanes<T, U>,
+    pub(crate) analytic: AnalyticLane<T, U>,
+    pub(crate) spline_particles: Vec<SplineParticle<T, U>>,
+    // Room the spline lane grows to once it fills, from `reserve_budget`.
+    pub(crate) spline_reserved: usize,
+    pub(crate) spline_paths: Vec<SplinePath>,
+    pub(crate) anchors: AnchorTable,
+    pub(crate) types: TypeTable<T>,
+    pub(crate) trails: TrailPool,
+    // Starts at `TICK_ORIGIN`; see `tick()` for the public count.
+    pub(crate) tick: u32,
+    pub(crate) budget: Option<ParticleBudget>,
+    pub(crate) budget_stats: BudgetStats,
+    pub(crate) budgets_active: bool,
+    pub(crate) thin_rng: u32,
+    pub(crate) evict_scratch: Vec<(u32, u16, u32)>,
+    pub(crate) spawn_scratch: Vec<ParticleSpawn<T, U>>,
+    pub(crate) emit_scratch: Vec<ParticleSpawn<T, U>>,
+    pub(crate) interpolation: bool,
+    pub(crate) spline_previous: Vec<PreviousState>,
+    pub(crate) handles: HandleTable,
+    pub(crate) deaths: DeathLog<T, U>,
+    pub(crate) time: TimeControls,
+    pub(crate) time_scaled: bool,
+    pub(crate) affectors: AffectorList<T, U>,
+    pub(crate) spline_scratch: Vec<(Vec2, f32)>,
+    pub(crate) shockwaves: Vec<ActiveShockwave>,
+    pub(crate) flocks: Vec<Flock>,
+    pub(crate) constraints: ConstraintList,
+    pub(crate) collision: Option<CollisionSolver>,
+    pub(crate) spatial: Option<SpatialIndex>,
+    #[cfg(feature = "parallel")]
+    pub(crate) parallel_config: ParallelConfig,
+    #[cfg(feature = "parallel")]
+    pub(crate) parallel_alive: Vec<usize>,
 }
 
-impl<T> ParticleSystem<T>
+impl<T, U> ParticleSystem<T, U>
 where
     T: ParticleTypeTrait,
+    U: ParticlePayload,
 {
     pub fn new() -> Self {
         Self {
-            ballistic_particles: Vec::new(),
+            ballistic: BallisticLanes::new(),
+            analytic: AnalyticLane::new(),
             spline_particles: Vec::new(),
+            spline_reserved: 0,
+            spline_paths: Vec::new(),
+            anchors: AnchorTable::default(),
+            types: TypeTable::new(),
+            trails: TrailPool::default(),
+            tick: TICK_ORIGIN,
+            budget: None,
+            budget_stats: BudgetStats::default(),
+            budgets_active: false,
+            thin_rng: 0x9E37_79B9,
+            evict_scratch: Vec::new(),
+            spawn_scratch: Vec::new(),
+            emit_scratch: Vec::new(),
+            interpolation: false,
+            spline_previous: Vec::new(),
+            handles: HandleTable::default(),
+            deaths: DeathLog::new(),
+            time: TimeControls::default(),
+            time_scaled: false,
+            affectors: AffectorList::new(),
+            spline_scratch: Vec::new(),
+            shockwaves: Vec::new(),
+            flocks: Vec::new(),
+            constraints: ConstraintList::default(),
+            collision: None,
+            spatial: None,
+            #[cfg(feature = "parallel")]
+            parallel_config: ParallelConfig::default(),
+            #[cfg(feature = "parallel")]
+            parallel_alive: Vec::new(),
         }
     }
 
     pub fn len(&self) -> usize {
-        self.ballistic_particles.len() + self.spline_particles.len()
+        self.ballistic.len() + self.analytic.len() + self.spline_particles.len()
     }
 
     pub fn is_empty(&self) -> bool {
-        self.ballistic_particles.is_empty() && self.spline_particles.is_empty()
+        self.ballistic.is_empty() && self.analytic.is_empty() && self.spline_particles.is_empty()
     }
 
     pub fn clear(&mut self) {
-        self.ballistic_particles.clear();
+        self.ballistic.clear();
+        self.analytic.clear();
         self.spline_particles.clear();
+        self.spline_previous.clear();
+        self.deaths.dying.clear();
+        self.trails.clear();
+        self.types.reset_live();
+        self.handles.clear();
+        self.shockwaves.clear();
+        self.refresh_spatial_index();
     }
 
     pub fn reserve_particles(&mut self, additional: u32) {
-        self.ballistic_particles.reserve(additional as usize);
+        self.ballistic.reserve(additional as usize);
+    }
+
+    pub fn reserve_spline_particles(&mut self, additional: u32) {
+        self.spline_particles.reserve(additional as usize);
     }
 
+    #[deprecated(note = "use `reserve_particles` and `reserve_spline_particles`")]
     pub fn reserve_bundle<B>(&mut self, additional: u32) {
         let _ = std::marker::PhantomData::<B>;
         self.reserve_particles(additional);
+        self.reserve_spline_particles(additional);
     }
 
-    pub fn new_particle(&mut self, particle_type: T, counter: u32, pos: Vec2, size: Vec2) -> usize {
-        self.spawn(ParticleSpawn::new(particle_type, counter, pos, size))
+    pub fn add_spline_path(&mut self, path: SplinePath) -> SplinePathId {
+        debug_assert!(!path.is_empty(), "add_spline_path received empty path");
+        let id = SplinePathId(self.spline_paths.len() as u32);
+        self.spline_paths.push(path);
+        id
+    }
+
+    pub fn spline_path(&self, id: SplinePathId) -> &SplinePath {
+        &self.spline_paths[id.index()]
+    }
+
+    pub fn add_anchor(&mut self, pos: Vec2) -> AnchorId {
+        self.anchors.add(pos)
+    }
+
+    pub fn set_anchor(&mut self, id: AnchorId, pos: Vec2) {
+        self.anchors.set(id, pos);
+    }
+
+    pub fn anchor(&self, id: AnchorId) -> Vec2 {
+        self.anchors.get(id)
+    }
+
+    // Only particles spawned after the style is set get a trail.
+    pub fn set_trail_style(&mut self, particle_type: T, style: TrailStyle)
+    where
+        T: Hash + Eq,
+    {
+        self.type_settings_mut(particle_type).trail = Some(style);
     }
 
-    pub fn spawn(&mut self, spawn: ParticleSpawn<T>) -> usize {
+    pub fn clear_trail_style(&mut self, particle_type: T)
+    where
+        T: Hash + Eq,
+    {
+        self.type_settings_mut(particle_type).trail = None;
+    }
+
+    // Returns the live count before the spawn. It does not name the particle:
+    // indices shift as particles die, and under a budget the spawn may be
+    // rejected or evict others. Use `spawn_with_handle` to keep track of one.
+    pub fn spawn(&mut self, spawn: ParticleSpawn<T, U>) -> usize {
         let id = self.len();
-        self.push_spawn(spawn);
+        if self.budgets_active {
+            self.spawn_budgeted(std::iter::once(spawn));
+        } else {
+            self.push_spawn(spawn);
+        }
         id
     }
 
     pub fn spawn_batch<I>(&mut self, iter: I)
     where
-        I: IntoIterator<Item = ParticleSpawn<T>>,
+        I: IntoIterator<Item = ParticleSpawn<T, U>>,
     {
+        if self.budgets_active {
+            self.spawn_budgeted(iter);
+            return;
+        }
+
         let iter = iter.into_iter();
         let (lower, _) = iter.size_hint();
         if lower > 0 {
-            self.ballistic_particles.reserve(lower);
+            self.ballistic.reserve(lower);
         }
 
         for spawn in iter {
@@ -75,28 +228,38 @@ where
 
     pub fn spawn_ballistic_batch<I>(&mut self, iter: I)
     where
-        I: IntoIterator<Item = ParticleSpawn<T>>,
+        I: IntoIterator<Item = ParticleSpawn<T, U>>,
     {
+        if self.budgets_active {
+            self.spawn_budgeted(iter);
+            return;
+        }
+
         let iter = iter.into_iter();
         let (lower, _) = iter.size_hint();
         if lower > 0 {
-            self.ballistic_particles.reserve(lower);
+            self.ballistic.reserve(lower);
         }
 
         for spawn in iter {
             debug_assert!(
-                spawn.spline.is_none(),
+                !spawn.is_spline(),
                 "spawn_ballistic_batch received spline spawn"
             );
-            self.ballistic_particles
-                .push(ParticleCore::from_spawn(&spawn));
+            let core = self.new_core(&spawn);
+            self.push_ballistic(&spawn, core);
         }
     }
 
     pub fn spawn_spline_batch<I>(&mut self, iter: I)
     where
-        I: IntoIterator<Item = ParticleSpawn<T>>,
+        I: IntoIterator<Item = ParticleSpawn<T, U>>,
     {
+        if self.budgets_active {
+            self.spawn_budgeted(iter);
+            return;
+        }
+
         let iter = iter.into_iter();
         let (lower, _) = iter.size_hint();
         if lower > 0 {
@@ -105,19 +268,24 @@ where
 
         for spawn in iter {
             debug_assert!(
-                spawn.spline.is_some(),
+                spawn.is_spline(),
                 "spawn_spline_batch received non-spline spawn"
             );
-            self.spline_particles.push(SplineParticle {
-                core: ParticleCore::from_spawn(&spawn),
+            let core = self.new_core(&spawn);
+            self.push_spline(SplineParticle {
+                core,
                 spline: SplineMotion::from_spawn(&spawn),
             });
         }
     }
 
-    pub fn for_each_particle(&self, mut f: impl FnMut(ParticleRenderData<T>)) {
-        for p in &self.ballistic_particles {
-            f(p.into());
+    pub fn for_each_particle(&self, mut f: impl FnMut(ParticleRenderData<T, U>)) {
+        for (hot, cold) in self.ballistic.iter() {
+            f((hot, cold).into());
+        }
+
+        for core in self.analytic.iter_at(self.tick) {
+            f((&core).into());
         }
 
         for p in &self.spline_particles {
@@ -125,51 +293,323 @@ where
         }
     }
 
-    pub fn step(&mut self) {
-        let mut i = 0;
-        while i < self.ballistic_particles.len() {
-            let particle = &mut self.ballistic_particles[i];
-            if particle.counter == 0 {
-                self.ballistic_particles.swap_remove(i);
-                continue;
+    pub fn for_each_trail(&self, mut f: impl FnMut(ParticleRenderData<T, U>, TrailView<'_>)) {
+        for (hot, cold) in self.ballistic.iter() {
+            if cold.trail != NO_TRAIL {
+                f((hot, cold).into(), self.trails.view(cold.trail, hot.pos));
             }
+        }
 
-            particle.counter -= 1;
-            step_core_particle(particle);
-            i += 1;
+        for p in &self.spline_particles {
+            if p.core.trail != NO_TRAIL {
+                f((&p.core).into(), self.trails.view(p.core.trail, p.core.pos));
+            }
+        }
+    }
+
+    pub fn step(&mut self) {
+        self.step_particles();
+        self.refresh_spatial_index();
+    }
+
+    pub(crate) fn step_particles(&mut self) {
+        self.tick = self.tick.wrapping_add(1);
+        self.deaths.dying.clear();
+        self.analytic.retire_expired(
+            self.tick,
+            &mut self.trails,
+            &mut self.types,
+            &mut self.handles,
+            &mut self.deaths,
+        );
+        if !self.shockwaves.is_empty() {
+            self.run_shockwaves();
+        }
+        if !self.flocks.is_empty() {
+            self.run_flocking();
+        }
+        self.integrate();
+        // `on_step` may give velocity to particles in sub-lanes that never
+        // integrate it.
+        if T::STEP_HOOK {
+            self.ballistic.promote_moving(&mut self.handles);
+        }
+        if self.constraints.is_active() {
+            self.run_constraints();
+        }
+        if self.collision.is_some() {
+            self.run_collision();
+        }
+    }
+
+    fn integrate(&mut self) {
+        #[cfg(feature = "parallel")]
+        if self.step_parallel() {
+            return;
+        }
+
+        if self.affectors.is_active() {
+            self.step_affected();
+            return;
+        }
+
+        if self.time_scaled {
+            self.step_time_scaled();
+            return;
         }
 
+        self.ballistic.step(
+            &mut self.trails,
+            &mut self.types,
+            &mut self.handles,
+            &mut self.deaths,
+        );
+        self.step_spline_lane();
+    }
+
+    // Time controls, affectors, flocking, constraints and collision step every
+    // particle individually, which the analytic lane cannot do.
+    #[inline(always)]
+    pub(crate) fn stepwise(&self) -> bool {
+        self.time_scaled
+            || self.affectors.is_active()
+            || !self.flocks.is_empty()
+            || self.constraints.is_active()
+            || self.collision.is_some()
+    }
+
+    pub(crate) fn step_spline_lane(&mut self) {
         let mut i = 0;
         while i < self.spline_particles.len() {
             let particle = &mut self.spline_particles[i];
             if particle.core.counter == 0 {
-                self.spline_particles.swap_remove(i);
+                self.remove_particle(ParticleLocation::new(SPLINE_LANE, i), DeathCause::Expired);
                 continue;
             }
 
-            particle.core.counter -= 1;
-            step_core_particle(&mut particle.core);
-            step_spline_motion(&mut particle.core, &mut particle.spline);
+            if self.interpolation {
+                self.spline_previous[i] = PreviousState::from(&particle.core);
+            }
+            step_spline_particle(particle, &self.spline_paths, &self.anchors);
+            if particle.core.trail != NO_TRAIL {
+                self.trails.record(particle.core.trail, particle.core.pos);
+            }
             i += 1;
         }
     }
 
-    fn push_spawn(&mut self, spawn: ParticleSpawn<T>) {
-        if spawn.spline.is_some() {
-            self.spline_particles.push(SplineParticle {
-                core: ParticleCore::from_spawn(&spawn),
+    pub(crate) fn push_spawn(&mut self, spawn: ParticleSpawn<T, U>) -> ParticleLocation {
+        let core = self.new_core(&spawn);
+        if spawn.is_spline() {
+            self.push_spline(SplineParticle {
+                core,
                 spline: SplineMotion::from_spawn(&spawn),
-            });
+            })
+        } else {
+            self.push_ballistic(&spawn, core)
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn push_spline(&mut self, particle: SplineParticle<T, U>) -> ParticleLocation {
+        if self.spline_particles.len() == self.spline_particles.capacity()
+            || (self.interpolation && self.spline_previous.len() == self.spline_previous.capacity())
+        {
+            self.grow_spline(self.spline_reserved);
+        }
+        if self.interpolation {
+            self.spline_previous
+                .push(PreviousState::from(&particle.core));
+        }
+        self.spline_particles.push(particle);
+        ParticleLocation::new(SPLINE_LANE, self.spline_particles.len() - 1)
+    }
+
+    // Sizes the spline lane for `count` particles, or at least one more.
+    #[cold]
+    pub(crate) fn grow_spline(&mut self, count: usize) {
+        let additional = count.saturating_sub(self.spline_particles.len()).max(1);
+        self.spline_particles.reserve(additional);
+        if self.interpolation {
+            self.spline_previous.reserve(additional);
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn remove_spline(&mut self, index: usize) -> SplineParticle<T, U> {
+        if self.interpolation {
+            self.spline_previous.swap_remove(index);
+        }
+        let removed = self.spline_particles.swap_remove(index);
+        if let Some(moved) = self.spline_particles.get(index) {
+            self.handles
+                .relocate(moved.core.handle, ParticleLocation::new(SPLINE_LANE, index));
+        }
+        removed
+    }
+
+    // Removes one particle from any lane, recording its death and releasing
+    // its trail, type count and handle.
+    pub(crate) fn remove_particle(&mut self, location: ParticleLocation, cause: DeathCause) {
+        let index = location.index as usize;
+        let recording = self.deaths.is_recording();
+        let (particle, trail, type_slot, handle) = match location.lane {
+            SPLINE_LANE => {
+                let core = self.remove_spline(index).core;
+                let particle = recording.then(|| (&core).into());
+                (particle, core.trail, core.type_slot, core.handle)
+            }
+            ANALYTIC_LANE => {
+                let particle = recording.then(|| {
+                    let mut core = self.analytic.spawns[index];
+                    let age = self.analytic.timing[index]
+                        .age(self.tick)
+                        .unwrap_or(core.counter);
+                    advance_closed_form(&mut core, age);
+                    (&core).into()
+                });
+                let core = self.analytic.swap_remove(index, &mut self.handles);
+                (particle, core.trail, core.type_slot, core.handle)
+            }
+            lane => {
+                let lane = &mut self.ballistic.lanes[lane as usize];
+                let particle = recording.then(|| (&lane.hot[index], &lane.cold[index]).into());
+                let cold = lane.swap_remove(index, &mut self.handles);
+                (particle, cold.trail, cold.type_slot, cold.handle)
+            }
+        };
+        if let Some(particle) = particle {
+            self.deaths
+                .record(particle, self.handles.handle(handle), cause);
+        }
+        retire_particle(
+            trail,
+            type_slot,
+            handle,
+            &mut self.trails,
+            &mut self.types,
+            &mut self.handles,
+        );
+    }
+
+    pub(crate) fn sync_spline_previous(&mut self) {
+        self.spline_previous.clear();
+        self.spline_previous.extend(
+            self.spline_particles
+                .iter()
+                .map(|particle| PreviousState::from(&particle.core)),
+        );
+    }
+
+    #[inline(always)]
+    fn push_ballistic(
+        &mut self,
+        spawn: &ParticleSpawn<T, U>,
+        core: ParticleCore<T, U>,
+    ) -> ParticleLocation {
+        let core = if spawn.analytic && !self.stepwise() {
+            match self.analytic.try_push(core) {
+                Ok(()) => {
+                    return ParticleLocation::new(ANALYTIC_LANE, self.analytic.len() - 1);
+                }
+                Err(core) => core,
+            }
         } else {
-            self.ballistic_particles
-                .push(ParticleCore::from_spawn(&spawn));
+            core
+        };
+        self.ballistic.push(core)
+    }
+
+    fn spawn_budgeted<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = ParticleSpawn<T, U>>,
+    {
+        let mut spawns = std::mem::take(&mut self.spawn_scratch);
+        spawns.clear();
+        spawns.extend(iter);
+        self.admit_spawns(&mut spawns);
+        for spawn in spawns.drain(..) {
+            self.push_spawn(spawn);
         }
+        self.spawn_scratch = spawns;
+    }
+
+    #[inline(always)]
+    pub(crate) fn new_core(&mut self, spawn: &ParticleSpawn<T, U>) -> ParticleCore<T, U> {
+        let mut core = ParticleCore::from_spawn(spawn);
+        core.spawn_tick = self.tick;
+        spawn
+            .particle_type
+            .on_spawn(&mut ParticleMut::core(&mut core));
+        if !self.types.is_empty() {
+            let slot = self.types.slot(spawn.particle_type);
+            if slot != NO_TYPE_SLOT {
+                core.type_slot = slot;
+                let settings = &mut self.types.settings[slot as usize];
+                settings.live += 1;
+                if let Some(style) = settings.trail {
+                    core.trail = self.trails.acquire(style, core.pos);
+                }
+            }
+        }
+        core
+    }
+
+    // Registers `particle_type` on first use and gives the new slot to its live
+    // particles, so they count towards its budget and take its time scale.
+    pub(crate) fn type_slot_mut(&mut self, particle_type: T) -> u16
+    where
+        T: Hash + Eq,
+    {
+        let mut slot = self.types.slot(particle_type);
+        if slot == NO_TYPE_SLOT {
+            slot = self.types.register(particle_type);
+            let mut live = 0;
+            let mut adopt = |core_type: T, type_slot: &mut u16| {
+                if core_type == particle_type {
+                    *type_slot = slot;
+                    live += 1;
+                }
+            };
+            for lane in &mut self.ballistic.lanes {
+                for cold in &mut lane.cold {
+                    adopt(cold.particle_type, &mut cold.type_slot);
+                }
+            }
+            for core in &mut self.analytic.spawns {
+                adopt(core.particle_type, &mut core.type_slot);
+            }
+            for particle in &mut self.spline_particles {
+                adopt(particle.core.particle_type, &mut particle.core.type_slot);
+            }
+            self.types.settings[slot as usize].live = live;
+        }
+        slot
+    }
+
+    pub(crate) fn type_settings_mut(&mut self, particle_type: T) -> &mut TypeSettings
+    where
+        T: Hash + Eq,
+    {
+        let slot = self.type_slot_mut(particle_type);
+        &mut self.types.settings[slot as usize]
     }
 }
 
-impl<T> Default for ParticleSystem<T>
+// Payload-carrying systems spawn through `spawn` with `with_payload`.
+impl<T> ParticleSystem<T>
 where
     T: ParticleTypeTrait,
+{
+    pub fn new_particle(&mut self, particle_type: T, counter: u32, pos: Vec2, size: Vec2) -> usize {
+        self.spawn(ParticleSpawn::new(particle_type, counter, pos, size))
+    }
+}
+
+impl<T, U> Default for ParticleSystem<T, U>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
 {
     fn default() -> Self {
         Self::new()
@@ -177,9 +617,61 @@ where
 }
 
 #[inline(always)]
-fn step_core_particle<T>(particle: &mut ParticleCore<T>)
+pub(crate) fn retire_particle<T>(
+    trail: u32,
+    type_slot: u16,
+    handle: u32,
+    trails: &mut TrailPool,
+    types: &mut TypeTable<T>,
+    handles: &mut HandleTable,
+) where
+    T: ParticleTypeTrait,
+{
+    trails.release(trail);
+    types.on_removed(type_slot);
+    handles.release(handle);
+}
+
+// Callers handle death and trail recording; this only advances a live particle.
+#[inline(always)]
+pub(crate) fn step_spline_particle<T, U>(
+    particle: &mut SplineParticle<T, U>,
+    paths: &[SplinePath],
+    anchors: &AnchorTable,
+) where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    particle.core.counter -= 1;
+    let previous_pos = particle.core.pos;
+    step_core_particle(&mut particle.core);
+    let (parameter, progress) = step_spline_motion(
+        &mut particle.core,
+        &mut particle.spline,
+        paths,
+        anchors,
+        1.0,
+    );
+    if particle.core.oriented {
+        let motion = particle.core.pos - previous_pos;
+        let tangent = if particle.core.orientation == Orientation::AlignToSplineTangent {
+            particle.spline.tangent(parameter, progress, paths, anchors)
+        } else {
+            motion
+        };
+        orient_particle(&mut particle.core, motion, tangent);
+    }
+    if T::STEP_HOOK {
+        let particle_type = particle.core.particle_type;
+        particle_type.on_step(&mut ParticleMut::spline(particle));
+    }
+}
+
+#[inline(always)]
+fn step_core_particle<T, U>(particle: &mut ParticleCore<T, U>)
 where
     T: ParticleTypeTrait,
+    U: ParticlePayload,
 {
     const FLAGS_LINEAR: u16 = HAS_VELOCITY | HAS_ACCELERATION;
     const FLAGS_ALPHA_ONLY: u16 = HAS_ALPHA_VELOCITY;
@@ -227,61 +719,121 @@ where
             particle.alpha_velocity += particle.alpha_acceleration;
             particle.alpha = (particle.alpha + particle.alpha_velocity).clamp(0.0, 1.0);
         }
-        flags => step_core_particle_generic(particle, flags),
+        flags => step_core_particle_generic(particle, flags, 1.0),
     }
 }
 
+// `scale` is the time scale; the unscaled path passes a literal `1.0`, which
+// folds away once inlined.
 #[inline(always)]
-fn step_core_particle_generic<T>(particle: &mut ParticleCore<T>, flags: u16)
-where
+pub(crate) fn step_core_particle_generic<T, U>(
+    particle: &mut ParticleCore<T, U>,
+    flags: u16,
+    scale: f32,
+) where
     T: ParticleTypeTrait,
+    U: ParticlePayload,
 {
     if (flags & HAS_VELOCITY) != 0 {
         if (flags & HAS_ACCELERATION) != 0 {
-            particle.velocity += particle.acceleration;
+            particle.velocity += particle.acceleration * scale;
         }
-        particle.pos += particle.velocity;
+        particle.pos += particle.velocity * scale;
     }
 
     if (flags & HAS_SIZE_VELOCITY) != 0 {
         if (flags & HAS_SIZE_ACCELERATION) != 0 {
-            particle.size_velocity += particle.size_acceleration;
+            particle.size_velocity += particle.size_acceleration * scale;
         }
-        particle.size += particle.size_velocity;
+        particle.size += particle.size_velocity * scale;
         particle.size = particle.size.max(Vec2::ZERO);
     }
 
     if (flags & HAS_ROTATION_VELOCITY) != 0 {
         if (flags & HAS_ROTATION_ACCELERATION) != 0 {
-            particle.rotation_velocity += particle.rotation_acceleration;
+            particle.rotation_velocity += particle.rotation_acceleration * scale;
         }
-        particle.rotation += particle.rotation_velocity;
+        particle.rotation += particle.rotation_velocity * scale;
     }
 
     if (flags & HAS_ALPHA_VELOCITY) != 0 {
         if (flags & HAS_ALPHA_ACCELERATION) != 0 {
-            particle.alpha_velocity += particle.alpha_acceleration;
+            particle.alpha_velocity += particle.alpha_acceleration * scale;
         }
-        particle.alpha = (particle.alpha + particle.alpha_velocity).clamp(0.0, 1.0);
+        particle.alpha = (particle.alpha + particle.alpha_velocity * scale).clamp(0.0, 1.0);
     }
 }
 
+// Returns the curve parameter the particle is at and its progress along the
+// curve, which differ under arc-length progress.
 #[inline(always)]
-fn step_spline_motion<T>(particle: &mut ParticleCore<T>, spline: &mut SplineMotion)
+pub(crate) fn step_spline_motion<T, U>(
+    particle: &mut ParticleCore<T, U>,
+    spline: &mut SplineMotion,
+    paths: &[SplinePath],
+    anchors: &AnchorTable,
+    scale: f32,
+) -> (f32, f32)
 where
     T: ParticleTypeTrait,
+    U: ParticlePayload,
 {
-    if spline.has(HAS_SPLINE_VELOCITY) {
-        if spline.has(HAS_SPLINE_ACCELERATION) {
-            spline.velocity += spline.acceleration;
+    let (mut new_pos, progress, parameter) = if spline.has(HAS_SPLINE_ARC_LENGTH) {
+        let path = &paths[spline
+            .path
+            .expect("internal error: arc-length spline requires a path")
+            .index()];
+        let length = path.length();
+        if spline.has(HAS_SPLINE_VELOCITY) {
+            if spline.has(HAS_SPLINE_ACCELERATION) {
+                spline.velocity += spline.acceleration * scale;
+            }
+            spline.t = (spline.t + spline.velocity * scale).clamp(0.0, length);
+        }
+        let progress = if length > 0.0 { spline.t / length } else { 0.0 };
+        let parameter = path.parameter_at_distance(spline.t);
+        (path.point(parameter), progress, parameter)
+    } else {
+        if spline.has(HAS_SPLINE_VELOCITY) {
+            if spline.has(HAS_SPLINE_ACCELERATION) {
+                spline.velocity += spline.acceleration * scale;
+            }
+            spline.t = (spline.t + spline.velocity * scale).clamp(0.0, 1.0);
         }
-        spline.t = (spline.t + spline.velocity).clamp(0.0, 1.0);
+        (spline.evaluate(spline.t, paths), spline.t, spline.t)
+    };
+
+    if spline.has(HAS_SPLINE_ANCHORS) {
+        new_pos += spline.anchor_offset(progress, anchors);
     }
 
-    let new_pos = spline.evaluate_bezier(spline.t);
-    if spline.strength == 1.0 {
+    // Strength is the fraction of the gap closed per step, so it compounds
+    // under scaled time.
+    let strength = if scale == 1.0 {
+        spline.strength
+    } else {
+        1.0 - (1.0 - spline.strength).powf(scale)
+    };
+    if strength == 1.0 {
         particle.pos = new_pos;
     } else {
-        particle.pos += (new_pos - particle.pos) * spline.strength;
+        particle.pos += (new_pos - particle.pos) * strength;
+    }
+
+    (parameter, progress)
+}
+
+#[inline(always)]
+pub(crate) fn orient_particle<T, U>(particle: &mut ParticleCore<T, U>, motion: Vec2, tangent: Vec2)
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    if let Some(rotation) = facing_rotation(particle.orientation, particle.pos, motion, tangent) {
+        particle.rotation = rotation;
+    }
+
+    if particle.stretch != 0.0 {
+        particle.stretch_scale = 1.0 + motion.length() * particle.stretch;
     }
 }
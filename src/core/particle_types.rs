@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use super::budget::{BudgetStats, ParticleBudget};
+use super::particle_model::ParticleTypeTrait;
+use super::time_scale::TimeScale;
+use super::trail::TrailStyle;
+
+pub(crate) const NO_TYPE_SLOT: u16 = u16::MAX;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct TypeSettings {
+    pub(crate) trail: Option<TrailStyle>,
+    pub(crate) budget: Option<ParticleBudget>,
+    pub(crate) stats: BudgetStats,
+    pub(crate) live: usize,
+    pub(crate) time: TimeScale,
+    pub(crate) collides: bool,
+}
+
+// Per-type configuration lives in dense slots. Every particle of a configured
+// type stores its slot, so step-time lookups are a plain index. Type tags only
+// need `Hash + Eq` to be configured; spawns have no such bound, so the table
+// keeps the map lookup from the first registration.
+#[derive(Clone, Debug)]
+pub(crate) struct TypeTable<T>
+where
+    T: ParticleTypeTrait,
+{
+    slots: HashMap<T, u16>,
+    lookup: fn(&HashMap<T, u16>, &T) -> Option<u16>,
+    pub(crate) settings: Vec<TypeSettings>,
+}
+
+impl<T> TypeTable<T>
+where
+    T: ParticleTypeTrait,
+{
+    pub(crate) fn new() -> Self {
+        Self {
+            slots: HashMap::new(),
+            lookup: |_, _| None,
+            settings: Vec::new(),
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.settings.is_empty()
+    }
+
+    #[inline(always)]
+    pub(crate) fn slot(&self, particle_type: T) -> u16 {
+        (self.lookup)(&self.slots, &particle_type).unwrap_or(NO_TYPE_SLOT)
+    }
+
+    // Callers go through `ParticleSystem::type_settings_mut`, which hands the
+    // new slot to live particles of the type.
+    pub(crate) fn register(&mut self, particle_type: T) -> u16
+    where
+        T: Hash + Eq,
+    {
+        let slot = self.settings.len() as u16;
+        self.lookup = |slots, particle_type| slots.get(particle_type).copied();
+        self.slots.insert(particle_type, slot);
+        self.settings.push(TypeSettings::default());
+        slot
+    }
+
+    pub(crate) fn has_trails(&self) -> bool {
+        self.settings
+            .iter()
+            .any(|settings| settings.trail.is_some())
+    }
+
+    pub(crate) fn get(&self, slot: u16) -> Option<&TypeSettings> {
+        self.settings.get(slot as usize)
+    }
+
+    #[inline(always)]
+    pub(crate) fn time_scale(&self, slot: u16) -> f32 {
+        self.get(slot)
+            .map_or(1.0, |settings| settings.time.effective())
+    }
+
+    #[inline(always)]
+    pub(crate) fn collides(&self, slot: u16) -> bool {
+        self.get(slot).is_some_and(|settings| settings.collides)
+    }
+
+    #[inline(always)]
+    pub(crate) fn on_removed(&mut self, slot: u16) {
+        if let Some(settings) = self.settings.get_mut(slot as usize) {
+            settings.live -= 1;
+        }
+    }
+
+    pub(crate) fn reset_live(&mut self) {
+        for settings in &mut self.settings {
+            settings.live = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TypeTable, NO_TYPE_SLOT};
+    use crate::core::ParticleTypeTrait;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    struct Tag(u32);
+
+    impl ParticleTypeTrait for Tag {}
+
+    #[test]
+    fn registered_types_get_dense_slots() {
+        let mut table = TypeTable::new();
+        assert!(table.is_empty());
+        assert_eq!(table.slot(Tag(7)), NO_TYPE_SLOT);
+
+        for i in 0..100 {
+            assert_eq!(table.register(Tag(i * 3)), i as u16);
+        }
+        for i in 0..100 {
+            assert_eq!(table.slot(Tag(i * 3)), i as u16);
+            assert_eq!(table.slot(Tag(i * 3 + 1)), NO_TYPE_SLOT);
+        }
+        assert_eq!(table.settings.len(), 100);
+    }
+}
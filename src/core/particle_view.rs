@@ -0,0 +1,305 @@
+use glam::Vec2;
+
+use super::particle_model::{
+    BallisticCold, BallisticHot, ParticleCore, ParticlePayload, ParticleRenderData,
+    ParticleTypeTrait, SplineMotion, SplineParticle, HAS_VELOCITY,
+};
+
+enum ViewState<'a, T, U>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    Ballistic {
+        hot: &'a mut BallisticHot,
+        cold: &'a mut BallisticCold<T, U>,
+    },
+    Core(&'a mut ParticleCore<T, U>),
+}
+
+// Mutable access to one live particle, whichever lane it sits in. Position,
+// size, rotation and alpha can always be written. Velocity is only integrated
+// for particles spawned with one; writing it while spawning, from
+// `for_each_particle_mut`, an affector or `on_step` enables it for the rest.
+// Size, rotation and alpha rates are fixed at spawn and have no setters, since
+// enabling them later would mean rescanning every lane each step.
+pub struct ParticleMut<'a, T, U = ()>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    state: ViewState<'a, T, U>,
+    spline: Option<&'a mut SplineMotion>,
+}
+
+impl<'a, T, U> ParticleMut<'a, T, U>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    #[inline(always)]
+    pub(crate) fn ballistic(hot: &'a mut BallisticHot, cold: &'a mut BallisticCold<T, U>) -> Self {
+        Self {
+            state: ViewState::Ballistic { hot, cold },
+            spline: None,
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn core(core: &'a mut ParticleCore<T, U>) -> Self {
+        Self {
+            state: ViewState::Core(core),
+            spline: None,
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn spline(particle: &'a mut SplineParticle<T, U>) -> Self {
+        Self {
+            state: ViewState::Core(&mut particle.core),
+            spline: Some(&mut particle.spline),
+        }
+    }
+
+    #[inline(always)]
+    pub fn particle_type(&self) -> T {
+        match &self.state {
+            ViewState::Ballistic { cold, .. } => cold.particle_type,
+            ViewState::Core(core) => core.particle_type,
+        }
+    }
+
+    #[inline(always)]
+    pub fn draw_layer(&self) -> u32 {
+        match &self.state {
+            ViewState::Ballistic { cold, .. } => cold.draw_layer,
+            ViewState::Core(core) => core.draw_layer,
+        }
+    }
+
+    // Steps left to live.
+    #[inline(always)]
+    pub fn counter(&self) -> u32 {
+        match &self.state {
+            ViewState::Ballistic { hot, .. } => hot.counter,
+            ViewState::Core(core) => core.counter,
+        }
+    }
+
+    // A counter of `0` removes the particle on the next step.
+    #[inline(always)]
+    pub fn set_counter(&mut self, counter: u32) {
+        match &mut self.state {
+            ViewState::Ballistic { hot, .. } => hot.counter = counter,
+            ViewState::Core(core) => core.counter = counter,
+        }
+    }
+
+    #[inline(always)]
+    pub fn pos(&self) -> Vec2 {
+        match &self.state {
+            ViewState::Ballistic { hot, .. } => hot.pos,
+            ViewState::Core(core) => core.pos,
+        }
+    }
+
+    #[inline(always)]
+    pub fn set_pos(&mut self, pos: Vec2) {
+        match &mut self.state {
+            ViewState::Ballistic { hot, .. } => hot.pos = pos,
+            ViewState::Core(core) => core.pos = pos,
+        }
+    }
+
+    #[inline(always)]
+    pub fn velocity(&self) -> Vec2 {
+        match &self.state {
+            ViewState::Ballistic { hot, .. } => hot.velocity,
+            ViewState::Core(core) => core.velocity,
+        }
+    }
+
+    #[inline(always)]
+    pub fn set_velocity(&mut self, velocity: Vec2) {
+        match &mut self.state {
+            ViewState::Ballistic { hot, .. } => hot.velocity = velocity,
+            ViewState::Core(core) => {
+                core.velocity = velocity;
+                core.flags |= HAS_VELOCITY;
+            }
+        }
+    }
+
+    // Unstretched size.
+    #[inline(always)]
+    pub fn size(&self) -> Vec2 {
+        match &self.state {
+            ViewState::Ballistic { hot, .. } => hot.size,
+            ViewState::Core(core) => core.size,
+        }
+    }
+
+    #[inline(always)]
+    pub fn set_size(&mut self, size: Vec2) {
+        let size = size.max(Vec2::ZERO);
+        match &mut self.state {
+            ViewState::Ballistic { hot, .. } => hot.size = size,
+            ViewState::Core(core) => core.size = size,
+        }
+    }
+
+    #[inline(always)]
+    pub fn rotation(&self) -> f32 {
+        match &self.state {
+            ViewState::Ballistic { hot, .. } => hot.rotation,
+            ViewState::Core(core) => core.rotation,
+        }
+    }
+
+    #[inline(always)]
+    pub fn set_rotation(&mut self, rotation: f32) {
+        match &mut self.state {
+            ViewState::Ballistic { hot, .. } => hot.rotation = rotation,
+            ViewState::Core(core) => core.rotation = rotation,
+        }
+    }
+
+    #[inline(always)]
+    pub fn alpha(&self) -> f32 {
+        match &self.state {
+            ViewState::Ballistic { hot, .. } => hot.alpha,
+            ViewState::Core(core) => core.alpha,
+        }
+    }
+
+    #[inline(always)]
+    pub fn set_alpha(&mut self, alpha: f32) {
+        let alpha = alpha.clamp(0.0, 1.0);
+        match &mut self.state {
+            ViewState::Ballistic { hot, .. } => hot.alpha = alpha,
+            ViewState::Core(core) => core.alpha = alpha,
+        }
+    }
+
+    // Fraction of the gap to its curve a spline particle closes each step, or
+    // `None` for particles not on a spline.
+    #[inline(always)]
+    pub fn spline_strength(&self) -> Option<f32> {
+        self.spline.as_ref().map(|spline| spline.strength)
+    }
+
+    // Ignored for particles not on a spline.
+    #[inline(always)]
+    pub fn set_spline_strength(&mut self, strength: f32) {
+        if let Some(spline) = &mut self.spline {
+            spline.strength = strength.clamp(0.0, 1.0);
+        }
+    }
+
+    #[inline(always)]
+    pub fn payload(&self) -> U {
+        match &self.state {
+            ViewState::Ballistic { cold, .. } => cold.payload,
+            ViewState::Core(core) => core.payload,
+        }
+    }
+
+    #[inline(always)]
+    pub fn set_payload(&mut self, payload: U) {
+        match &mut self.state {
+            ViewState::Ballistic { cold, .. } => cold.payload = payload,
+            ViewState::Core(core) => core.payload = payload,
+        }
+    }
+
+    pub fn render_data(&self) -> ParticleRenderData<T, U> {
+        match &self.state {
+            ViewState::Ballistic { hot, cold } => (&**hot, &**cold).into(),
+            ViewState::Core(core) => (&**core).into(),
+        }
+    }
+}
+
+enum BatchLane<'a, T, U>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    Ballistic {
+        hot: &'a mut [BallisticHot],
+        cold: &'a mut [BallisticCold<T, U>],
+    },
+    Spline(&'a mut [SplineParticle<T, U>]),
+}
+
+// A run of live particles from one lane, handed to affectors. Indices are
+// only stable for the duration of the call.
+pub struct ParticleBatch<'a, T, U = ()>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    lane: BatchLane<'a, T, U>,
+}
+
+impl<'a, T, U> ParticleBatch<'a, T, U>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    pub(crate) fn ballistic(
+        hot: &'a mut [BallisticHot],
+        cold: &'a mut [BallisticCold<T, U>],
+    ) -> Self {
+        Self {
+            lane: BatchLane::Ballistic { hot, cold },
+        }
+    }
+
+    pub(crate) fn spline(particles: &'a mut [SplineParticle<T, U>]) -> Self {
+        Self {
+            lane: BatchLane::Spline(particles),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.lane {
+            BatchLane::Ballistic { hot, .. } => hot.len(),
+            BatchLane::Spline(particles) => particles.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Whether these particles follow a spline.
+    pub fn is_spline(&self) -> bool {
+        matches!(self.lane, BatchLane::Spline(_))
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> ParticleMut<'_, T, U> {
+        match &mut self.lane {
+            BatchLane::Ballistic { hot, cold } => {
+                ParticleMut::ballistic(&mut hot[index], &mut cold[index])
+            }
+            BatchLane::Spline(particles) => ParticleMut::spline(&mut particles[index]),
+        }
+    }
+
+    #[inline(always)]
+    pub fn for_each(&mut self, mut f: impl FnMut(&mut ParticleMut<'_, T, U>)) {
+        match &mut self.lane {
+            BatchLane::Ballistic { hot, cold } => {
+                for (hot, cold) in hot.iter_mut().zip(cold.iter_mut()) {
+                    f(&mut ParticleMut::ballistic(hot, cold));
+                }
+            }
+            BatchLane::Spline(particles) => {
+                for particle in particles.iter_mut() {
+                    f(&mut ParticleMut::spline(particle));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use glam::Vec2;
+
+use super::death::DeathCause;
+use super::handle::{ParticleLocation, SPLINE_LANE};
+use super::particle_model::{ParticlePayload, ParticleRenderData, ParticleTypeTrait};
+use super::particle_system::ParticleSystem;
+use super::region::Rect;
+
+impl<T, U> ParticleSystem<T, U>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    // Keeps only the particles `keep` accepts, removing the rest with the
+    // same swap-and-compact pass `step` uses. Removed particles are reported
+    // as `DeathCause::Killed`. Returns how many were removed.
+    pub fn retain(&mut self, mut keep: impl FnMut(ParticleRenderData<T, U>) -> bool) -> usize {
+        let mut removed = self.ballistic.retain(
+            &mut keep,
+            &mut self.trails,
+            &mut self.types,
+            &mut self.handles,
+            &mut self.deaths,
+        );
+        removed += self.analytic.retain(
+            self.tick,
+            &mut keep,
+            &mut self.trails,
+            &mut self.types,
+            &mut self.handles,
+            &mut self.deaths,
+        );
+
+        let mut i = 0;
+        while i < self.spline_particles.len() {
+            if !keep((&self.spline_particles[i].core).into()) {
+                self.remove_particle(ParticleLocation::new(SPLINE_LANE, i), DeathCause::Killed);
+                removed += 1;
+                continue;
+            }
+            i += 1;
+        }
+        removed
+    }
+
+    pub fn kill_by_type(&mut self, particle_type: T) -> usize
+    where
+        T: PartialEq,
+    {
+        self.retain(|particle| particle.particle_type != particle_type)
+    }
+
+    pub fn kill_layer(&mut self, draw_layer: u32) -> usize {
+        self.retain(|particle| particle.draw_layer != draw_layer)
+    }
+
+    // Kills particles whose position lies in `rect`.
+    pub fn kill_in_rect(&mut self, rect: Rect) -> usize {
+        self.retain(|particle| !rect.contains(particle.pos))
+    }
+
+    // Kills particles whose position lies within `radius` of `center`.
+    pub fn kill_in_circle(&mut self, center: Vec2, radius: f32) -> usize {
+        let radius_sq = radius * radius;
+        self.retain(|particle| particle.pos.distance_squared(center) > radius_sq)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{ParticleHandle, ParticleSpawn, SplineState};
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    enum Kind {
+        Ember,
+        Ash,
+    }
+    impl ParticleTypeTrait for Kind {}
+
+    fn at(kind: Kind, pos: Vec2) -> ParticleSpawn<Kind> {
+        ParticleSpawn::new(kind, 10, pos, Vec2::ONE)
+    }
+
+    // One particle per lane at `pos`: static, moving, analytic and spline.
+    fn every_lane(kind: Kind, pos: Vec2) -> [ParticleSpawn<Kind>; 4] {
+        [
+            at(kind, pos),
+            at(kind, pos).with_velocity(Vec2::ZERO),
+            at(kind, pos).with_velocity(Vec2::ZERO).with_analytic(),
+            at(kind, pos).with_spline(SplineState {
+                t: 0.0,
+                strength: 0.0,
+                point_1: pos,
+                point_2: pos,
+                point_3: pos,
+            }),
+        ]
+    }
+
+    fn spawn_all(
+        system: &mut ParticleSystem<Kind>,
+        spawns: impl IntoIterator<Item = ParticleSpawn<Kind>>,
+    ) -> Vec<ParticleHandle> {
+        spawns
+            .into_iter()
+            .map(|spawn| system.spawn_with_handle(spawn).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn retain_sees_current_state_in_every_lane() {
+        let mut system = ParticleSystem::new();
+        let moving = Vec2::X;
+        let handles = spawn_all(
+            &mut system,
+            [
+                at(Kind::Ember, Vec2::ZERO).with_velocity(moving),
+                at(Kind::Ember, Vec2::ZERO)
+                    .with_velocity(moving)
+                    .with_analytic(),
+                at(Kind::Ember, Vec2::ZERO)
+                    .with_velocity(moving)
+                    .with_spline(SplineState {
+                        t: 0.0,
+                        strength: 0.0,
+                        point_1: Vec2::ZERO,
+                        point_2: Vec2::ZERO,
+                        point_3: Vec2::ZERO,
+                    }),
+                at(Kind::Ember, Vec2::ZERO),
+            ],
+        );
+        for _ in 0..3 {
+            system.step();
+        }
+
+        let mut seen = Vec::new();
+        let removed = system.retain(|p| {
+            seen.push(p.counter);
+            p.pos.x < 2.0
+        });
+        assert_eq!(removed, 3);
+        assert_eq!(seen, [7; 4]);
+        assert_eq!(system.len(), 1);
+        assert!(system.contains(handles[3]));
+    }
+
+    #[test]
+    fn kills_by_type_and_layer_reach_every_lane() {
+        let mut system = ParticleSystem::new();
+        system.set_death_events(true);
+        let embers = spawn_all(&mut system, every_lane(Kind::Ember, Vec2::ZERO));
+        let ash = spawn_all(
+            &mut system,
+            every_lane(Kind::Ash, Vec2::ZERO).map(|spawn| spawn.with_draw_layer(2)),
+        );
+        system.step();
+
+        assert_eq!(system.kill_by_type(Kind::Ember), 4);
+        assert!(embers.iter().all(|&handle| !system.contains(handle)));
+        assert!(ash.iter().all(|&handle| system.contains(handle)));
+        assert_eq!(system.kill_layer(1), 0);
+        assert_eq!(system.kill_layer(2), 4);
+        assert!(system.is_empty());
+
+        let deaths: Vec<_> = system.drain_deaths().collect();
+        assert_eq!(deaths.len(), 8);
+        assert!(deaths.iter().all(|death| death.cause == DeathCause::Killed));
+    }
+
+    #[test]
+    fn area_kills_include_their_boundary() {
+        let mut system = ParticleSystem::new();
+        let inside = Vec2::new(1.0, 1.0);
+        let edge = Vec2::new(2.0, 0.0);
+        let outside = Vec2::new(3.0, 0.0);
+        for pos in [inside, edge, outside] {
+            spawn_all(&mut system, every_lane(Kind::Ember, pos));
+        }
+
+        let rect = Rect::new(Vec2::ZERO, Vec2::new(2.0, 2.0));
+        assert_eq!(system.kill_in_rect(rect), 8);
+        assert_eq!(system.len(), 4);
+
+        for pos in [inside, edge] {
+            spawn_all(&mut system, every_lane(Kind::Ember, pos));
+        }
+        assert_eq!(system.kill_in_circle(Vec2::ZERO, 2.0), 8);
+        let mut left = Vec::new();
+        system.for_each_particle(|p| left.push(p.pos));
+        assert_eq!(left, [outside; 4]);
+    }
+}
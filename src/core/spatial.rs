@@ -0,0 +1,538 @@
+use std::ops::Range;
+
+use glam::{IVec2, Vec2};
+
+use super::analytic::advance_closed_form;
+use super::handle::{ParticleHandle, ParticleLocation, ANALYTIC_LANE, SPLINE_LANE};
+use super::particle_model::{ParticlePayload, ParticleRenderData, ParticleTypeTrait};
+use super::particle_system::ParticleSystem;
+use super::region::Rect;
+
+// Cell, position and lane location of one particle. Queries only walk these,
+// and counting never reads further.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct SpatialKey {
+    pub(crate) cell: IVec2,
+    pub(crate) pos: Vec2,
+    pub(crate) location: ParticleLocation,
+}
+
+const EMPTY_KEY: SpatialKey = SpatialKey {
+    cell: IVec2::ZERO,
+    pos: Vec2::ZERO,
+    location: ParticleLocation { lane: 0, index: 0 },
+};
+
+#[inline(always)]
+fn cell_hash(cell: IVec2) -> u32 {
+    (cell.x as u32).wrapping_mul(0x8DA6_B343) ^ (cell.y as u32).wrapping_mul(0xD816_3841)
+}
+
+// A spatial hash over particle positions as of its last rebuild. Keys point
+// at lane locations, so the index only holds while no particle has been
+// removed or moved between lanes since; `layout` records the handle table's
+// layout counter at rebuild time to check that. Positions edited outside
+// `step` mark it `stale` instead. Keys are counting-sorted into
+// buckets, one contiguous run per bucket, and the bucket count tracks the
+// particle count to keep runs short.
+#[derive(Clone, Debug)]
+pub(crate) struct SpatialIndex {
+    inverse_cell_size: f32,
+    layout: u32,
+    stale: bool,
+    bucket_mask: usize,
+    bucket_starts: Vec<u32>,
+    keys: Vec<SpatialKey>,
+    // Keys in lane order with their cell hash, before sorting.
+    staged: Vec<(u32, SpatialKey)>,
+    cursors: Vec<u32>,
+}
+
+impl SpatialIndex {
+    pub(crate) fn new(cell_size: f32) -> Self {
+        Self {
+            inverse_cell_size: 1.0 / cell_size,
+            layout: 0,
+            stale: false,
+            bucket_mask: 0,
+            bucket_starts: vec![0; 2],
+            keys: Vec::new(),
+            staged: Vec::new(),
+            cursors: Vec::new(),
+        }
+    }
+
+    // Takes effect from the next rebuild.
+    pub(crate) fn set_cell_size(&mut self, cell_size: f32) {
+        self.inverse_cell_size = 1.0 / cell_size;
+    }
+
+    // `floor` is a libm call on targets without SSE4.1, so truncate and step
+    // negative fractions down by hand.
+    #[inline(always)]
+    fn cell(&self, pos: Vec2) -> IVec2 {
+        let scaled = pos * self.inverse_cell_size;
+        let truncated = scaled.as_ivec2();
+        truncated - IVec2::select(scaled.cmplt(truncated.as_vec2()), IVec2::ONE, IVec2::ZERO)
+    }
+
+    #[inline(always)]
+    pub(crate) fn stage(&mut self, pos: Vec2, lane: u16, index: usize) {
+        let cell = self.cell(pos);
+        let key = SpatialKey {
+            cell,
+            pos,
+            location: ParticleLocation::new(lane, index),
+        };
+        self.staged.push((cell_hash(cell), key));
+    }
+
+    pub(crate) fn finish(&mut self) {
+        let count = self.staged.len();
+        let buckets = count.next_power_of_two();
+        self.bucket_mask = buckets - 1;
+
+        self.bucket_starts.clear();
+        self.bucket_starts.resize(buckets + 1, 0);
+        for (hash, _) in &self.staged {
+            self.bucket_starts[(*hash as usize & self.bucket_mask) + 1] += 1;
+        }
+        for bucket in 0..buckets {
+            self.bucket_starts[bucket + 1] += self.bucket_starts[bucket];
+        }
+
+        self.cursors.clear();
+        self.cursors
+            .extend_from_slice(&self.bucket_starts[..buckets]);
+        self.keys.clear();
+        self.keys.resize(count, EMPTY_KEY);
+        for (hash, key) in &self.staged {
+            let cursor = &mut self.cursors[*hash as usize & self.bucket_mask];
+            self.keys[*cursor as usize] = *key;
+            *cursor += 1;
+        }
+        self.staged.clear();
+    }
+
+    // Keys grouped by bucket, so a cell's keys are mostly one run.
+    pub(crate) fn keys(&self) -> &[SpatialKey] {
+        &self.keys
+    }
+
+    // Replaces `out` with the bucket runs of `cell` and the eight cells around
+    // it. Runs can hold keys of other cells that share the bucket.
+    pub(crate) fn neighborhood(&self, cell: IVec2, out: &mut Vec<(IVec2, Range<usize>)>) {
+        out.clear();
+        for y in cell.y - 1..=cell.y + 1 {
+            for x in cell.x - 1..=cell.x + 1 {
+                let cell = IVec2::new(x, y);
+                let bucket = cell_hash(cell) as usize & self.bucket_mask;
+                let start = self.bucket_starts[bucket] as usize;
+                let end = self.bucket_starts[bucket + 1] as usize;
+                if start < end {
+                    out.push((cell, start..end));
+                }
+            }
+        }
+    }
+
+    // Calls `f` with the key of every particle whose position lies in `rect`.
+    // Queries spanning more cells than there are buckets scan the keys
+    // directly instead.
+    #[inline(always)]
+    fn visit_rect(&self, rect: Rect, mut f: impl FnMut(&SpatialKey)) {
+        let min = self.cell(rect.min);
+        let max = self.cell(rect.max);
+        let cells =
+            (i64::from(max.x) - i64::from(min.x) + 1) * (i64::from(max.y) - i64::from(min.y) + 1);
+        if cells > self.bucket_mask as i64 {
+            for key in &self.keys {
+                if rect.contains(key.pos) {
+                    f(key);
+                }
+            }
+            return;
+        }
+
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                let cell = IVec2::new(x, y);
+                let bucket = cell_hash(cell) as usize & self.bucket_mask;
+                let start = self.bucket_starts[bucket] as usize;
+                let end = self.bucket_starts[bucket + 1] as usize;
+                for key in &self.keys[start..end] {
+                    if key.cell == cell && rect.contains(key.pos) {
+                        f(key);
+                    }
+                }
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn visit_circle(&self, center: Vec2, radius: f32, mut f: impl FnMut(&SpatialKey)) {
+        let radius_sq = radius * radius;
+        let bounds = Rect::from_center_size(center, Vec2::splat(radius * 2.0));
+        self.visit_rect(bounds, |key| {
+            if key.pos.distance_squared(center) <= radius_sq {
+                f(key);
+            }
+        });
+    }
+}
+
+impl<T, U> ParticleSystem<T, U>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    // Indexes particle positions in a hash of `cell_size` cells, rebuilt at the
+    // end of every `step` and `advance`. Region queries then read the index
+    // rather than scanning all particles. Particles spawned or moved since the
+    // last rebuild are missed by queries until the next one. Once any particle
+    // is removed or moved outside `step`, e.g. by `translate` or
+    // `for_each_particle_mut`, queries scan until the index is rebuilt. Cells
+    // about the size of a typical query work best.
+    pub fn set_spatial_index(&mut self, cell_size: f32) {
+        debug_assert!(cell_size > 0.0, "spatial index cells must have a size");
+        self.spatial = Some(SpatialIndex::new(cell_size));
+        self.rebuild_spatial_index();
+    }
+
+    pub fn clear_spatial_index(&mut self) {
+        self.spatial = None;
+    }
+
+    pub fn has_spatial_index(&self) -> bool {
+        self.spatial.is_some()
+    }
+
+    pub fn rebuild_spatial_index(&mut self) {
+        let Some(mut index) = self.spatial.take() else {
+            return;
+        };
+        for lane in &self.ballistic.lanes {
+            for (i, hot) in lane.hot.iter().enumerate() {
+                index.stage(hot.pos, lane.index, i);
+            }
+        }
+        for (i, spawn) in self.analytic.spawns.iter().enumerate() {
+            if let Some(age) = self.analytic.timing[i].age(self.tick) {
+                let mut core = *spawn;
+                advance_closed_form(&mut core, age);
+                index.stage(core.pos, ANALYTIC_LANE, i);
+            }
+        }
+        for (i, particle) in self.spline_particles.iter().enumerate() {
+            index.stage(particle.core.pos, SPLINE_LANE, i);
+        }
+        index.finish();
+        index.layout = self.handles.layout();
+        index.stale = false;
+        self.spatial = Some(index);
+    }
+
+    // Calls `f` for every particle whose position lies in `rect`.
+    pub fn for_each_particle_in_rect(
+        &self,
+        rect: Rect,
+        mut f: impl FnMut(ParticleRenderData<T, U>),
+    ) {
+        match self.current_spatial_index() {
+            Some(index) => index.visit_rect(rect, |key| f(self.indexed_render_data(key))),
+            None => self.for_each_particle(|particle| {
+                if rect.contains(particle.pos) {
+                    f(particle);
+                }
+            }),
+        }
+    }
+
+    pub fn for_each_particle_in_circle(
+        &self,
+        center: Vec2,
+        radius: f32,
+        mut f: impl FnMut(ParticleRenderData<T, U>),
+    ) {
+        let radius_sq = radius * radius;
+        match self.current_spatial_index() {
+            Some(index) => {
+                index.visit_circle(center, radius, |key| f(self.indexed_render_data(key)))
+            }
+            None => self.for_each_particle(|particle| {
+                if particle.pos.distance_squared(center) <= radius_sq {
+                    f(particle);
+                }
+            }),
+        }
+    }
+
+    // Appends the handles of particles in `rect` to `out`. Particles spawned
+    // without a handle are skipped.
+    pub fn handles_in_rect(&self, rect: Rect, out: &mut Vec<ParticleHandle>) {
+        match self.current_spatial_index() {
+            Some(index) => index.visit_rect(rect, |key| out.extend(self.handle_at(key.location))),
+            None => self.for_each_particle_with_handle(|particle, handle| {
+                if rect.contains(particle.pos) {
+                    out.extend(handle);
+                }
+            }),
+        }
+    }
+
+    pub fn handles_in_circle(&self, center: Vec2, radius: f32, out: &mut Vec<ParticleHandle>) {
+        let radius_sq = radius * radius;
+        match self.current_spatial_index() {
+            Some(index) => index.visit_circle(center, radius, |key| {
+                out.extend(self.handle_at(key.location))
+            }),
+            None => self.for_each_particle_with_handle(|particle, handle| {
+                if particle.pos.distance_squared(center) <= radius_sq {
+                    out.extend(handle);
+                }
+            }),
+        }
+    }
+
+    // Counting only reads positions, never render data.
+    pub fn count_in_rect(&self, rect: Rect) -> usize {
+        let mut count = 0;
+        match self.current_spatial_index() {
+            Some(index) => index.visit_rect(rect, |_| count += 1),
+            None => self.for_each_position(|pos| count += usize::from(rect.contains(pos))),
+        }
+        count
+    }
+
+    pub fn count_in_circle(&self, center: Vec2, radius: f32) -> usize {
+        let radius_sq = radius * radius;
+        let mut count = 0;
+        match self.current_spatial_index() {
+            Some(index) => index.visit_circle(center, radius, |_| count += 1),
+            None => self.for_each_position(|pos| {
+                count += usize::from(pos.distance_squared(center) <= radius_sq);
+            }),
+        }
+        count
+    }
+
+    #[inline(always)]
+    pub(crate) fn refresh_spatial_index(&mut self) {
+        if self.spatial.is_some() {
+            self.rebuild_spatial_index();
+        }
+    }
+
+    // Called after positions change outside `step`.
+    #[inline(always)]
+    pub(crate) fn invalidate_spatial_index(&mut self) {
+        if let Some(index) = &mut self.spatial {
+            index.stale = true;
+        }
+    }
+
+    // The index, unless a removal since its rebuild left locations dangling or
+    // positions have moved on from it.
+    #[inline(always)]
+    fn current_spatial_index(&self) -> Option<&SpatialIndex> {
+        self.spatial
+            .as_ref()
+            .filter(|index| !index.stale && index.layout == self.handles.layout())
+    }
+
+    // Indexed analytic particles were alive at the current tick, so their
+    // render data always evaluates.
+    #[inline(always)]
+    fn indexed_render_data(&self, key: &SpatialKey) -> ParticleRenderData<T, U> {
+        self.render_data_at(key.location)
+            .expect("indexed particles are live")
+    }
+
+    fn for_each_particle_with_handle(
+        &self,
+        mut f: impl FnMut(ParticleRenderData<T, U>, Option<ParticleHandle>),
+    ) {
+        for (hot, cold) in self.ballistic.iter() {
+            f((hot, cold).into(), self.handles.handle(cold.handle));
+        }
+        for core in self.analytic.iter_at(self.tick) {
+            f((&core).into(), self.handles.handle(core.handle));
+        }
+        for particle in &self.spline_particles {
+            f(
+                (&particle.core).into(),
+                self.handles.handle(particle.core.handle),
+            );
+        }
+    }
+
+    fn for_each_position(&self, mut f: impl FnMut(Vec2)) {
+        for (hot, _) in self.ballistic.iter() {
+            f(hot.pos);
+        }
+        for core in self.analytic.iter_at(self.tick) {
+            f(core.pos);
+        }
+        for particle in &self.spline_particles {
+            f(particle.core.pos);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use glam::Vec2;
+
+    use crate::core::{
+        ParticleHandle, ParticleSpawn, ParticleSystem, ParticleTypeTrait, Rect, SplineState,
+    };
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct Dot;
+
+    impl ParticleTypeTrait for Dot {}
+
+    // Particles scattered over every lane, including negative coordinates and
+    // cell boundaries.
+    fn scattered() -> (ParticleSystem<Dot>, Vec<ParticleHandle>) {
+        let mut system = ParticleSystem::new();
+        let mut seed = 0x2545_f491_u32;
+        let mut next = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            (seed >> 8) as f32 / (1u32 << 24) as f32 * 200.0 - 100.0
+        };
+
+        let mut handles = Vec::new();
+        for i in 0..400 {
+            let pos = if i % 25 == 0 {
+                Vec2::new((i / 25) as f32 * 8.0 - 64.0, 16.0)
+            } else {
+                Vec2::new(next(), next())
+            };
+            let spawn = ParticleSpawn::new(Dot, 1000, pos, Vec2::ONE);
+            let spawn = match i % 4 {
+                0 => spawn,
+                1 => spawn.with_velocity(Vec2::new(next(), next()) * 0.01),
+                2 => spawn
+                    .with_velocity(Vec2::new(next(), next()) * 0.01)
+                    .with_analytic(),
+                _ => spawn.with_spline(SplineState {
+                    t: 0.0,
+                    strength: 1.0,
+                    point_1: pos + Vec2::new(next(), next()) * 0.1,
+                    point_2: pos + Vec2::new(next(), next()) * 0.1,
+                    point_3: pos + Vec2::new(next(), next()) * 0.1,
+                }),
+            };
+            handles.push(system.spawn_with_handle(spawn).unwrap());
+        }
+        (system, handles)
+    }
+
+    fn scan(
+        system: &ParticleSystem<Dot>,
+        handles: &[ParticleHandle],
+        inside: impl Fn(Vec2) -> bool,
+    ) -> HashSet<ParticleHandle> {
+        handles
+            .iter()
+            .copied()
+            .filter(|&handle| system.get(handle).is_some_and(|p| inside(p.pos)))
+            .collect()
+    }
+
+    // Every handle at most once, and exactly the expected ones.
+    fn assert_same(found: Vec<ParticleHandle>, expected: &HashSet<ParticleHandle>) {
+        let unique: HashSet<_> = found.iter().copied().collect();
+        assert_eq!(unique.len(), found.len());
+        assert_eq!(&unique, expected);
+    }
+
+    fn assert_queries_match_scan(system: &ParticleSystem<Dot>, handles: &[ParticleHandle]) {
+        let rects = [
+            Rect::new(Vec2::splat(-100.0), Vec2::splat(100.0)),
+            Rect::new(Vec2::new(-30.0, -5.0), Vec2::new(12.5, 40.0)),
+            Rect::new(Vec2::new(-64.0, 16.0), Vec2::new(-32.0, 16.0)),
+            Rect::new(Vec2::new(50.0, 50.0), Vec2::new(51.0, 51.0)),
+        ];
+        for rect in rects {
+            let expected = scan(system, handles, |pos| rect.contains(pos));
+            let mut found = Vec::new();
+            system.handles_in_rect(rect, &mut found);
+            assert_same(found, &expected);
+            assert_eq!(system.count_in_rect(rect), expected.len());
+
+            let mut visited = 0;
+            system.for_each_particle_in_rect(rect, |p| {
+                assert!(rect.contains(p.pos));
+                visited += 1;
+            });
+            assert_eq!(visited, expected.len());
+        }
+
+        let circles = [
+            (Vec2::ZERO, 40.0),
+            (Vec2::new(-48.0, 16.0), 8.0),
+            (Vec2::new(90.0, -90.0), 25.0),
+            (Vec2::new(3.0, 3.0), 0.0),
+        ];
+        for (center, radius) in circles {
+            let inside = |pos: Vec2| pos.distance_squared(center) <= radius * radius;
+            let expected = scan(system, handles, inside);
+            let mut found = Vec::new();
+            system.handles_in_circle(center, radius, &mut found);
+            assert_same(found, &expected);
+            assert_eq!(system.count_in_circle(center, radius), expected.len());
+
+            let mut visited = 0;
+            system.for_each_particle_in_circle(center, radius, |p| {
+                assert!(inside(p.pos));
+                visited += 1;
+            });
+            assert_eq!(visited, expected.len());
+        }
+    }
+
+    #[test]
+    fn queries_match_a_linear_scan() {
+        let (mut system, handles) = scattered();
+        system.set_spatial_index(10.0);
+        assert_queries_match_scan(&system, &handles);
+
+        for _ in 0..30 {
+            system.step();
+        }
+        assert_queries_match_scan(&system, &handles);
+
+        system.advance(50);
+        assert_queries_match_scan(&system, &handles);
+    }
+
+    #[test]
+    fn queries_follow_changes_outside_step() {
+        let (mut system, handles) = scattered();
+        system.set_spatial_index(16.0);
+        system.step();
+
+        system.translate(Vec2::new(37.0, -11.0));
+        assert_queries_match_scan(&system, &handles);
+        system.step();
+
+        system.rotate_around(Vec2::new(5.0, 5.0), 0.7);
+        assert_queries_match_scan(&system, &handles);
+        system.step();
+
+        system.for_each_particle_mut(|particle| particle.set_pos(-particle.pos()));
+        assert_queries_match_scan(&system, &handles);
+        system.step();
+
+        system.kill_in_circle(Vec2::ZERO, 30.0);
+        assert_queries_match_scan(&system, &handles);
+        system.step();
+        assert_queries_match_scan(&system, &handles);
+    }
+}
@@ -0,0 +1,563 @@
+use glam::{Affine2, Vec2};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SplinePathId(pub(crate) u32);
+
+impl SplinePathId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+// Every segment is stored as cubic polynomial coefficients so quadratic, cubic,
+// Catmull-Rom and linear pieces all evaluate through the same Horner form.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct SplineSegment {
+    pub(crate) a: Vec2,
+    pub(crate) b: Vec2,
+    pub(crate) c: Vec2,
+    pub(crate) d: Vec2,
+}
+
+impl SplineSegment {
+    pub(crate) fn line(point_1: Vec2, point_2: Vec2) -> Self {
+        Self {
+            a: Vec2::ZERO,
+            b: Vec2::ZERO,
+            c: point_2 - point_1,
+            d: point_1,
+        }
+    }
+
+    pub(crate) fn quadratic_bezier(point_1: Vec2, point_2: Vec2, point_3: Vec2) -> Self {
+        Self {
+            a: Vec2::ZERO,
+            b: point_1 - (point_2 * 2.0) + point_3,
+            c: (point_2 - point_1) * 2.0,
+            d: point_1,
+        }
+    }
+
+    pub(crate) fn cubic_bezier(point_1: Vec2, point_2: Vec2, point_3: Vec2, point_4: Vec2) -> Self {
+        Self {
+            a: (point_2 - point_3) * 3.0 + point_4 - point_1,
+            b: (point_1 - (point_2 * 2.0) + point_3) * 3.0,
+            c: (point_2 - point_1) * 3.0,
+            d: point_1,
+        }
+    }
+
+    pub(crate) fn catmull_rom(point_0: Vec2, point_1: Vec2, point_2: Vec2, point_3: Vec2) -> Self {
+        Self {
+            a: ((point_1 - point_2) * 3.0 + point_3 - point_0) * 0.5,
+            b: ((point_0 * 2.0) - (point_1 * 5.0) + (point_2 * 4.0) - point_3) * 0.5,
+            c: (point_2 - point_0) * 0.5,
+            d: point_1,
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn evaluate(&self, t: f32) -> Vec2 {
+        (((self.a * t) + self.b) * t + self.c) * t + self.d
+    }
+
+    #[inline(always)]
+    pub(crate) fn derivative(&self, t: f32) -> Vec2 {
+        ((self.a * (3.0 * t)) + (self.b * 2.0)) * t + self.c
+    }
+
+    // Cumulative chord length at evenly spaced samples, starting from `0.0`.
+    fn sample_lengths(&self) -> [f32; ARC_LENGTH_SAMPLES_PER_SEGMENT + 1] {
+        let mut lengths = [0.0; ARC_LENGTH_SAMPLES_PER_SEGMENT + 1];
+        let mut previous = self.evaluate(0.0);
+        for i in 1..=ARC_LENGTH_SAMPLES_PER_SEGMENT {
+            let point = self.evaluate(i as f32 / ARC_LENGTH_SAMPLES_PER_SEGMENT as f32);
+            lengths[i] = lengths[i - 1] + point.distance(previous);
+            previous = point;
+        }
+        lengths
+    }
+
+    fn length(&self) -> f32 {
+        self.sample_lengths()[ARC_LENGTH_SAMPLES_PER_SEGMENT]
+    }
+
+    fn point_at_distance(&self, distance: f32) -> Vec2 {
+        self.evaluate(parameter_at_distance(&self.sample_lengths(), distance))
+    }
+}
+
+pub const ARC_LENGTH_SAMPLES_PER_SEGMENT: usize = 16;
+
+#[derive(Clone, Debug, Default)]
+pub struct SplinePath {
+    segments: Vec<SplineSegment>,
+    // Cumulative chord length at each of the evenly spaced parameter samples,
+    // starting with the `0.0` entry for the start of the path.
+    arc_lengths: Vec<f32>,
+}
+
+impl SplinePath {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn quadratic_bezier(point_1: Vec2, point_2: Vec2, point_3: Vec2) -> Self {
+        Self::new().with_quadratic_bezier(point_1, point_2, point_3)
+    }
+
+    pub fn cubic_bezier(point_1: Vec2, point_2: Vec2, point_3: Vec2, point_4: Vec2) -> Self {
+        Self::new().with_cubic_bezier(point_1, point_2, point_3, point_4)
+    }
+
+    pub fn polyline(points: &[Vec2]) -> Self {
+        let mut path = Self::new();
+        for pair in points.windows(2) {
+            path.push_segment(SplineSegment::line(pair[0], pair[1]));
+        }
+        path
+    }
+
+    // Passes through every point. The first and last points are mirrored to
+    // provide the outer tangents, so the chain starts and ends on them.
+    pub fn catmull_rom(points: &[Vec2]) -> Self {
+        let mut path = Self::new();
+        if points.len() < 2 {
+            return path;
+        }
+
+        let last = points.len() - 1;
+        for i in 0..last {
+            let point_0 = if i == 0 {
+                points[0] * 2.0 - points[1]
+            } else {
+                points[i - 1]
+            };
+            let point_3 = if i + 1 == last {
+                points[last] * 2.0 - points[last - 1]
+            } else {
+                points[i + 2]
+            };
+            path.push_segment(SplineSegment::catmull_rom(
+                point_0,
+                points[i],
+                points[i + 1],
+                point_3,
+            ));
+        }
+        path
+    }
+
+    pub fn with_line(mut self, point_1: Vec2, point_2: Vec2) -> Self {
+        self.push_segment(SplineSegment::line(point_1, point_2));
+        self
+    }
+
+    pub fn with_quadratic_bezier(mut self, point_1: Vec2, point_2: Vec2, point_3: Vec2) -> Self {
+        self.push_segment(SplineSegment::quadratic_bezier(point_1, point_2, point_3));
+        self
+    }
+
+    pub fn with_cubic_bezier(
+        mut self,
+        point_1: Vec2,
+        point_2: Vec2,
+        point_3: Vec2,
+        point_4: Vec2,
+    ) -> Self {
+        self.push_segment(SplineSegment::cubic_bezier(
+            point_1, point_2, point_3, point_4,
+        ));
+        self
+    }
+
+    pub fn with_path(mut self, other: &SplinePath) -> Self {
+        for segment in &other.segments {
+            self.push_segment(*segment);
+        }
+        self
+    }
+
+    pub fn segment_count(&self) -> usize {
+        self.segments.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    // `t` spans the whole path in `0..=1`, with each segment getting an equal share.
+    #[inline(always)]
+    pub fn point(&self, t: f32) -> Vec2 {
+        let count = self.segments.len();
+        if count == 0 {
+            return Vec2::ZERO;
+        }
+
+        let scaled = t * count as f32;
+        let index = (scaled as usize).min(count - 1);
+        self.segments[index].evaluate(scaled - index as f32)
+    }
+
+    // Derivative with respect to the whole-path `t`.
+    #[inline(always)]
+    pub fn tangent(&self, t: f32) -> Vec2 {
+        let count = self.segments.len();
+        if count == 0 {
+            return Vec2::ZERO;
+        }
+
+        let scaled = t * count as f32;
+        let index = (scaled as usize).min(count - 1);
+        self.segments[index].derivative(scaled - index as f32) * count as f32
+    }
+
+    pub fn length(&self) -> f32 {
+        self.arc_lengths.last().copied().unwrap_or(0.0)
+    }
+
+    // Maps a distance along the path to the matching `t`, interpolating
+    // linearly between the precomputed length samples.
+    pub fn parameter_at_distance(&self, distance: f32) -> f32 {
+        parameter_at_distance(&self.arc_lengths, distance)
+    }
+
+    #[inline(always)]
+    pub fn point_at_distance(&self, distance: f32) -> Vec2 {
+        self.point(self.parameter_at_distance(distance))
+    }
+
+    // Applies a similarity transform; `length_scale` is its scale factor, so
+    // arc lengths follow without resampling.
+    pub(crate) fn transform(&mut self, transform: Affine2, length_scale: f32) {
+        for segment in &mut self.segments {
+            segment.a = transform.transform_vector2(segment.a);
+            segment.b = transform.transform_vector2(segment.b);
+            segment.c = transform.transform_vector2(segment.c);
+            segment.d = transform.transform_point2(segment.d);
+        }
+        for length in &mut self.arc_lengths {
+            *length *= length_scale;
+        }
+    }
+
+    fn push_segment(&mut self, segment: SplineSegment) {
+        if self.arc_lengths.is_empty() {
+            self.arc_lengths.push(0.0);
+        }
+
+        let start = self.length();
+        self.arc_lengths.extend(
+            segment.sample_lengths()[1..]
+                .iter()
+                .map(|&length| start + length),
+        );
+        self.segments.push(segment);
+    }
+}
+
+// `t` in `0..=1` at `distance` along a curve with cumulative lengths
+// `arc_lengths` at evenly spaced parameter samples.
+fn parameter_at_distance(arc_lengths: &[f32], distance: f32) -> f32 {
+    let sample_count = arc_lengths.len();
+    if sample_count < 2 {
+        return 0.0;
+    }
+
+    let distance = distance.clamp(0.0, arc_lengths[sample_count - 1]);
+    let upper = arc_lengths
+        .partition_point(|&length| length < distance)
+        .clamp(1, sample_count - 1);
+    let lower_length = arc_lengths[upper - 1];
+    let span = arc_lengths[upper] - lower_length;
+    let fraction = if span > 0.0 {
+        (distance - lower_length) / span
+    } else {
+        0.0
+    };
+
+    ((upper - 1) as f32 + fraction) / (sample_count - 1) as f32
+}
+
+pub fn calculate_cubic_bezier_point(
+    t: f32,
+    point_1: Vec2,
+    point_2: Vec2,
+    point_3: Vec2,
+    point_4: Vec2,
+) -> Vec2 {
+    let one_minus_t = 1.0 - t;
+    (point_1 * one_minus_t * one_minus_t * one_minus_t)
+        + (point_2 * 3.0 * one_minus_t * one_minus_t * t)
+        + (point_3 * 3.0 * one_minus_t * t * t)
+        + (point_4 * t * t * t)
+}
+
+pub fn calculate_catmull_rom_point(
+    t: f32,
+    point_0: Vec2,
+    point_1: Vec2,
+    point_2: Vec2,
+    point_3: Vec2,
+) -> Vec2 {
+    SplineSegment::catmull_rom(point_0, point_1, point_2, point_3).evaluate(t)
+}
+
+// The length helpers sample the curve on every call. Curves queried
+// repeatedly belong in a `SplinePath`, which keeps its length table.
+pub fn calculate_bezier_length(point_1: Vec2, point_2: Vec2, point_3: Vec2) -> f32 {
+    SplineSegment::quadratic_bezier(point_1, point_2, point_3).length()
+}
+
+pub fn calculate_bezier_point_at_distance(
+    distance: f32,
+    point_1: Vec2,
+    point_2: Vec2,
+    point_3: Vec2,
+) -> Vec2 {
+    SplineSegment::quadratic_bezier(point_1, point_2, point_3).point_at_distance(distance)
+}
+
+pub fn calculate_cubic_bezier_length(
+    point_1: Vec2,
+    point_2: Vec2,
+    point_3: Vec2,
+    point_4: Vec2,
+) -> f32 {
+    SplineSegment::cubic_bezier(point_1, point_2, point_3, point_4).length()
+}
+
+pub fn calculate_cubic_bezier_point_at_distance(
+    distance: f32,
+    point_1: Vec2,
+    point_2: Vec2,
+    point_3: Vec2,
+    point_4: Vec2,
+) -> Vec2 {
+    SplineSegment::cubic_bezier(point_1, point_2, point_3, point_4).point_at_distance(distance)
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec2;
+
+    use super::{
+        calculate_bezier_length, calculate_bezier_point_at_distance, calculate_cubic_bezier_length,
+        calculate_cubic_bezier_point, calculate_cubic_bezier_point_at_distance, SplinePath,
+    };
+    use crate::core::{ParticleSpawn, ParticleSystem, ParticleTypeTrait, SplinePathState};
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct Mote;
+
+    impl ParticleTypeTrait for Mote {}
+
+    const EPSILON: f32 = 1e-4;
+
+    fn samples() -> impl Iterator<Item = f32> {
+        (0..=20).map(|i| i as f32 / 20.0)
+    }
+
+    #[test]
+    fn bezier_coefficients_match_bernstein_form() {
+        let [p1, p2, p3, p4] = [
+            Vec2::new(0.0, 0.0),
+            Vec2::new(10.0, 30.0),
+            Vec2::new(40.0, -20.0),
+            Vec2::new(50.0, 5.0),
+        ];
+        let cubic = SplinePath::cubic_bezier(p1, p2, p3, p4);
+        let quadratic = SplinePath::quadratic_bezier(p1, p2, p3);
+        for t in samples() {
+            let expected = calculate_cubic_bezier_point(t, p1, p2, p3, p4);
+            assert!(cubic.point(t).abs_diff_eq(expected, EPSILON));
+
+            let u = 1.0 - t;
+            let expected = p1 * u * u + p2 * 2.0 * u * t + p3 * t * t;
+            assert!(quadratic.point(t).abs_diff_eq(expected, EPSILON));
+        }
+    }
+
+    #[test]
+    fn catmull_rom_passes_through_every_point() {
+        let points = [
+            Vec2::new(0.0, 0.0),
+            Vec2::new(10.0, 5.0),
+            Vec2::new(15.0, -5.0),
+            Vec2::new(30.0, 0.0),
+        ];
+        let path = SplinePath::catmull_rom(&points);
+        assert_eq!(path.segment_count(), 3);
+        for (i, &point) in points.iter().enumerate() {
+            let t = i as f32 / 3.0;
+            assert!(path.point(t).abs_diff_eq(point, EPSILON), "{i}");
+        }
+        assert!(SplinePath::catmull_rom(&points[..1]).is_empty());
+    }
+
+    #[test]
+    fn segments_share_t_equally() {
+        let path = SplinePath::polyline(&[Vec2::ZERO, Vec2::new(10.0, 0.0), Vec2::new(10.0, 90.0)]);
+        assert!(path.point(0.25).abs_diff_eq(Vec2::new(5.0, 0.0), EPSILON));
+        assert!(path.point(0.75).abs_diff_eq(Vec2::new(10.0, 45.0), EPSILON));
+        assert!(path.point(1.0).abs_diff_eq(Vec2::new(10.0, 90.0), EPSILON));
+        assert_eq!(SplinePath::new().point(0.5), Vec2::ZERO);
+    }
+
+    #[test]
+    fn tangent_is_the_whole_path_derivative() {
+        let path = SplinePath::cubic_bezier(
+            Vec2::ZERO,
+            Vec2::new(0.0, 40.0),
+            Vec2::new(60.0, 40.0),
+            Vec2::new(60.0, 0.0),
+        )
+        .with_quadratic_bezier(
+            Vec2::new(60.0, 0.0),
+            Vec2::new(60.0, -30.0),
+            Vec2::new(90.0, -30.0),
+        );
+        let h = 1e-3;
+        for t in [0.1, 0.3, 0.45, 0.6, 0.9] {
+            let finite = (path.point(t + h) - path.point(t - h)) / (2.0 * h);
+            let tangent = path.tangent(t);
+            assert!(
+                (finite - tangent).length() < tangent.length() * 1e-2,
+                "{t}: {finite} vs {tangent}"
+            );
+        }
+    }
+
+    #[test]
+    fn polyline_lengths_are_exact() {
+        let path = SplinePath::polyline(&[Vec2::ZERO, Vec2::new(10.0, 0.0), Vec2::new(10.0, 90.0)]);
+        assert!((path.length() - 100.0).abs() < EPSILON);
+        assert!((path.parameter_at_distance(10.0) - 0.5).abs() < EPSILON);
+        assert!((path.parameter_at_distance(55.0) - 0.75).abs() < EPSILON);
+        assert!(path
+            .point_at_distance(55.0)
+            .abs_diff_eq(Vec2::new(10.0, 45.0), EPSILON));
+
+        // Distances outside the path clamp to its ends.
+        assert_eq!(path.parameter_at_distance(-5.0), 0.0);
+        assert_eq!(path.parameter_at_distance(500.0), 1.0);
+        assert_eq!(SplinePath::new().parameter_at_distance(1.0), 0.0);
+    }
+
+    #[test]
+    fn curve_lengths_approach_dense_sampling() {
+        let [p1, p2, p3, p4] = [
+            Vec2::ZERO,
+            Vec2::new(0.0, 50.0),
+            Vec2::new(80.0, 50.0),
+            Vec2::new(80.0, 0.0),
+        ];
+        let path = SplinePath::cubic_bezier(p1, p2, p3, p4);
+        let dense: f32 = (1..=4096)
+            .map(|i| {
+                let t = i as f32 / 4096.0;
+                calculate_cubic_bezier_point(t, p1, p2, p3, p4).distance(
+                    calculate_cubic_bezier_point(t - 1.0 / 4096.0, p1, p2, p3, p4),
+                )
+            })
+            .sum();
+        assert!((path.length() - dense).abs() < dense * 5e-3);
+
+        // Distance lookups land that far along the curve.
+        for distance in [10.0, 60.0, 120.0] {
+            let t = path.parameter_at_distance(distance);
+            let travelled: f32 = (1..=1024)
+                .map(|i| {
+                    path.point(t * i as f32 / 1024.0)
+                        .distance(path.point(t * (i - 1) as f32 / 1024.0))
+                })
+                .sum();
+            assert!(
+                (travelled - distance).abs() < 0.5,
+                "{distance}: {travelled}"
+            );
+        }
+    }
+
+    #[test]
+    fn helpers_match_spline_paths() {
+        let [p1, p2, p3, p4] = [
+            Vec2::new(-10.0, 0.0),
+            Vec2::new(0.0, 30.0),
+            Vec2::new(20.0, -10.0),
+            Vec2::new(40.0, 10.0),
+        ];
+        let quadratic = SplinePath::quadratic_bezier(p1, p2, p3);
+        let cubic = SplinePath::cubic_bezier(p1, p2, p3, p4);
+        assert_eq!(calculate_bezier_length(p1, p2, p3), quadratic.length());
+        assert_eq!(
+            calculate_cubic_bezier_length(p1, p2, p3, p4),
+            cubic.length()
+        );
+        for distance in [0.0, 12.5, 30.0, 1000.0] {
+            assert!(calculate_bezier_point_at_distance(distance, p1, p2, p3)
+                .abs_diff_eq(quadratic.point_at_distance(distance), EPSILON));
+            assert!(
+                calculate_cubic_bezier_point_at_distance(distance, p1, p2, p3, p4)
+                    .abs_diff_eq(cubic.point_at_distance(distance), EPSILON)
+            );
+        }
+    }
+
+    #[test]
+    fn arc_length_progress_moves_at_constant_speed() {
+        let mut system = ParticleSystem::new();
+        // Control points bunched at the start make parameter speed uneven.
+        let path = system.add_spline_path(SplinePath::cubic_bezier(
+            Vec2::ZERO,
+            Vec2::new(2.0, 2.0),
+            Vec2::new(4.0, 4.0),
+            Vec2::new(100.0, 0.0),
+        ));
+        let length = system.spline_path(path).length();
+        let state = |arc_length| SplinePathState {
+            t: 0.0,
+            strength: 1.0,
+            path,
+            arc_length,
+        };
+        let spawn = |arc_length| {
+            ParticleSpawn::new(Mote, 1000, Vec2::ZERO, Vec2::ONE)
+                .with_spline_path(state(arc_length))
+                .with_spline_velocity(if arc_length { 2.0 } else { 0.02 })
+        };
+        let by_length = system.spawn_with_handle(spawn(true)).unwrap();
+        let by_parameter = system.spawn_with_handle(spawn(false)).unwrap();
+
+        let mut steps = Vec::new();
+        let mut previous = (Vec2::ZERO, Vec2::ZERO);
+        for _ in 0..40 {
+            system.step();
+            let now = (
+                system.get(by_length).unwrap().pos,
+                system.get(by_parameter).unwrap().pos,
+            );
+            steps.push((now.0.distance(previous.0), now.1.distance(previous.1)));
+            previous = now;
+        }
+        // The length table is linear between samples, so a step can be off by
+        // a few percent where parameter speed changes sharply.
+        for &(distance, _) in &steps[1..] {
+            assert!((distance - 2.0).abs() < 0.15, "{distance}");
+        }
+        let (slowest, fastest) = steps[1..]
+            .iter()
+            .fold((f32::MAX, 0.0f32), |(lo, hi), &(_, d)| {
+                (lo.min(d), hi.max(d))
+            });
+        assert!(fastest > slowest * 2.0, "parameter speed was even");
+
+        // Progress stops at the end of the path.
+        for _ in 0..100 {
+            system.step();
+        }
+        let end = system.spline_path(path).point(1.0);
+        assert!(system.get(by_length).unwrap().pos.abs_diff_eq(end, 1e-3));
+        assert!(length > 100.0);
+    }
+}
@@ -0,0 +1,544 @@
+use std::hash::Hash;
+
+use glam::Vec2;
+
+use super::anchor::AnchorTable;
+use super::ballistic_lanes::{orient_ballistic, ORIENTED_LANE};
+use super::death::DeathCause;
+use super::handle::{ParticleLocation, SPLINE_LANE};
+use super::interpolation::PreviousState;
+use super::particle_model::{
+    BallisticCold, BallisticHot, Orientation, ParticlePayload, ParticleTypeTrait, SplineParticle,
+    HAS_ACCELERATION, HAS_ALPHA_ACCELERATION, HAS_ALPHA_VELOCITY, HAS_ROTATION_ACCELERATION,
+    HAS_ROTATION_VELOCITY, HAS_SIZE_ACCELERATION, HAS_SIZE_VELOCITY, HAS_VELOCITY,
+};
+use super::particle_system::{
+    orient_particle, step_core_particle_generic, step_spline_motion, ParticleSystem,
+};
+use super::particle_types::TypeTable;
+use super::particle_view::ParticleMut;
+use super::spline_path::SplinePath;
+use super::trail::NO_TRAIL;
+
+// A circular region where time runs at `time_scale`, blending back to normal
+// speed over the outer `falloff` of its radius.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimeDilationZone {
+    pub center: Vec2,
+    pub radius: f32,
+    pub time_scale: f32,
+    pub falloff: f32,
+}
+
+impl TimeDilationZone {
+    pub fn new(center: Vec2, radius: f32, time_scale: f32) -> Self {
+        Self {
+            center,
+            radius,
+            time_scale,
+            falloff: 0.0,
+        }
+    }
+
+    pub fn with_falloff(mut self, falloff: f32) -> Self {
+        self.falloff = falloff;
+        self
+    }
+
+    #[inline(always)]
+    fn factor(&self, pos: Vec2) -> f32 {
+        let distance_sq = pos.distance_squared(self.center);
+        if distance_sq >= self.radius * self.radius {
+            return 1.0;
+        }
+
+        let inner = (self.radius - self.falloff).max(0.0);
+        let distance = distance_sq.sqrt();
+        if distance <= inner {
+            return self.time_scale;
+        }
+        let t = (distance - inner) / (self.radius - inner);
+        self.time_scale + (1.0 - self.time_scale) * t
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TimeZoneId(pub(crate) u32);
+
+impl TimeZoneId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+// Pausing is kept apart from the scale so resuming restores it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct TimeScale {
+    pub(crate) scale: f32,
+    pub(crate) paused: bool,
+}
+
+impl TimeScale {
+    #[inline(always)]
+    pub(crate) fn effective(&self) -> f32 {
+        if self.paused {
+            0.0
+        } else {
+            self.scale
+        }
+    }
+
+    fn is_identity(&self) -> bool {
+        !self.paused && self.scale == 1.0
+    }
+}
+
+impl Default for TimeScale {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            paused: false,
+        }
+    }
+}
+
+// Per-layer scales and dilation zones. Per-type scales live in the type table
+// with the rest of the per-type settings.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct TimeControls {
+    layers: Vec<(u32, TimeScale)>,
+    zones: Vec<Option<TimeDilationZone>>,
+    types_scaled: bool,
+}
+
+impl TimeControls {
+    fn is_active(&self) -> bool {
+        self.types_scaled || !self.layers.is_empty() || self.zones.iter().any(Option::is_some)
+    }
+
+    fn layer(&self, draw_layer: u32) -> TimeScale {
+        self.layers
+            .iter()
+            .find(|(layer, _)| *layer == draw_layer)
+            .map_or(TimeScale::default(), |(_, time)| *time)
+    }
+
+    fn set_layer(&mut self, draw_layer: u32, time: TimeScale) {
+        self.layers.retain(|(layer, _)| *layer != draw_layer);
+        if !time.is_identity() {
+            self.layers.push((draw_layer, time));
+        }
+    }
+
+    // Layer, type and every zone the particle is in, multiplied together.
+    #[inline(always)]
+    pub(crate) fn scale<T>(
+        &self,
+        types: &TypeTable<T>,
+        type_slot: u16,
+        draw_layer: u32,
+        pos: Vec2,
+    ) -> f32
+    where
+        T: ParticleTypeTrait,
+    {
+        let mut scale = self.layer(draw_layer).effective();
+        if self.types_scaled {
+            scale *= types.time_scale(type_slot);
+        }
+        for zone in self.zones.iter().flatten() {
+            scale *= zone.factor(pos);
+        }
+        scale
+    }
+}
+
+impl<T, U> ParticleSystem<T, U>
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    // Scales how fast particles on `draw_layer` move and age; `0` freezes them.
+    pub fn set_layer_time_scale(&mut self, draw_layer: u32, scale: f32) {
+        let mut time = self.time.layer(draw_layer);
+        time.scale = scale.max(0.0);
+        self.time.set_layer(draw_layer, time);
+        self.refresh_time_scaled();
+    }
+
+    pub fn layer_time_scale(&self, draw_layer: u32) -> f32 {
+        self.time.layer(draw_layer).scale
+    }
+
+    pub fn set_layer_paused(&mut self, draw_layer: u32, paused: bool) {
+        let mut time = self.time.layer(draw_layer);
+        time.paused = paused;
+        self.time.set_layer(draw_layer, time);
+        self.refresh_time_scaled();
+    }
+
+    pub fn is_layer_paused(&self, draw_layer: u32) -> bool {
+        self.time.layer(draw_layer).paused
+    }
+
+    // Unlike trail styles, type time scales also reach particles spawned
+    // before the type was first configured.
+    pub fn set_type_time_scale(&mut self, particle_type: T, scale: f32)
+    where
+        T: Hash + Eq,
+    {
+        self.type_settings_mut(particle_type).time.scale = scale.max(0.0);
+        self.refresh_time_scaled();
+    }
+
+    pub fn type_time_scale(&self, particle_type: T) -> f32 {
+        let slot = self.types.slot(particle_type);
+        self.types
+            .get(slot)
+            .map_or(1.0, |settings| settings.time.scale)
+    }
+
+    pub fn set_type_paused(&mut self, particle_type: T, paused: bool)
+    where
+        T: Hash + Eq,
+    {
+        self.type_settings_mut(particle_type).time.paused = paused;
+        self.refresh_time_scaled();
+    }
+
+    pub fn is_type_paused(&self, particle_type: T) -> bool {
+        let slot = self.types.slot(particle_type);
+        self.types
+            .get(slot)
+            .is_some_and(|settings| settings.time.paused)
+    }
+
+    pub fn add_time_zone(&mut self, zone: TimeDilationZone) -> TimeZoneId {
+        let id = TimeZoneId(self.time.zones.len() as u32);
+        self.time.zones.push(Some(zone));
+        self.refresh_time_scaled();
+        id
+    }
+
+    // Zones are read at step time, so moving one each frame is cheap.
+    pub fn set_time_zone(&mut self, id: TimeZoneId, zone: TimeDilationZone) {
+        self.time.zones[id.index()] = Some(zone);
+    }
+
+    pub fn time_zone(&self, id: TimeZoneId) -> Option<TimeDilationZone> {
+        self.time.zones[id.index()]
+    }
+
+    pub fn remove_time_zone(&mut self, id: TimeZoneId) {
+        self.time.zones[id.index()] = None;
+        self.refresh_time_scaled();
+    }
+
+    pub fn clear_time_controls(&mut self) {
+        self.time = TimeControls::default();
+        for settings in &mut self.types.settings {
+            settings.time = TimeScale::default();
+        }
+        self.refresh_time_scaled();
+    }
+
+    // Any time control moves `step` onto a path that looks up a scale per
+    // particle, with analytic particles moved to the ballistic lane first
+    // since their closed form assumes unscaled time.
+    fn refresh_time_scaled(&mut self) {
+        self.time.types_scaled = self
+            .types
+            .settings
+            .iter()
+            .any(|settings| !settings.time.is_identity());
+        self.time_scaled = self.time.is_active();
+        if self.time_scaled {
+            self.materialize_analytic();
+        }
+    }
+
+    // Particles whose counter already reached zero are removed even where time
+    // is frozen: their lifetime ran out in an earlier step, and a pause only
+    // holds particles that still have some left.
+    pub(crate) fn step_time_scaled(&mut self) {
+        self.ballistic.step_scaled(
+            &mut self.trails,
+            &mut self.types,
+            &mut self.handles,
+            &mut self.deaths,
+            &self.time,
+        );
+
+        let mut i = 0;
+        while i < self.spline_particles.len() {
+            let particle = &mut self.spline_particles[i];
+            if particle.core.counter == 0 {
+                self.remove_particle(ParticleLocation::new(SPLINE_LANE, i), DeathCause::Expired);
+                continue;
+            }
+
+            if self.interpolation {
+                self.spline_previous[i] = PreviousState::from(&particle.core);
+            }
+            let core = &particle.core;
+            let scale = self
+                .time
+                .scale(&self.types, core.type_slot, core.draw_layer, core.pos);
+            step_spline_particle_scaled(particle, &self.spline_paths, &self.anchors, scale);
+            if particle.core.trail != NO_TRAIL && scale > 0.0 {
+                self.trails.record(particle.core.trail, particle.core.pos);
+            }
+            i += 1;
+        }
+    }
+}
+
+// Counts `scale` steps of lifetime, carrying the fraction to the next step.
+#[inline(always)]
+pub(crate) fn consume_lifetime(counter: &mut u32, partial_step: &mut f32, scale: f32) {
+    let elapsed = *partial_step + scale;
+    let whole = elapsed.floor();
+    *partial_step = elapsed - whole;
+    *counter = counter.saturating_sub(whole as u32);
+}
+
+// `step_keyed_particle` over `scale` steps of time, with the key read at run
+// time. Velocities and accelerations are per step, so a scale of `1` matches
+// `step` exactly.
+pub(crate) fn step_ballistic_scaled<T, U>(
+    particle: &mut BallisticHot,
+    cold: &mut BallisticCold<T, U>,
+    key: u16,
+    scale: f32,
+) where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    consume_lifetime(&mut particle.counter, &mut cold.partial_step, scale);
+
+    if (key & HAS_VELOCITY) != 0 {
+        if (key & HAS_ACCELERATION) != 0 {
+            particle.velocity += particle.acceleration * scale;
+        }
+        particle.pos += particle.velocity * scale;
+    }
+
+    if (key & HAS_SIZE_VELOCITY) != 0 {
+        if (key & HAS_SIZE_ACCELERATION) != 0 {
+            particle.size_velocity += particle.size_acceleration * scale;
+        }
+        particle.size += particle.size_velocity * scale;
+        particle.size = particle.size.max(Vec2::ZERO);
+    }
+
+    if (key & HAS_ROTATION_VELOCITY) != 0 {
+        if (key & HAS_ROTATION_ACCELERATION) != 0 {
+            particle.rotation_velocity += cold.rotation_acceleration * scale;
+        }
+        particle.rotation += particle.rotation_velocity * scale;
+    }
+
+    if (key & HAS_ALPHA_VELOCITY) != 0 {
+        if (key & HAS_ALPHA_ACCELERATION) != 0 {
+            particle.alpha_velocity += cold.alpha_acceleration * scale;
+        }
+        particle.alpha = (particle.alpha + particle.alpha_velocity * scale).clamp(0.0, 1.0);
+    }
+
+    // A frozen particle has no motion to face along or stretch by.
+    if (key & ORIENTED_LANE) != 0 && scale > 0.0 {
+        orient_ballistic(particle, cold);
+    }
+
+    if T::STEP_HOOK && scale > 0.0 {
+        let particle_type = cold.particle_type;
+        particle_type.on_step(&mut ParticleMut::ballistic(particle, cold));
+    }
+}
+
+#[inline(always)]
+fn step_spline_particle_scaled<T, U>(
+    particle: &mut SplineParticle<T, U>,
+    paths: &[SplinePath],
+    anchors: &AnchorTable,
+    scale: f32,
+) where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    let previous_pos = integrate_spline_particle_scaled(particle, scale);
+    finish_spline_particle_scaled(particle, previous_pos, paths, anchors, scale);
+}
+
+// The first half of a spline step: lifetime and the particle's own motion.
+// Returns the position it started the step at.
+#[inline(always)]
+pub(crate) fn integrate_spline_particle_scaled<T, U>(
+    particle: &mut SplineParticle<T, U>,
+    scale: f32,
+) -> Vec2
+where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    let core = &mut particle.core;
+    consume_lifetime(&mut core.counter, &mut core.partial_step, scale);
+    let previous_pos = core.pos;
+    step_core_particle_generic(core, core.flags, scale);
+    previous_pos
+}
+
+// The second half: the pull onto the curve, orientation and the step hook.
+#[inline(always)]
+pub(crate) fn finish_spline_particle_scaled<T, U>(
+    particle: &mut SplineParticle<T, U>,
+    previous_pos: Vec2,
+    paths: &[SplinePath],
+    anchors: &AnchorTable,
+    scale: f32,
+) where
+    T: ParticleTypeTrait,
+    U: ParticlePayload,
+{
+    let core = &mut particle.core;
+    let (parameter, progress) =
+        step_spline_motion(core, &mut particle.spline, paths, anchors, scale);
+    // A frozen particle has no motion to face along or stretch by.
+    if core.oriented && scale > 0.0 {
+        let motion = core.pos - previous_pos;
+        let tangent = if core.orientation == Orientation::AlignToSplineTangent {
+            particle.spline.tangent(parameter, progress, paths, anchors)
+        } else {
+            motion
+        };
+        orient_particle(core, motion, tangent);
+    }
+    if T::STEP_HOOK && scale > 0.0 {
+        let particle_type = core.particle_type;
+        particle_type.on_step(&mut ParticleMut::spline(particle));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{DeathCause, ParticleHandle, ParticleSpawn};
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    enum Kind {
+        Slow,
+        Fast,
+    }
+    impl ParticleTypeTrait for Kind {}
+
+    fn mover(kind: Kind, pos: Vec2) -> ParticleSpawn<Kind> {
+        ParticleSpawn::new(kind, 10, pos, Vec2::ONE).with_velocity(Vec2::X)
+    }
+
+    fn spawn(system: &mut ParticleSystem<Kind>, spawn: ParticleSpawn<Kind>) -> ParticleHandle {
+        system.spawn_with_handle(spawn).unwrap()
+    }
+
+    #[test]
+    fn paused_layers_freeze_and_resume() {
+        let mut system = ParticleSystem::new();
+        let paused = spawn(
+            &mut system,
+            mover(Kind::Slow, Vec2::ZERO).with_draw_layer(1),
+        );
+        let running = spawn(&mut system, mover(Kind::Slow, Vec2::ZERO));
+        system.set_layer_paused(1, true);
+        system.step();
+        system.step();
+
+        let frozen = system.get(paused).unwrap();
+        assert_eq!((frozen.pos, frozen.counter), (Vec2::ZERO, 10));
+        assert_eq!(system.get(running).unwrap().pos, Vec2::new(2.0, 0.0));
+
+        system.set_layer_paused(1, false);
+        system.step();
+        let resumed = system.get(paused).unwrap();
+        assert_eq!((resumed.pos, resumed.counter), (Vec2::X, 9));
+    }
+
+    #[test]
+    fn type_scales_slow_motion_and_lifetime() {
+        let mut system = ParticleSystem::new();
+        let slow = spawn(&mut system, mover(Kind::Slow, Vec2::ZERO));
+        let fast = spawn(&mut system, mover(Kind::Fast, Vec2::ZERO));
+        system.set_type_time_scale(Kind::Slow, 0.5);
+        system.step();
+
+        // Half a step of lifetime is carried, not lost.
+        let slow_now = system.get(slow).unwrap();
+        assert_eq!((slow_now.pos.x, slow_now.counter), (0.5, 10));
+        system.step();
+        let slow_now = system.get(slow).unwrap();
+        assert_eq!((slow_now.pos.x, slow_now.counter), (1.0, 9));
+        assert_eq!(system.get(fast).unwrap().counter, 8);
+    }
+
+    #[test]
+    fn zones_scale_by_distance() {
+        let mut system = ParticleSystem::new();
+        let zone = TimeDilationZone::new(Vec2::ZERO, 10.0, 0.0).with_falloff(4.0);
+        let core = spawn(&mut system, mover(Kind::Slow, Vec2::new(-1.0, 0.0)));
+        let edge = spawn(&mut system, mover(Kind::Slow, Vec2::new(0.0, 8.0)));
+        let outside = spawn(&mut system, mover(Kind::Slow, Vec2::new(0.0, 20.0)));
+        let id = system.add_time_zone(zone);
+        system.step();
+
+        assert_eq!(system.get(core).unwrap().pos, Vec2::new(-1.0, 0.0));
+        // Half way through the falloff band.
+        assert_eq!(system.get(edge).unwrap().pos, Vec2::new(0.5, 8.0));
+        assert_eq!(system.get(outside).unwrap().pos, Vec2::new(1.0, 20.0));
+
+        system.remove_time_zone(id);
+        system.step();
+        assert_eq!(system.get(core).unwrap().pos, Vec2::ZERO);
+    }
+
+    #[test]
+    fn frozen_particles_keep_their_orientation() {
+        let mut system = ParticleSystem::new();
+        let spawn_state = mover(Kind::Slow, Vec2::ZERO)
+            .with_velocity(Vec2::Y)
+            .with_rotation(30.0)
+            .with_orientation(Orientation::AlignToVelocity)
+            .with_stretch(1.0);
+        let ballistic = spawn(&mut system, spawn_state);
+        system.set_type_paused(Kind::Slow, true);
+        system.step();
+
+        let frozen = system.get(ballistic).unwrap();
+        assert_eq!(frozen.rotation, 30.0);
+        assert_eq!(frozen.size, Vec2::ONE);
+
+        system.set_type_paused(Kind::Slow, false);
+        system.step();
+        let moving = system.get(ballistic).unwrap();
+        assert_ne!(moving.rotation, 30.0);
+        assert_eq!(moving.size, Vec2::new(2.0, 1.0));
+    }
+
+    #[test]
+    fn expired_particles_leave_paused_zones() {
+        let mut system = ParticleSystem::new();
+        system.set_death_events(true);
+        let last = spawn(
+            &mut system,
+            ParticleSpawn::new(Kind::Slow, 1, Vec2::ZERO, Vec2::ONE).with_velocity(Vec2::X),
+        );
+        let alive = spawn(&mut system, mover(Kind::Slow, Vec2::ZERO));
+        system.step();
+        assert_eq!(system.get(last).unwrap().counter, 0);
+
+        system.add_time_zone(TimeDilationZone::new(Vec2::ZERO, 100.0, 0.0));
+        system.step();
+        assert!(!system.contains(last));
+        assert_eq!(system.get(alive).unwrap().counter, 9);
+        let deaths: Vec<_> = system.drain_deaths().collect();
+        assert_eq!(deaths.len(), 1);
+        assert_eq!(deaths[0].cause, DeathCause::Expired);
+    }
+}
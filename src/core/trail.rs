@@ -0,0 +1,357 @@
+use glam::{Affine2, Vec2};
+
+pub(crate) const NO_TRAIL: u32 = u32::MAX;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TrailSampling {
+    // Record a point once the particle has moved this far from the last one.
+    Distance(f32),
+    // Record a point every `n` steps.
+    Steps(u32),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TrailStyle {
+    pub length: usize,
+    pub sampling: TrailSampling,
+    pub width_start: f32,
+    pub width_end: f32,
+    pub alpha_start: f32,
+    pub alpha_end: f32,
+}
+
+impl TrailStyle {
+    pub fn new(length: usize, sampling: TrailSampling) -> Self {
+        Self {
+            length,
+            sampling,
+            width_start: 1.0,
+            width_end: 0.0,
+            alpha_start: 1.0,
+            alpha_end: 0.0,
+        }
+    }
+
+    pub fn with_width(mut self, width_start: f32, width_end: f32) -> Self {
+        self.width_start = width_start;
+        self.width_end = width_end;
+        self
+    }
+
+    pub fn with_alpha(mut self, alpha_start: f32, alpha_end: f32) -> Self {
+        self.alpha_start = alpha_start;
+        self.alpha_end = alpha_end;
+        self
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RibbonVertex {
+    pub pos: Vec2,
+    pub alpha: f32,
+    // Normalized distance along the trail, `0` at the particle and `1` at the tail.
+    pub u: f32,
+}
+
+#[derive(Clone, Debug)]
+struct TrailHistory {
+    style: TrailStyle,
+    points: Vec<Vec2>,
+    head: usize,
+    len: usize,
+    steps_since_sample: u32,
+}
+
+impl TrailHistory {
+    fn reset(&mut self, style: TrailStyle, pos: Vec2) {
+        self.style = style;
+        self.points.clear();
+        self.points.resize(style.length.max(1), pos);
+        self.head = 0;
+        self.len = 1;
+        self.steps_since_sample = 0;
+    }
+
+    fn record(&mut self, pos: Vec2) {
+        self.steps_since_sample += 1;
+        let due = match self.style.sampling {
+            TrailSampling::Distance(distance) => {
+                self.points[self.head].distance_squared(pos) >= distance * distance
+            }
+            TrailSampling::Steps(steps) => self.steps_since_sample >= steps,
+        };
+        if !due {
+            return;
+        }
+
+        self.steps_since_sample = 0;
+        self.head = (self.head + 1) % self.points.len();
+        self.points[self.head] = pos;
+        self.len = (self.len + 1).min(self.points.len());
+    }
+}
+
+// Slots are recycled through a free list so steady-state trail churn does not
+// reallocate history buffers.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct TrailPool {
+    histories: Vec<TrailHistory>,
+    free: Vec<u32>,
+}
+
+impl TrailPool {
+    pub(crate) fn acquire(&mut self, style: TrailStyle, pos: Vec2) -> u32 {
+        if let Some(slot) = self.free.pop() {
+            self.histories[slot as usize].reset(style, pos);
+            return slot;
+        }
+
+        let mut history = TrailHistory {
+            style,
+            points: Vec::with_capacity(style.length.max(1)),
+            head: 0,
+            len: 0,
+            steps_since_sample: 0,
+        };
+        history.reset(style, pos);
+        self.histories.push(history);
+        (self.histories.len() - 1) as u32
+    }
+
+    #[inline(always)]
+    pub(crate) fn release(&mut self, slot: u32) {
+        if slot != NO_TRAIL {
+            self.free.push(slot);
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn record(&mut self, slot: u32, pos: Vec2) {
+        self.histories[slot as usize].record(pos);
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.histories.clear();
+        self.free.clear();
+    }
+
+    // Moves every recorded point, e.g. when the world origin shifts.
+    pub(crate) fn transform(&mut self, transform: Affine2) {
+        for history in &mut self.histories {
+            for point in &mut history.points {
+                *point = transform.transform_point2(*point);
+            }
+        }
+    }
+
+    pub(crate) fn view(&self, slot: u32, pos: Vec2) -> TrailView<'_> {
+        let history = &self.histories[slot as usize];
+        // The newest point is usually the one recorded this step, which is the
+        // particle's position again.
+        let skip = usize::from(history.points[history.head] == pos);
+        TrailView {
+            pos,
+            style: &history.style,
+            points: &history.points,
+            head: (history.head + history.points.len() - skip) % history.points.len(),
+            len: history.len - skip,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TrailView<'a> {
+    pos: Vec2,
+    style: &'a TrailStyle,
+    points: &'a [Vec2],
+    head: usize,
+    len: usize,
+}
+
+impl TrailView<'_> {
+    pub fn style(&self) -> &TrailStyle {
+        self.style
+    }
+
+    // Number of points yielded by `points`, including the live particle position.
+    pub fn len(&self) -> usize {
+        self.len + 1
+    }
+
+    // True while nothing has been recorded away from the particle, leaving no
+    // trail behind it to draw.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Newest first: the live particle position, then recorded history.
+    pub fn points(&self) -> impl Iterator<Item = Vec2> + Clone + '_ {
+        let capacity = self.points.len();
+        std::iter::once(self.pos)
+            .chain((0..self.len).map(move |i| self.points[(self.head + capacity - i) % capacity]))
+    }
+
+    // Appends a triangle strip with two vertices per trail point, tapering width
+    // and alpha from the particle towards the tail.
+    pub fn build_ribbon(&self, out: &mut Vec<RibbonVertex>) {
+        let count = self.len();
+        let last = (count - 1).max(1) as f32;
+        let mut previous: Option<Vec2> = None;
+        let mut ahead: Option<Vec2> = None;
+        let mut iter = self.points();
+        let mut i = 0;
+
+        // Repeated points, e.g. from a particle resting under step sampling,
+        // take their direction from the nearest distinct points either side.
+        while let Some(point) = iter.next() {
+            if previous.is_some_and(|previous| previous != point) {
+                ahead = previous;
+            }
+            let ahead = ahead.unwrap_or(point);
+            let behind = iter.clone().find(|&next| next != point).unwrap_or(point);
+            let normal = (ahead - behind).perp().normalize_or_zero();
+
+            let u = i as f32 / last;
+            let half_width = (self.style.width_start
+                + (self.style.width_end - self.style.width_start) * u)
+                * 0.5;
+            let alpha =
+                self.style.alpha_start + (self.style.alpha_end - self.style.alpha_start) * u;
+
+            out.push(RibbonVertex {
+                pos: point + normal * half_width,
+                alpha,
+                u,
+            });
+            out.push(RibbonVertex {
+                pos: point - normal * half_width,
+                alpha,
+                u,
+            });
+
+            previous = Some(point);
+            i += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec2;
+
+    use super::{RibbonVertex, TrailPool, TrailSampling, TrailStyle};
+    use crate::core::{ParticleSpawn, ParticleSystem, ParticleTypeTrait};
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    enum Streak {
+        Trailed,
+        Plain,
+    }
+
+    impl ParticleTypeTrait for Streak {}
+
+    fn at(x: f32) -> Vec2 {
+        Vec2::new(x, 0.0)
+    }
+
+    #[test]
+    fn history_wraps_newest_first() {
+        let mut pool = TrailPool::default();
+        let slot = pool.acquire(TrailStyle::new(3, TrailSampling::Steps(1)), at(0.0));
+        for x in 1..=5 {
+            pool.record(slot, at(x as f32));
+        }
+
+        let view = pool.view(slot, at(5.0));
+        assert_eq!(view.len(), 3);
+        let points: Vec<_> = view.points().collect();
+        assert_eq!(points, [at(5.0), at(4.0), at(3.0)]);
+
+        // Between samples the live position leads the newest recorded point.
+        let points: Vec<_> = pool.view(slot, at(5.5)).points().collect();
+        assert_eq!(points, [at(5.5), at(5.0), at(4.0), at(3.0)]);
+    }
+
+    #[test]
+    fn fresh_trail_is_empty() {
+        let mut pool = TrailPool::default();
+        let slot = pool.acquire(TrailStyle::new(4, TrailSampling::Distance(2.0)), at(0.0));
+        let view = pool.view(slot, at(0.0));
+        assert!(view.is_empty());
+        assert_eq!(view.points().collect::<Vec<_>>(), [at(0.0)]);
+
+        // Distance sampling waits until the particle is far enough away.
+        pool.record(slot, at(1.5));
+        assert_eq!(pool.view(slot, at(1.5)).len(), 2);
+        pool.record(slot, at(2.5));
+        let points: Vec<_> = pool.view(slot, at(2.5)).points().collect();
+        assert_eq!(points, [at(2.5), at(0.0)]);
+    }
+
+    #[test]
+    fn released_slots_are_reset() {
+        let mut pool = TrailPool::default();
+        let style = TrailStyle::new(4, TrailSampling::Steps(1));
+        let slot = pool.acquire(style, at(0.0));
+        pool.record(slot, at(1.0));
+        pool.release(slot);
+
+        assert_eq!(pool.acquire(style, at(10.0)), slot);
+        let points: Vec<_> = pool.view(slot, at(10.0)).points().collect();
+        assert_eq!(points, [at(10.0)]);
+    }
+
+    #[test]
+    fn ribbon_tapers_along_the_trail() {
+        let mut pool = TrailPool::default();
+        let style = TrailStyle::new(5, TrailSampling::Steps(1))
+            .with_width(2.0, 0.0)
+            .with_alpha(1.0, 0.5);
+        let slot = pool.acquire(style, at(0.0));
+        for x in [1.0, 2.0, 2.0, 3.0] {
+            pool.record(slot, at(x));
+        }
+
+        let mut out = Vec::new();
+        pool.view(slot, at(3.0)).build_ribbon(&mut out);
+        assert_eq!(out.len(), 10);
+        for pair in out.chunks(2) {
+            assert!(pair.iter().all(|vertex| vertex.pos.is_finite()));
+            assert_eq!(pair[0].u, pair[1].u);
+            assert_eq!(pair[0].alpha, pair[1].alpha);
+            // A straight trail along x spreads its width across y.
+            assert_eq!(pair[0].pos.x, pair[1].pos.x);
+            assert!((pair[0].pos.y + pair[1].pos.y).abs() < 1e-6);
+        }
+        let head = (out[0], out[1]);
+        assert_eq!(head.0.pos, Vec2::new(3.0, 1.0));
+        assert_eq!(head.1.pos, Vec2::new(3.0, -1.0));
+        assert_eq!((head.0.u, head.0.alpha), (0.0, 1.0));
+        let tail: &RibbonVertex = &out[8];
+        assert_eq!((tail.pos, tail.u, tail.alpha), (at(0.0), 1.0, 0.5));
+        // The repeated point still takes its width from its neighbours.
+        assert!((out[4].pos.y.abs() - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn only_styled_types_get_trails() {
+        let mut system = ParticleSystem::new();
+        system.set_trail_style(Streak::Trailed, TrailStyle::new(8, TrailSampling::Steps(1)));
+        let spawn = |particle_type| {
+            ParticleSpawn::new(particle_type, 100, Vec2::ZERO, Vec2::ONE).with_velocity(Vec2::X)
+        };
+        system.spawn(spawn(Streak::Trailed));
+        system.spawn(spawn(Streak::Plain));
+        for _ in 0..4 {
+            system.step();
+        }
+
+        let mut trails = Vec::new();
+        system.for_each_trail(|particle, trail| {
+            trails.push((particle.particle_type, trail.points().collect::<Vec<_>>()));
+        });
+        assert_eq!(trails.len(), 1);
+        assert_eq!(trails[0].0, Streak::Trailed);
+        assert_eq!(trails[0].1, [at(4.0), at(3.0), at(2.0), at(1.0), at(0.0)]);
+    }
+}
@@ -1,61 +1,38 @@
 use glam::IVec2;
 
-use ptcl_rs::core::ParticleTypeTrait;
+use ptcl_rs::core::{AtlasRegion, ParticleTypeTrait};
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum ParticleType {
     Smoke,
     Explosion,
     BloodBall,
 }
 
-impl ParticleTypeTrait for ParticleType {}
-
-pub struct SampleRegion {
-    pub pos: IVec2,
-    pub size: IVec2,
-}
-
-const SMOKE: SampleRegion = SampleRegion {
-    pos: IVec2 { x: 0, y: 269 },
-    size: IVec2 { x: 65, y: 61 },
-};
-
-const EXPLOSION_FRAME_1: SampleRegion = SampleRegion {
-    pos: IVec2 { x: 0, y: 164 },
-    size: IVec2 { x: 45, y: 42 },
-};
-
-const EXPLOSION_FRAME_2: SampleRegion = SampleRegion {
-    pos: IVec2 { x: 0, y: 40 },
-    size: IVec2 { x: 62, y: 60 },
-};
-
-const EXPLOSION_FRAME_3: SampleRegion = SampleRegion {
-    pos: IVec2 { x: 0, y: 101 },
-    size: IVec2 { x: 61, y: 62 },
-};
-
-const EXPLOSION_FRAME_4: SampleRegion = SampleRegion {
-    pos: IVec2 { x: 0, y: 206 },
-    size: IVec2 { x: 65, y: 61 },
-};
-
-const BLOOD_BALL: SampleRegion = SampleRegion {
-    pos: IVec2 { x: 63, y: 38 },
-    size: IVec2 { x: 16, y: 17 },
-};
-
-pub fn get_sample_region(particle_type: ParticleType, counter: u32) -> &'static SampleRegion {
-    match particle_type {
-        ParticleType::Explosion => match counter {
-            6..=7 => &EXPLOSION_FRAME_1,
-            4..=5 => &EXPLOSION_FRAME_2,
-            2..=3 => &EXPLOSION_FRAME_3,
-            0..=1 => &EXPLOSION_FRAME_4,
-            _ => &BLOOD_BALL,
-        },
-        ParticleType::Smoke => &SMOKE,
-        ParticleType::BloodBall => &BLOOD_BALL,
+impl ParticleTypeTrait for ParticleType {
+    fn atlas_region(self, counter: u32) -> Option<AtlasRegion> {
+        Some(match self {
+            ParticleType::Explosion => match counter {
+                6..=7 => EXPLOSION_FRAME_1,
+                4..=5 => EXPLOSION_FRAME_2,
+                2..=3 => EXPLOSION_FRAME_3,
+                0..=1 => EXPLOSION_FRAME_4,
+                _ => BLOOD_BALL,
+            },
+            ParticleType::Smoke => SMOKE,
+            ParticleType::BloodBall => BLOOD_BALL,
+        })
     }
 }
+
+const SMOKE: AtlasRegion = AtlasRegion::new(IVec2::new(0, 269), IVec2::new(65, 61));
+
+const EXPLOSION_FRAME_1: AtlasRegion = AtlasRegion::new(IVec2::new(0, 164), IVec2::new(45, 42));
+
+const EXPLOSION_FRAME_2: AtlasRegion = AtlasRegion::new(IVec2::new(0, 40), IVec2::new(62, 60));
+
+const EXPLOSION_FRAME_3: AtlasRegion = AtlasRegion::new(IVec2::new(0, 101), IVec2::new(61, 62));
+
+const EXPLOSION_FRAME_4: AtlasRegion = AtlasRegion::new(IVec2::new(0, 206), IVec2::new(65, 61));
+
+const BLOOD_BALL: AtlasRegion = AtlasRegion::new(IVec2::new(63, 38), IVec2::new(16, 17));
@@ -2,19 +2,26 @@ use glam::Vec2;
 use rand::{rngs::SmallRng, Rng, SeedableRng};
 use raylib::prelude::*;
 
-use ptcl_rs::core::{ParticleSpawn, ParticleSystem, SplineState};
+use ptcl_rs::core::{
+    AnchorId, EffectInstance, Emitter, OverflowPolicy, ParticleBudget, ParticleSpawn,
+    ParticleSystem, ParticleTypeTrait, SimulationClock, SplineState,
+};
 
-use crate::demo_particles::{get_sample_region, ParticleType};
+use crate::demo_particles::ParticleType;
 
 pub const FRAMES_PER_SECOND: u32 = 60;
+// The smoke lives up to 1000 steps, so this brings the emitters to steady state.
+const PREWARM_SECONDS: u32 = 16;
 
 pub struct State {
     pub running: bool,
-    pub time_since_last_update: f32,
+    pub clock: SimulationClock,
     pub sim_time: f32,
     pub sim_dims: Vec2,
     pub particle_system: ParticleSystem<ParticleType>,
     pub particle_effects_texture: Texture2D,
+    cursor_anchor: AnchorId,
+    rotating_emitters: EffectInstance<RotatingEmitters>,
     rng: SmallRng,
     ballistic_batch: Vec<ParticleSpawn<ParticleType>>,
     spline_batch: Vec<ParticleSpawn<ParticleType>>,
@@ -27,15 +34,23 @@ impl State {
         let particle_effects_texture = rl.load_texture(rlt, path).expect(texture_error);
 
         let mut particle_system = ParticleSystem::new();
-        particle_system.reserve_particles(120_000);
+        particle_system.set_budget(ParticleBudget::new(120_000, OverflowPolicy::EvictOldest));
+        particle_system.reserve_budget();
+        particle_system.set_interpolation(true);
+        let cursor_anchor = particle_system.add_anchor(sim_dims / 2.0);
+
+        let mut rotating_emitters = EffectInstance::new(RotatingEmitters::new(sim_dims));
+        rotating_emitters.prewarm(&mut particle_system, PREWARM_SECONDS * FRAMES_PER_SECOND);
 
         Self {
             running: true,
-            time_since_last_update: 0.0,
+            clock: SimulationClock::new(1.0 / FRAMES_PER_SECOND as f32),
             sim_time: 0.0,
             sim_dims,
             particle_system,
             particle_effects_texture,
+            cursor_anchor,
+            rotating_emitters,
             rng: SmallRng::from_os_rng(),
             ballistic_batch: Vec::with_capacity(1_600),
             spline_batch: Vec::with_capacity(1_600),
@@ -47,6 +62,17 @@ pub fn process_events_and_input(rl: &mut RaylibHandle, state: &mut State) {
     if rl.is_key_pressed(raylib::consts::KeyboardKey::KEY_ESCAPE) {
         state.running = false;
     }
+    if rl.is_key_pressed(raylib::consts::KeyboardKey::KEY_P) {
+        state.clock.toggle_pause();
+    }
+    if rl.is_key_pressed(raylib::consts::KeyboardKey::KEY_PERIOD) {
+        state.clock.step_once();
+    }
+
+    let mouse_pos = rl.get_mouse_position();
+    state
+        .particle_system
+        .set_anchor(state.cursor_anchor, Vec2::new(mouse_pos.x, mouse_pos.y));
 
     if rl.is_mouse_button_pressed(raylib::consts::MouseButton::MOUSE_BUTTON_LEFT) {
         spawn_click_burst(state, rl.get_mouse_position());
@@ -55,39 +81,44 @@ pub fn process_events_and_input(rl: &mut RaylibHandle, state: &mut State) {
 
 pub fn step(state: &mut State, dt: f32) {
     state.sim_time += dt;
-    spawn_rotating_emitters(state);
+    state.particle_system.emit(&mut state.rotating_emitters);
     state.particle_system.step();
 }
 
-pub fn draw(state: &mut State, d: &mut RaylibTextureMode<RaylibDrawHandle>) {
+// `alpha` is how far the frame sits between the last two simulation steps.
+pub fn draw(state: &mut State, alpha: f32, d: &mut RaylibTextureMode<RaylibDrawHandle>) {
     let mouse_pos = d.get_mouse_position();
     d.draw_circle(mouse_pos.x as i32, mouse_pos.y as i32, 6.0, Color::GREEN);
-    draw_particles(state, d);
+    draw_particles(state, alpha, d);
 }
 
-pub fn draw_particles(state: &State, d: &mut RaylibTextureMode<RaylibDrawHandle>) {
-    state.particle_system.for_each_particle(|particle| {
-        let sample_region = get_sample_region(particle.particle_type, particle.counter);
-        let color = Color::new(255, 255, 255, (particle.alpha * 255.0) as u8);
-        d.draw_texture_pro(
-            &state.particle_effects_texture,
-            Rectangle::new(
-                sample_region.pos.x as f32,
-                sample_region.pos.y as f32,
-                sample_region.size.x as f32,
-                sample_region.size.y as f32,
-            ),
-            Rectangle::new(
-                particle.pos.x,
-                particle.pos.y,
-                particle.size.x,
-                particle.size.y,
-            ),
-            Vector2::new(particle.size.x / 2.0, particle.size.y / 2.0),
-            particle.rotation,
-            color,
-        );
-    });
+pub fn draw_particles(state: &State, alpha: f32, d: &mut RaylibTextureMode<RaylibDrawHandle>) {
+    state
+        .particle_system
+        .for_each_particle_interpolated(alpha, |particle| {
+            let Some(sample_region) = particle.particle_type.atlas_region(particle.counter) else {
+                return;
+            };
+            let color = Color::new(255, 255, 255, (particle.alpha * 255.0) as u8);
+            d.draw_texture_pro(
+                &state.particle_effects_texture,
+                Rectangle::new(
+                    sample_region.pos.x as f32,
+                    sample_region.pos.y as f32,
+                    sample_region.size.x as f32,
+                    sample_region.size.y as f32,
+                ),
+                Rectangle::new(
+                    particle.pos.x,
+                    particle.pos.y,
+                    particle.size.x,
+                    particle.size.y,
+                ),
+                Vector2::new(particle.size.x / 2.0, particle.size.y / 2.0),
+                particle.rotation,
+                color,
+            );
+        });
 }
 
 fn spawn_click_burst(state: &mut State, mouse_pos: Vector2) {
@@ -120,8 +151,9 @@ fn spawn_click_burst(state: &mut State, mouse_pos: Vector2) {
                     strength: 1.0,
                     point_1: a,
                     point_2: b,
-                    point_3: center,
+                    point_3: Vec2::ZERO,
                 })
+                .with_spline_end_anchor(state.cursor_anchor)
                 .with_spline_velocity(state.rng.random_range(0.01..0.02))
                 .with_spline_acceleration(state.rng.random_range(-0.0005..0.000))
                 .with_size_velocity(state.rng.random_range(-0.5..0.0)),
@@ -186,71 +218,90 @@ fn spawn_click_burst(state: &mut State, mouse_pos: Vector2) {
         .spawn_ballistic_batch(state.ballistic_batch.drain(..));
 }
 
-fn spawn_rotating_emitters(state: &mut State) {
-    let angle = state.sim_time * 4.0;
-
-    let mut center = state.sim_dims / 2.0;
-    center.y += center.y / 2.0;
-    let offset = center / 8.0;
-
-    state.ballistic_batch.clear();
-
-    for i in 0..3 {
-        let rot = glam::Mat2::from_angle(angle + i as f32 * 90.0);
-        let rect_pos_rotated = rot * offset + center;
-
-        let sprite_size = (((state.sim_time + i as f32) * 2.0).sin() + 1.0) / 2.0 * offset.y + 4.0;
-        let rect_center = rect_pos_rotated + sprite_size / 2.0;
-
-        for _ in 0..8 {
-            let counter = state.rng.random_range(8..24);
-            let max_size = sprite_size / 2.0;
-            let size_v = state.rng.random_range(1.0..max_size);
-            let size = Vec2::new(size_v, size_v);
+// Three emitters circling a point below the screen center, shedding sparks
+// and long-lived smoke.
+pub struct RotatingEmitters {
+    time: f32,
+    dt: f32,
+    center: Vec2,
+    offset: Vec2,
+    rng: SmallRng,
+}
 
-            let mag = 0.1;
-            let vel = Vec2::new(
-                state.rng.random_range(-mag..mag),
-                state.rng.random_range(-mag..mag),
-            );
+impl RotatingEmitters {
+    pub fn new(sim_dims: Vec2) -> Self {
+        let mut center = sim_dims / 2.0;
+        center.y += center.y / 2.0;
 
-            state.ballistic_batch.push(
-                ParticleSpawn::new(ParticleType::Explosion, counter, rect_center, size)
-                    .with_alpha_velocity(-0.05)
-                    .with_velocity(vel)
-                    .with_acceleration(Vec2::new(0.0, 0.1)),
-            );
+        Self {
+            time: 0.0,
+            dt: 1.0 / FRAMES_PER_SECOND as f32,
+            center,
+            offset: center / 8.0,
+            rng: SmallRng::from_os_rng(),
         }
+    }
+}
 
-        for _ in 0..4 {
-            let counter = state.rng.random_range(60..1000);
-            let max_size = sprite_size / 2.0;
-            let size_v = state.rng.random_range(1.0..max_size);
-            let size = Vec2::new(size_v, size_v);
-
-            let x_mag = 0.1;
-            let y_mag = 0.5;
-            let vel = Vec2::new(
-                state.rng.random_range(-x_mag..x_mag),
-                state.rng.random_range(0.0..y_mag),
-            );
-
-            let spin_mag = 2.0;
-            state.ballistic_batch.push(
-                ParticleSpawn::new(ParticleType::Smoke, counter, rect_center, size)
-                    .with_alpha(0.1)
-                    .with_alpha_velocity(-0.001)
-                    .with_velocity(vel)
-                    .with_acceleration(Vec2::new(0.0, -0.1))
-                    .with_size_velocity(1.0)
-                    .with_size_acceleration(-0.01)
-                    .with_rotation_velocity(state.rng.random_range(-spin_mag..spin_mag))
-                    .with_rotation_acceleration(-0.01),
-            );
+impl Emitter<ParticleType> for RotatingEmitters {
+    fn emit(&mut self, out: &mut Vec<ParticleSpawn<ParticleType>>) {
+        self.time += self.dt;
+        let angle = self.time * 4.0;
+        let offset = self.offset;
+
+        for i in 0..3 {
+            let rot = glam::Mat2::from_angle(angle + i as f32 * 90.0);
+            let rect_pos_rotated = rot * offset + self.center;
+
+            let sprite_size = (((self.time + i as f32) * 2.0).sin() + 1.0) / 2.0 * offset.y + 4.0;
+            let rect_center = rect_pos_rotated + sprite_size / 2.0;
+
+            for _ in 0..8 {
+                let counter = self.rng.random_range(8..24);
+                let max_size = sprite_size / 2.0;
+                let size_v = self.rng.random_range(1.0..max_size);
+                let size = Vec2::new(size_v, size_v);
+
+                let mag = 0.1;
+                let vel = Vec2::new(
+                    self.rng.random_range(-mag..mag),
+                    self.rng.random_range(-mag..mag),
+                );
+
+                out.push(
+                    ParticleSpawn::new(ParticleType::Explosion, counter, rect_center, size)
+                        .with_alpha_velocity(-0.05)
+                        .with_velocity(vel)
+                        .with_acceleration(Vec2::new(0.0, 0.1)),
+                );
+            }
+
+            for _ in 0..4 {
+                let counter = self.rng.random_range(60..1000);
+                let max_size = sprite_size / 2.0;
+                let size_v = self.rng.random_range(1.0..max_size);
+                let size = Vec2::new(size_v, size_v);
+
+                let x_mag = 0.1;
+                let y_mag = 0.5;
+                let vel = Vec2::new(
+                    self.rng.random_range(-x_mag..x_mag),
+                    self.rng.random_range(0.0..y_mag),
+                );
+
+                let spin_mag = 2.0;
+                out.push(
+                    ParticleSpawn::new(ParticleType::Smoke, counter, rect_center, size)
+                        .with_alpha(0.1)
+                        .with_alpha_velocity(-0.001)
+                        .with_velocity(vel)
+                        .with_acceleration(Vec2::new(0.0, -0.1))
+                        .with_size_velocity(1.0)
+                        .with_size_acceleration(-0.01)
+                        .with_rotation_velocity(self.rng.random_range(-spin_mag..spin_mag))
+                        .with_rotation_acceleration(-0.01),
+                );
+            }
         }
     }
-
-    state
-        .particle_system
-        .spawn_ballistic_batch(state.ballistic_batch.drain(..));
 }
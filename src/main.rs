@@ -5,8 +5,6 @@ use raylib::{ffi::SetTraceLogLevel, prelude::TraceLogLevel};
 mod demo_particles;
 mod demo_scene;
 
-const TIMESTEP: f32 = 1.0 / demo_scene::FRAMES_PER_SECOND as f32;
-
 fn main() {
     let (mut rl, rlt) = raylib::init().title("ptcl-rs demo").build();
 
@@ -39,19 +37,10 @@ fn main() {
     while state.running && !rl.window_should_close() {
         demo_scene::process_events_and_input(&mut rl, &mut state);
 
-        let dt = rl.get_frame_time();
-        state.time_since_last_update += dt;
-
-        // Prevent unbounded catch-up spirals under heavy load.
-        let mut sim_steps = 0;
-        while state.time_since_last_update > TIMESTEP && sim_steps < 4 {
-            state.time_since_last_update -= TIMESTEP;
-            demo_scene::step(&mut state, TIMESTEP);
-            sim_steps += 1;
-        }
-
-        if sim_steps == 4 {
-            state.time_since_last_update = 0.0;
+        let frame = state.clock.advance(rl.get_frame_time());
+        let timestep = state.clock.timestep();
+        for _ in 0..frame.steps {
+            demo_scene::step(&mut state, timestep);
         }
 
         let mut draw_handle = rl.begin_drawing(&rlt);
@@ -59,7 +48,7 @@ fn main() {
             let low_res_draw_handle =
                 &mut draw_handle.begin_texture_mode(&rlt, &mut render_texture);
             low_res_draw_handle.clear_background(Color::BLACK);
-            demo_scene::draw(&mut state, low_res_draw_handle);
+            demo_scene::draw(&mut state, frame.alpha, low_res_draw_handle);
         }
 
         scale_and_blit_render_texture_to_window(